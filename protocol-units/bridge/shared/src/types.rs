@@ -61,8 +61,48 @@ pub struct HashLockPreImage(pub Vec<u8>);
 #[derive(Deref, Debug, Clone, PartialEq, Eq)]
 pub struct TimeLock(pub u64);
 
-#[derive(Deref, DerefMut, Debug, Clone, Copy, PartialEq, Eq)]
-pub struct Amount(pub u64);
+/// A token amount in base units, the smallest indivisible unit of the token.
+///
+/// Tokens on either side of the bridge may use different decimals, so conversions to and
+/// from whole tokens always take the decimals explicitly.
+#[derive(Deref, DerefMut, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Amount(pub u128);
+
+impl Amount {
+	/// The amount of `whole` tokens with `decimals` decimals, or `None` on overflow.
+	pub fn from_whole(whole: u128, decimals: u32) -> Option<Self> {
+		10u128.checked_pow(decimals)?.checked_mul(whole).map(Amount)
+	}
+
+	/// The amount in whole tokens with `decimals` decimals, or `None` if it is not a whole
+	/// number of tokens.
+	pub fn to_whole(&self, decimals: u32) -> Option<u128> {
+		let unit = 10u128.checked_pow(decimals)?;
+		(self.0 % unit == 0).then(|| self.0 / unit)
+	}
+
+	pub fn checked_add(self, other: Amount) -> Option<Self> {
+		self.0.checked_add(other.0).map(Amount)
+	}
+
+	pub fn checked_sub(self, other: Amount) -> Option<Self> {
+		self.0.checked_sub(other.0).map(Amount)
+	}
+}
+
+impl From<u64> for Amount {
+	fn from(base_units: u64) -> Self {
+		Amount(base_units.into())
+	}
+}
+
+impl TryFrom<Amount> for u64 {
+	type Error = std::num::TryFromIntError;
+
+	fn try_from(amount: Amount) -> Result<Self, Self::Error> {
+		u64::try_from(amount.0)
+	}
+}
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct BridgeTransferDetails<A, H> {
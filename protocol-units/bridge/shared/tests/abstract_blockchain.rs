@@ -14,10 +14,12 @@ mod shared;
 use shared::testing::blockchain::{
 	AbstractBlockchain, AbstractBlockchainEvent, CounterpartyCall, InitiatorCall, Transaction,
 };
+use shared::testing::eth::{EthAbstractBlockchain, EthAddress, EthHash};
+use shared::testing::rng::TestRng;
 
 use crate::shared::testing::blockchain::{
-	counterparty_contract::SmartContractCounterpartyEvent,
-	initiator_contract::SmartContractInitiatorEvent,
+	counterparty_contract::{SmartContractCounterpartyError, SmartContractCounterpartyEvent},
+	initiator_contract::{SmartContractInitiatorError, SmartContractInitiatorEvent},
 };
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -39,8 +41,8 @@ impl From<RecipientAddress> for TestAddress {
 }
 
 impl From<HashLockPreImage> for TestHash {
-	fn from(_value: HashLockPreImage) -> Self {
-		todo!()
+	fn from(value: HashLockPreImage) -> Self {
+		Self(static_str_ops::staticize(&String::from_utf8(value.0).expect("Invalid UTF-8")))
 	}
 }
 
@@ -62,6 +64,7 @@ async fn test_initiate_bridge_transfer() {
 	let amount = Amount(1000);
 	let time_lock = TimeLock(100);
 	let hash_lock = HashLock(TestHash("hash_lock"));
+	blockchain.add_account(initiator_address.0.clone(), amount);
 
 	let transaction = Transaction::Initiator(InitiatorCall::InitiateBridgeTransfer(
 		initiator_address.clone(),
@@ -77,7 +80,7 @@ async fn test_initiate_bridge_transfer() {
 	let monitor_event = monitor.next().await;
 	assert!(event.is_some());
 	assert!(monitor_event.is_some());
-	assert_eq!(event, monitor_event);
+	assert_eq!(event.as_ref(), monitor_event.as_deref());
 
 	let event = event.unwrap();
 	assert_eq!(
@@ -135,7 +138,7 @@ async fn test_lock_bridge_transfer() {
 	let monitor_event = monitor.next().await;
 	assert!(monitor_event.is_some());
 	assert!(event.is_some());
-	assert_eq!(event, monitor_event);
+	assert_eq!(event.as_ref(), monitor_event.as_deref());
 
 	let event = event.unwrap();
 	assert_eq!(
@@ -161,3 +164,360 @@ async fn test_lock_bridge_transfer() {
 	assert_eq!(details.time_lock, time_lock);
 	assert_eq!(details.amount, amount);
 }
+
+#[test]
+fn test_step_initiate_and_complete_bridge_transfer() {
+	let rng = ChaChaRng::from_seed([0u8; 32]);
+	let mut blockchain = AbstractBlockchain::<TestAddress, TestHash, _>::new(rng, "TestBlockchain");
+
+	assert_eq!(blockchain.step(), None);
+
+	let initiator_address = InitiatorAddress(TestAddress("initiator"));
+	let recipient_address = RecipientAddress::from(TestAddress("recipient"));
+	let amount = Amount(1000);
+	let time_lock = TimeLock(100);
+	let hash_lock = HashLock(TestHash("secret"));
+	blockchain.add_account(initiator_address.0.clone(), amount);
+
+	blockchain
+		.transaction_sender
+		.unbounded_send(Transaction::Initiator(InitiatorCall::InitiateBridgeTransfer(
+			initiator_address.clone(),
+			recipient_address.clone(),
+			amount,
+			time_lock.clone(),
+			hash_lock.clone(),
+		)))
+		.unwrap();
+
+	let bridge_transfer_id = BridgeTransferId(TestHash("unique_hash"));
+	assert_eq!(
+		blockchain.step(),
+		Some(AbstractBlockchainEvent::InitiatorContractEvent(Ok(
+			SmartContractInitiatorEvent::InitiatedBridgeTransfer(BridgeTransferDetails {
+				bridge_transfer_id: bridge_transfer_id.clone(),
+				initiator_address,
+				recipient_address,
+				amount,
+				time_lock,
+				hash_lock,
+			})
+		)))
+	);
+	assert_eq!(blockchain.step(), None);
+
+	let secret = HashLockPreImage(b"secret".to_vec());
+	blockchain
+		.transaction_sender
+		.unbounded_send(Transaction::Initiator(InitiatorCall::CompleteBridgeTransfer(
+			bridge_transfer_id.clone(),
+			secret.clone(),
+		)))
+		.unwrap();
+
+	assert_eq!(
+		blockchain.step(),
+		Some(AbstractBlockchainEvent::InitiatorContractEvent(Ok(
+			SmartContractInitiatorEvent::CompletedBridgeTransfer(bridge_transfer_id, secret)
+		)))
+	);
+	assert_eq!(blockchain.step(), None);
+
+	// a completed transfer is no longer refunded once its time lock expires
+	assert!(blockchain.initiator_contract.initiated_transfers.is_empty());
+	blockchain.forward_time(200);
+	assert!(blockchain.sweep_expired_transfers().is_empty());
+	assert_eq!(blockchain.get_balance(&TestAddress("initiator")), Some(&Amount(0)));
+}
+
+#[test]
+fn test_eth_initiate_lock_and_complete_bridge_transfer() {
+	let mut initiator_chain = EthAbstractBlockchain::new(TestRng::from_seed([0u8; 32]), "Eth1");
+	let mut counterparty_chain = EthAbstractBlockchain::new(TestRng::from_seed([1u8; 32]), "Eth2");
+
+	let initiator_address = InitiatorAddress(EthAddress([1; 20]));
+	let recipient = EthAddress([2; 20]);
+	let amount = Amount(1000);
+	let time_lock = TimeLock(100);
+	let secret = HashLockPreImage(b"secret".to_vec());
+	let hash_lock = HashLock(EthHash::from(secret.clone()));
+	initiator_chain.add_account(initiator_address.0, amount);
+
+	initiator_chain
+		.transaction_sender
+		.unbounded_send(Transaction::Initiator(InitiatorCall::InitiateBridgeTransfer(
+			initiator_address,
+			RecipientAddress::from(recipient),
+			amount,
+			time_lock.clone(),
+			hash_lock.clone(),
+		)))
+		.unwrap();
+	let bridge_transfer_id = match initiator_chain.step() {
+		Some(AbstractBlockchainEvent::InitiatorContractEvent(Ok(
+			SmartContractInitiatorEvent::InitiatedBridgeTransfer(details),
+		))) => details.bridge_transfer_id,
+		event => panic!("unexpected event: {event:?}"),
+	};
+
+	for call in [
+		CounterpartyCall::LockBridgeTransfer(
+			bridge_transfer_id.clone(),
+			hash_lock,
+			time_lock,
+			RecipientAddress::from(recipient),
+			amount,
+		),
+		CounterpartyCall::CompleteBridgeTransfer(bridge_transfer_id.clone(), secret.clone()),
+	] {
+		counterparty_chain
+			.transaction_sender
+			.unbounded_send(Transaction::Counterparty(call))
+			.unwrap();
+	}
+	assert!(matches!(
+		counterparty_chain.step(),
+		Some(AbstractBlockchainEvent::CounterpartyContractEvent(Ok(
+			SmartContractCounterpartyEvent::LockedBridgeTransfer(_)
+		)))
+	));
+	assert!(matches!(
+		counterparty_chain.step(),
+		Some(AbstractBlockchainEvent::CounterpartyContractEvent(Ok(
+			SmartContractCounterpartyEvent::CompletedBridgeTransfer(_)
+		)))
+	));
+	assert_eq!(counterparty_chain.get_balance(&recipient), Some(&amount));
+
+	// the secret revealed on the counterparty chain completes the transfer on the initiator chain
+	initiator_chain
+		.transaction_sender
+		.unbounded_send(Transaction::Initiator(InitiatorCall::CompleteBridgeTransfer(
+			bridge_transfer_id.clone(),
+			secret.clone(),
+		)))
+		.unwrap();
+	assert_eq!(
+		initiator_chain.step(),
+		Some(AbstractBlockchainEvent::InitiatorContractEvent(Ok(
+			SmartContractInitiatorEvent::CompletedBridgeTransfer(bridge_transfer_id, secret)
+		)))
+	);
+}
+
+#[test]
+fn test_dropped_event_listeners_are_unregistered() {
+	let rng = ChaChaRng::from_seed([0u8; 32]);
+	let mut blockchain = AbstractBlockchain::<TestAddress, TestHash, _>::new(rng, "TestBlockchain");
+	blockchain.add_account(TestAddress("initiator"), Amount(1000));
+
+	let mut listeners: Vec<_> = (0..100).map(|_| blockchain.add_event_listener()).collect();
+	assert_eq!(blockchain.event_listeners.len(), 100);
+
+	listeners.truncate(10);
+	blockchain.prune_event_listeners();
+	assert_eq!(blockchain.event_listeners.len(), 10);
+
+	// dropped listeners are also unregistered when an event is emitted
+	listeners.truncate(5);
+	blockchain
+		.transaction_sender
+		.unbounded_send(Transaction::Initiator(InitiatorCall::InitiateBridgeTransfer(
+			InitiatorAddress(TestAddress("initiator")),
+			RecipientAddress::from(TestAddress("recipient")),
+			Amount(1000),
+			TimeLock(100),
+			HashLock(TestHash("secret")),
+		)))
+		.unwrap();
+	let event = blockchain.step().expect("event emitted");
+	assert_eq!(blockchain.event_listeners.len(), 5);
+
+	for listener in &mut listeners {
+		assert_eq!(listener.try_next().unwrap().as_deref(), Some(&event));
+	}
+}
+
+#[test]
+fn test_listeners_share_a_single_event_allocation() {
+	let rng = ChaChaRng::from_seed([0u8; 32]);
+	let mut blockchain = AbstractBlockchain::<TestAddress, TestHash, _>::new(rng, "TestBlockchain");
+	blockchain.add_account(TestAddress("initiator"), Amount(1000));
+
+	let mut listeners: Vec<_> = (0..3).map(|_| blockchain.add_event_listener()).collect();
+	blockchain
+		.transaction_sender
+		.unbounded_send(Transaction::Initiator(InitiatorCall::InitiateBridgeTransfer(
+			InitiatorAddress(TestAddress("initiator")),
+			RecipientAddress::from(TestAddress("recipient")),
+			Amount(1000),
+			TimeLock(100),
+			HashLock(TestHash("secret")),
+		)))
+		.unwrap();
+	let event = blockchain.step().expect("event emitted");
+
+	let received: Vec<_> = listeners
+		.iter_mut()
+		.map(|listener| listener.try_next().unwrap().expect("listener received the event"))
+		.collect();
+	for shared in &received {
+		assert_eq!(**shared, event);
+		assert!(std::sync::Arc::ptr_eq(shared, &received[0]));
+	}
+}
+
+#[test]
+fn test_complete_bridge_transfer_credits_recipient_balance() {
+	let rng = ChaChaRng::from_seed([0u8; 32]);
+	let mut blockchain = AbstractBlockchain::<TestAddress, TestHash, _>::new(rng, "TestBlockchain");
+
+	let recipient = TestAddress("recipient");
+	blockchain.add_account(recipient.clone(), Amount::from_whole(1, 8).unwrap());
+	blockchain.add_account(TestAddress("whale"), Amount(u128::MAX));
+
+	let lock_and_complete = |blockchain: &mut AbstractBlockchain<_, _, _>, to: TestAddress| {
+		let bridge_transfer_id = BridgeTransferId(TestHash(to.0));
+		for call in [
+			CounterpartyCall::LockBridgeTransfer(
+				bridge_transfer_id.clone(),
+				HashLock(TestHash("secret")),
+				TimeLock(100),
+				RecipientAddress::from(to),
+				Amount::from_whole(2, 8).unwrap(),
+			),
+			CounterpartyCall::CompleteBridgeTransfer(
+				bridge_transfer_id,
+				HashLockPreImage(b"secret".to_vec()),
+			),
+		] {
+			blockchain
+				.transaction_sender
+				.unbounded_send(Transaction::Counterparty(call))
+				.unwrap();
+		}
+		blockchain.step().expect("lock event");
+		blockchain.step().expect("complete event")
+	};
+
+	let event = lock_and_complete(&mut blockchain, recipient.clone());
+	assert!(matches!(
+		event,
+		AbstractBlockchainEvent::CounterpartyContractEvent(Ok(
+			SmartContractCounterpartyEvent::CompletedBridgeTransfer(_)
+		))
+	));
+	assert_eq!(blockchain.get_balance(&recipient), Some(&Amount::from_whole(3, 8).unwrap()));
+
+	let event = lock_and_complete(&mut blockchain, TestAddress("whale"));
+	assert_eq!(
+		event,
+		AbstractBlockchainEvent::CounterpartyContractEvent(Err(
+			SmartContractCounterpartyError::BalanceOverflow
+		))
+	);
+	assert_eq!(blockchain.get_balance(&TestAddress("whale")), Some(&Amount(u128::MAX)));
+}
+
+#[test]
+fn test_initiate_bridge_transfer_debits_initiator() {
+	let rng = ChaChaRng::from_seed([0u8; 32]);
+	let mut blockchain = AbstractBlockchain::<TestAddress, TestHash, _>::new(rng, "TestBlockchain");
+
+	let initiator = TestAddress("initiator");
+	blockchain.add_account(initiator.clone(), Amount(1500));
+	for _ in 0..2 {
+		blockchain
+			.transaction_sender
+			.unbounded_send(Transaction::Initiator(InitiatorCall::InitiateBridgeTransfer(
+				InitiatorAddress(initiator.clone()),
+				RecipientAddress::from(TestAddress("recipient")),
+				Amount(1000),
+				TimeLock(100),
+				HashLock(TestHash("secret")),
+			)))
+			.unwrap();
+	}
+
+	assert!(matches!(
+		blockchain.step(),
+		Some(AbstractBlockchainEvent::InitiatorContractEvent(Ok(
+			SmartContractInitiatorEvent::InitiatedBridgeTransfer(_)
+		)))
+	));
+	assert_eq!(blockchain.get_balance(&initiator), Some(&Amount(500)));
+
+	// the second transfer exceeds the remaining balance and leaves it untouched
+	assert_eq!(
+		blockchain.step(),
+		Some(AbstractBlockchainEvent::InitiatorContractEvent(Err(
+			SmartContractInitiatorError::InsufficientBalance
+		)))
+	);
+	assert_eq!(blockchain.get_balance(&initiator), Some(&Amount(500)));
+	assert_eq!(blockchain.initiator_contract.initiated_transfers.len(), 1);
+}
+
+#[test]
+fn test_sweep_expired_transfers() {
+	let rng = ChaChaRng::from_seed([0u8; 32]);
+	let mut blockchain = AbstractBlockchain::<TestAddress, TestHash, _>::new(rng, "TestBlockchain");
+
+	let initiator = TestAddress("initiator");
+	blockchain.add_account(initiator.clone(), Amount(1500));
+	blockchain
+		.transaction_sender
+		.unbounded_send(Transaction::Initiator(InitiatorCall::InitiateBridgeTransfer(
+			InitiatorAddress(initiator.clone()),
+			RecipientAddress::from(TestAddress("recipient")),
+			Amount(1000),
+			TimeLock(50),
+			HashLock(TestHash("secret")),
+		)))
+		.unwrap();
+	for (name, time_lock) in [("a", 10), ("b", 20), ("c", 30), ("d", 200)] {
+		blockchain
+			.transaction_sender
+			.unbounded_send(Transaction::Counterparty(CounterpartyCall::LockBridgeTransfer(
+				BridgeTransferId(TestHash(name)),
+				HashLock(TestHash("secret")),
+				TimeLock(time_lock),
+				RecipientAddress::from(TestAddress(name)),
+				Amount(100),
+			)))
+			.unwrap();
+	}
+	while blockchain.step().is_some() {}
+	assert_eq!(blockchain.get_balance(&initiator), Some(&Amount(500)));
+
+	let mut monitor = blockchain.add_event_listener();
+	blockchain.forward_time(100);
+	let events = blockchain.sweep_expired_transfers();
+
+	assert_eq!(events.len(), 4);
+	assert!(events.contains(&AbstractBlockchainEvent::InitiatorContractEvent(Ok(
+		SmartContractInitiatorEvent::RefundedBridgeTransfer(BridgeTransferId(TestHash(
+			"unique_hash"
+		)))
+	))));
+	for name in ["a", "b", "c"] {
+		assert!(events.contains(&AbstractBlockchainEvent::CounterpartyContractEvent(Ok(
+			SmartContractCounterpartyEvent::AbortedBridgeTransfer(BridgeTransferId(TestHash(name)))
+		))));
+		// the recipients of aborted locks are not paid
+		assert_eq!(blockchain.get_balance(&TestAddress(name)), None);
+	}
+	// the refund restores the balance from before the transfer
+	assert_eq!(blockchain.get_balance(&initiator), Some(&Amount(1500)));
+
+	// the lock expiring later is kept, and nothing is swept twice
+	assert!(blockchain
+		.counterparty_contract
+		.locked_transfers
+		.contains_key(&BridgeTransferId(TestHash("d"))));
+	assert!(blockchain.initiator_contract.initiated_transfers.is_empty());
+	assert!(blockchain.sweep_expired_transfers().is_empty());
+
+	let notified: Vec<_> = std::iter::from_fn(|| monitor.try_next().ok().flatten()).collect();
+	assert_eq!(notified.len(), 4);
+}
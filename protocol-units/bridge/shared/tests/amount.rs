@@ -0,0 +1,33 @@
+use bridge_shared::types::Amount;
+
+#[test]
+fn test_amount_whole_conversions_at_decimal_boundaries() {
+	assert_eq!(Amount::from_whole(1, 0), Some(Amount(1)));
+	assert_eq!(Amount::from_whole(1, 8), Some(Amount(100_000_000)));
+	assert_eq!(Amount::from_whole(1, 18), Some(Amount(1_000_000_000_000_000_000)));
+
+	assert_eq!(Amount(100_000_000).to_whole(8), Some(1));
+	assert_eq!(Amount(200_000_000).to_whole(8), Some(2));
+	// one base unit either side of a whole token is not a whole number of tokens
+	assert_eq!(Amount(99_999_999).to_whole(8), None);
+	assert_eq!(Amount(100_000_001).to_whole(8), None);
+	// the same base units are a different number of tokens for a different decimals
+	assert_eq!(Amount(1_000_000_000_000_000_000).to_whole(18), Some(1));
+	assert_eq!(Amount(1_000_000_000_000_000_000).to_whole(8), Some(10_000_000_000));
+}
+
+#[test]
+fn test_amount_overflow() {
+	// 10^38 is the largest power of ten that fits in a u128
+	assert_eq!(Amount::from_whole(1, 38), Some(Amount(10u128.pow(38))));
+	assert_eq!(Amount::from_whole(1, 39), None);
+	assert_eq!(Amount::from_whole(u128::MAX / 10 + 1, 1), None);
+	assert_eq!(Amount(0).to_whole(39), None);
+
+	assert_eq!(Amount(u128::MAX).checked_add(Amount(1)), None);
+	assert_eq!(Amount(0).checked_sub(Amount(1)), None);
+	assert_eq!(Amount(1).checked_add(Amount(1)), Some(Amount(2)));
+
+	assert_eq!(u64::try_from(Amount::from(u64::MAX)), Ok(u64::MAX));
+	assert!(u64::try_from(Amount(u64::MAX as u128 + 1)).is_err());
+}
@@ -7,7 +7,7 @@ use bridge_shared::{
 		BridgeContractInitiatorEvent, BridgeContractInitiatorMonitoring,
 	},
 	bridge_service::{BridgeService, BridgeServiceConfig},
-	types::{Convert, GenUniqueHash, HashLockPreImage, RecipientAddress},
+	types::{Amount, Convert, GenUniqueHash, HashLockPreImage, RecipientAddress},
 };
 
 use futures::{channel::mpsc::UnboundedReceiver, Stream, StreamExt};
@@ -17,6 +17,7 @@ use std::{
 	fmt::{Debug, Formatter},
 	hash::{DefaultHasher, Hash, Hasher},
 	pin::Pin,
+	sync::Arc,
 	task::{Context, Poll},
 };
 
@@ -164,21 +165,23 @@ impl From<BC2Hash> for BC1Hash {
 }
 
 pub struct InitiatorContractMonitoring<A, H> {
-	listener: UnboundedReceiver<AbstractBlockchainEvent<A, H>>,
+	listener: UnboundedReceiver<Arc<AbstractBlockchainEvent<A, H>>>,
 }
 
 impl<A, H> InitiatorContractMonitoring<A, H> {
-	pub fn build(listener: UnboundedReceiver<AbstractBlockchainEvent<A, H>>) -> Self {
+	pub fn build(listener: UnboundedReceiver<Arc<AbstractBlockchainEvent<A, H>>>) -> Self {
 		Self { listener }
 	}
 }
 
-impl<A: Debug, H: Debug> BridgeContractInitiatorMonitoring for InitiatorContractMonitoring<A, H> {
+impl<A: Debug + Clone, H: Debug + Clone> BridgeContractInitiatorMonitoring
+	for InitiatorContractMonitoring<A, H>
+{
 	type Address = A;
 	type Hash = H;
 }
 
-impl<A: Debug, H: Debug> Stream for InitiatorContractMonitoring<A, H> {
+impl<A: Debug + Clone, H: Debug + Clone> Stream for InitiatorContractMonitoring<A, H> {
 	type Item = BridgeContractInitiatorEvent<
 		<Self as BridgeContractInitiatorMonitoring>::Address,
 		<Self as BridgeContractInitiatorMonitoring>::Hash,
@@ -186,9 +189,10 @@ impl<A: Debug, H: Debug> Stream for InitiatorContractMonitoring<A, H> {
 
 	fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
 		let this = self.get_mut();
-		if let Poll::Ready(Some(AbstractBlockchainEvent::InitiatorContractEvent(contract_result))) =
-			this.listener.poll_next_unpin(cx)
-		{
+		let Poll::Ready(Some(event)) = this.listener.poll_next_unpin(cx) else {
+			return Poll::Pending;
+		};
+		if let AbstractBlockchainEvent::InitiatorContractEvent(contract_result) = &*event {
 			tracing::trace!(
 				"InitiatorContractMonitoring: Received contract event: {:?}",
 				contract_result
@@ -198,13 +202,19 @@ impl<A: Debug, H: Debug> Stream for InitiatorContractMonitoring<A, H> {
 			match contract_result {
 				Ok(contract_event) => match contract_event {
 					InitiatedBridgeTransfer(details) => {
-						return Poll::Ready(Some(BridgeContractInitiatorEvent::Initiated(details)))
+						return Poll::Ready(Some(BridgeContractInitiatorEvent::Initiated(
+							details.clone(),
+						)))
 					}
 					CompletedBridgeTransfer(bridge_transfer_id, _) => {
 						return Poll::Ready(Some(BridgeContractInitiatorEvent::Completed(
-							bridge_transfer_id,
+							bridge_transfer_id.clone(),
 						)))
 					}
+					RefundedBridgeTransfer(_) => {
+						// not surfaced by the bridge monitoring, the bridge service does not
+						// handle refunds yet
+					}
 				},
 				Err(_) => {
 					// Handle error
@@ -216,31 +226,31 @@ impl<A: Debug, H: Debug> Stream for InitiatorContractMonitoring<A, H> {
 }
 
 pub struct CounterpartyContractMonitoring<A, H> {
-	listener: UnboundedReceiver<AbstractBlockchainEvent<A, H>>,
+	listener: UnboundedReceiver<Arc<AbstractBlockchainEvent<A, H>>>,
 }
 
 impl<A, H> CounterpartyContractMonitoring<A, H> {
-	pub fn build(listener: UnboundedReceiver<AbstractBlockchainEvent<A, H>>) -> Self {
+	pub fn build(listener: UnboundedReceiver<Arc<AbstractBlockchainEvent<A, H>>>) -> Self {
 		Self { listener }
 	}
 }
 
-impl<A: Debug, H: Debug> BridgeContractCounterpartyMonitoring
+impl<A: Debug + Clone, H: Debug + Clone> BridgeContractCounterpartyMonitoring
 	for CounterpartyContractMonitoring<A, H>
 {
 	type Address = A;
 	type Hash = H;
 }
 
-impl<A: Debug, H: Debug> Stream for CounterpartyContractMonitoring<A, H> {
+impl<A: Debug + Clone, H: Debug + Clone> Stream for CounterpartyContractMonitoring<A, H> {
 	type Item = BridgeContractCounterpartyEvent<H>;
 
 	fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
 		let this = self.get_mut();
-		if let Poll::Ready(Some(AbstractBlockchainEvent::CounterpartyContractEvent(
-			contract_result,
-		))) = this.listener.poll_next_unpin(cx)
-		{
+		let Poll::Ready(Some(event)) = this.listener.poll_next_unpin(cx) else {
+			return Poll::Pending;
+		};
+		if let AbstractBlockchainEvent::CounterpartyContractEvent(contract_result) = &*event {
 			tracing::trace!(
 				"CounterpartyContractMonitoring: Received contract event: {:?}",
 				contract_result
@@ -249,13 +259,18 @@ impl<A: Debug, H: Debug> Stream for CounterpartyContractMonitoring<A, H> {
 			match contract_result {
 				Ok(contract_event) => match contract_event {
 					LockedBridgeTransfer(details) => {
-						return Poll::Ready(Some(BridgeContractCounterpartyEvent::Locked(details)))
+						return Poll::Ready(Some(BridgeContractCounterpartyEvent::Locked(
+							details.clone(),
+						)))
 					}
 					CompletedBridgeTransfer(details) => {
 						return Poll::Ready(Some(BridgeContractCounterpartyEvent::Completed(
-							details,
+							details.clone(),
 						)))
 					}
+					AbortedBridgeTransfer(_) => {
+						// not surfaced by the bridge monitoring
+					}
 				},
 				Err(_) => {
 					// Handle error
@@ -304,6 +319,10 @@ pub fn setup_bridge_service(config: BridgeServiceConfig) -> SetupBridgeServiceRe
 	let mut blockchain_2 =
 		AbstractBlockchain::<BC2Address, BC2Hash, _>::new(rng.seeded_clone(), "Blockchain2");
 
+	// initiating a transfer debits the initiator
+	blockchain_1.add_account(BC1Address("initiator"), Amount(u128::MAX));
+	blockchain_2.add_account(BC2Address("initiator"), Amount(u128::MAX));
+
 	// Contracts and monitors for blockchain 1
 	let client_1 =
 		AbstractBlockchainClient::new(blockchain_1.connection(), rng.seeded_clone(), 0.0, 0.00);
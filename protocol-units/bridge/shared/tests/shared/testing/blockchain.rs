@@ -3,6 +3,7 @@ use rand::Rng;
 use std::{
 	collections::HashMap,
 	pin::Pin,
+	sync::Arc,
 	task::{Context, Poll},
 };
 
@@ -55,7 +56,7 @@ pub struct AbstractBlockchain<A, H, R> {
 	pub transaction_sender: mpsc::UnboundedSender<Transaction<A, H>>,
 	pub transaction_receiver: mpsc::UnboundedReceiver<Transaction<A, H>>,
 
-	pub event_listeners: Vec<mpsc::UnboundedSender<AbstractBlockchainEvent<A, H>>>,
+	pub event_listeners: Vec<mpsc::UnboundedSender<Arc<AbstractBlockchainEvent<A, H>>>>,
 
 	waker: AtomicWaker,
 
@@ -91,12 +92,26 @@ where
 		}
 	}
 
-	pub fn add_event_listener(&mut self) -> mpsc::UnboundedReceiver<AbstractBlockchainEvent<A, H>> {
+	/// Registers a listener notified of every event. Dropping the returned receiver
+	/// unregisters the listener.
+	///
+	/// Events are delivered behind an `Arc` shared by all listeners, so fanning an event
+	/// out costs a reference count bump per listener rather than a deep copy, which keeps
+	/// allocation flat under load as listeners are added.
+	pub fn add_event_listener(
+		&mut self,
+	) -> mpsc::UnboundedReceiver<Arc<AbstractBlockchainEvent<A, H>>> {
+		self.prune_event_listeners();
 		let (sender, receiver) = mpsc::unbounded();
 		self.event_listeners.push(sender);
 		receiver
 	}
 
+	/// Removes the listeners whose receiver has been dropped.
+	pub fn prune_event_listeners(&mut self) {
+		self.event_listeners.retain(|listener| !listener.is_closed());
+	}
+
 	pub fn forward_time(&mut self, duration: u64) {
 		self.time += duration;
 	}
@@ -127,6 +142,133 @@ where
 	}
 }
 
+impl<A, H, R> AbstractBlockchain<A, H, R>
+where
+	A: BridgeAddressType + From<RecipientAddress>,
+	H: BridgeHashType + GenUniqueHash,
+	R: Rng + Unpin,
+	H: From<HashLockPreImage>,
+{
+	/// Synchronously processes exactly one queued transaction and returns the resulting
+	/// event, or `None` if no transaction is queued. Listeners are notified as when polling.
+	pub fn step(&mut self) -> Option<AbstractBlockchainEvent<A, H>> {
+		match self.transaction_receiver.try_next() {
+			Ok(Some(transaction)) => {
+				self.process_transaction(transaction);
+				self.emit_event()
+			}
+			Ok(None) | Err(_) => None,
+		}
+	}
+
+	fn process_transaction(&mut self, transaction: Transaction<A, H>) {
+		tracing::trace!(
+			"AbstractBlockchain[{}]: Received transaction: {:?}",
+			self.name,
+			transaction
+		);
+		match transaction {
+			Transaction::Initiator(call) => match call {
+				InitiatorCall::InitiateBridgeTransfer(
+					initiator_address,
+					recipient_address,
+					amount,
+					time_lock,
+					hash_lock,
+				) => {
+					self.events.push(AbstractBlockchainEvent::InitiatorContractEvent(
+						self.initiator_contract.initiate_bridge_transfer(
+							&mut self.accounts,
+							initiator_address.clone(),
+							recipient_address.clone(),
+							amount,
+							time_lock.clone(),
+							hash_lock.clone(),
+						),
+					));
+				}
+				InitiatorCall::CompleteBridgeTransfer(bridge_transfer_id, secret) => {
+					self.events.push(AbstractBlockchainEvent::InitiatorContractEvent(
+						self.initiator_contract.complete_bridge_transfer(
+							&mut self.accounts,
+							bridge_transfer_id.clone(),
+							secret.clone(),
+						),
+					));
+				}
+			},
+			Transaction::Counterparty(call) => match call {
+				CounterpartyCall::LockBridgeTransfer(
+					bridge_transfer_id,
+					hash_lock,
+					time_lock,
+					recipient_address,
+					amount,
+				) => {
+					self.events.push(AbstractBlockchainEvent::CounterpartyContractEvent(
+						self.counterparty_contract.lock_bridge_transfer(
+							bridge_transfer_id.clone(),
+							hash_lock.clone(),
+							time_lock.clone(),
+							recipient_address.clone(),
+							amount,
+						),
+					));
+				}
+				CounterpartyCall::CompleteBridgeTransfer(bridge_transfer_id, pre_image) => {
+					self.events.push(AbstractBlockchainEvent::CounterpartyContractEvent(
+						self.counterparty_contract.complete_bridge_transfer(
+							&mut self.accounts,
+							&bridge_transfer_id,
+							pre_image,
+						),
+					));
+				}
+			},
+		}
+	}
+
+	/// Refunds the initiated transfers and aborts the locked transfers whose time lock is
+	/// before the current time, returning the resulting events. Listeners are notified as when
+	/// polling.
+	pub fn sweep_expired_transfers(&mut self) -> Vec<AbstractBlockchainEvent<A, H>> {
+		let refunded = self
+			.initiator_contract
+			.refund_expired_transfers(&mut self.accounts, self.time)
+			.into_iter()
+			.map(AbstractBlockchainEvent::InitiatorContractEvent);
+		let aborted = self
+			.counterparty_contract
+			.abort_expired_transfers(self.time)
+			.into_iter()
+			.map(AbstractBlockchainEvent::CounterpartyContractEvent);
+		let events: Vec<_> = refunded.chain(aborted).collect();
+
+		let mut swept = Vec::with_capacity(events.len());
+		for event in events {
+			self.events.push(event);
+			swept.extend(self.emit_event());
+		}
+		swept
+	}
+
+	fn emit_event(&mut self) -> Option<AbstractBlockchainEvent<A, H>> {
+		let event = self.events.pop()?;
+		if self.event_listeners.is_empty() {
+			return Some(event);
+		}
+		// a single copy is shared by every listener
+		let shared = Arc::new(event.clone());
+		let name = &self.name;
+		// a failed send means the listener was dropped, unregister it
+		self.event_listeners.retain(|listener| {
+			tracing::trace!("AbstractBlockchain[{}]: Sending event to listener", name);
+			listener.unbounded_send(Arc::clone(&shared)).is_ok()
+		});
+		Some(event)
+	}
+}
+
 impl<A, H, R> Future for AbstractBlockchain<A, H, R>
 where
 	A: BridgeAddressType + From<RecipientAddress>,
@@ -165,71 +307,7 @@ where
 		let this = self.get_mut();
 
 		match this.transaction_receiver.poll_next_unpin(cx) {
-			Poll::Ready(Some(transaction)) => {
-				tracing::trace!(
-					"AbstractBlockchain[{}]: Received transaction: {:?}",
-					this.name,
-					transaction
-				);
-				match transaction {
-					Transaction::Initiator(call) => match call {
-						InitiatorCall::InitiateBridgeTransfer(
-							initiator_address,
-							recipient_address,
-							amount,
-							time_lock,
-							hash_lock,
-						) => {
-							this.events.push(AbstractBlockchainEvent::InitiatorContractEvent(
-								this.initiator_contract.initiate_bridge_transfer(
-									initiator_address.clone(),
-									recipient_address.clone(),
-									amount,
-									time_lock.clone(),
-									hash_lock.clone(),
-								),
-							));
-						}
-						InitiatorCall::CompleteBridgeTransfer(bridge_transfer_id, secret) => {
-							this.events.push(AbstractBlockchainEvent::InitiatorContractEvent(
-								this.initiator_contract.complete_bridge_transfer(
-									&mut this.accounts,
-									bridge_transfer_id.clone(),
-									secret.clone(),
-								),
-							));
-						}
-					},
-					Transaction::Counterparty(call) => match call {
-						CounterpartyCall::LockBridgeTransfer(
-							bridge_transfer_id,
-							hash_lock,
-							time_lock,
-							recipient_address,
-							amount,
-						) => {
-							this.events.push(AbstractBlockchainEvent::CounterpartyContractEvent(
-								this.counterparty_contract.lock_bridge_transfer(
-									bridge_transfer_id.clone(),
-									hash_lock.clone(),
-									time_lock.clone(),
-									recipient_address.clone(),
-									amount,
-								),
-							));
-						}
-						CounterpartyCall::CompleteBridgeTransfer(bridge_transfer_id, pre_image) => {
-							this.events.push(AbstractBlockchainEvent::CounterpartyContractEvent(
-								this.counterparty_contract.complete_bridge_transfer(
-									&mut this.accounts,
-									&bridge_transfer_id,
-									pre_image,
-								),
-							));
-						}
-					},
-				}
-			}
+			Poll::Ready(Some(transaction)) => this.process_transaction(transaction),
 			Poll::Ready(None) => {
 				tracing::warn!("AbstractBlockchain[{}]: Transaction receiver dropped", this.name);
 			}
@@ -241,12 +319,7 @@ where
 			}
 		}
 
-		if let Some(event) = this.events.pop() {
-			for listener in &mut this.event_listeners {
-				tracing::trace!("AbstractBlockchain[{}]: Sending event to listener", this.name);
-				listener.unbounded_send(event.clone()).expect("listener dropped");
-			}
-
+		if let Some(event) = this.emit_event() {
 			tracing::trace!("AbstractBlockchain[{}]: Poll::Ready({:?})", this.name, event);
 			return Poll::Ready(Some(event));
 		}
@@ -10,6 +10,7 @@ use thiserror::Error;
 pub enum SmartContractCounterpartyEvent<H> {
 	LockedBridgeTransfer(LockDetails<H>),
 	CompletedBridgeTransfer(CompletedDetails<H>),
+	AbortedBridgeTransfer(BridgeTransferId<H>),
 }
 
 #[derive(Debug, Error, Clone, PartialEq, Eq)]
@@ -18,6 +19,8 @@ pub enum SmartContractCounterpartyError {
 	TransferNotFound,
 	#[error("Invalid hash lock pre image (secret)")]
 	InvalidHashLockPreImage,
+	#[error("Balance overflow")]
+	BalanceOverflow,
 }
 
 #[derive(Debug)]
@@ -103,10 +106,36 @@ where
 		// TODO: fix this
 		let account = A::from(transfer.recipient_address.clone());
 		let balance = accounts.entry(account).or_insert(Amount(0));
-		**balance += *transfer.amount;
+		*balance = balance
+			.checked_add(transfer.amount)
+			.ok_or(SmartContractCounterpartyError::BalanceOverflow)?;
 
 		Ok(SmartContractCounterpartyEvent::CompletedBridgeTransfer(
 			CompletedDetails::from_lock_details(transfer, pre_image),
 		))
 	}
+
+	/// Releases every lock whose time lock is before `time`, without paying the recipient.
+	pub fn abort_expired_transfers(&mut self, time: u64) -> Vec<SCCResult<H>> {
+		let expired: Vec<_> = self
+			.locked_transfers
+			.iter()
+			.filter(|(_, transfer)| transfer.time_lock.0 < time)
+			.map(|(bridge_transfer_id, _)| bridge_transfer_id.clone())
+			.collect();
+
+		expired
+			.into_iter()
+			.filter_map(|bridge_transfer_id| self.locked_transfers.remove(&bridge_transfer_id))
+			.map(|transfer| {
+				tracing::trace!(
+					"SmartContractCounterparty: Aborting bridge transfer: {:?}",
+					transfer
+				);
+				Ok(SmartContractCounterpartyEvent::AbortedBridgeTransfer(
+					transfer.bridge_transfer_id,
+				))
+			})
+			.collect()
+	}
 }
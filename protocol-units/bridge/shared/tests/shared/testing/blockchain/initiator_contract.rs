@@ -12,6 +12,7 @@ use bridge_shared::types::{
 pub enum SmartContractInitiatorEvent<A, H> {
 	InitiatedBridgeTransfer(BridgeTransferDetails<A, H>),
 	CompletedBridgeTransfer(BridgeTransferId<H>, HashLockPreImage),
+	RefundedBridgeTransfer(BridgeTransferId<H>),
 }
 
 #[derive(Debug)]
@@ -35,6 +36,10 @@ pub enum SmartContractInitiatorError {
 	TransferNotFound,
 	#[error("Invalid hash lock pre image (secret)")]
 	InvalidHashLockPreImage,
+	#[error("Balance overflow")]
+	BalanceOverflow,
+	#[error("Insufficient balance")]
+	InsufficientBalance,
 }
 
 pub type SCIResult<A, H> = Result<SmartContractInitiatorEvent<A, H>, SmartContractInitiatorError>;
@@ -50,14 +55,23 @@ where
 		Self { initiated_transfers: HashMap::new(), accounts: HashMap::default(), rng }
 	}
 
+	/// Debits the amount from the initiator, to be refunded if the transfer expires.
 	pub fn initiate_bridge_transfer(
 		&mut self,
+		accounts: &mut HashMap<A, Amount>,
 		initiator: InitiatorAddress<A>,
 		recipient: RecipientAddress,
 		amount: Amount,
 		time_lock: TimeLock,
 		hash_lock: HashLock<H>,
 	) -> SCIResult<A, H> {
+		let balance = accounts
+			.get_mut(&initiator.0)
+			.ok_or(SmartContractInitiatorError::InsufficientBalance)?;
+		*balance = balance
+			.checked_sub(amount)
+			.ok_or(SmartContractInitiatorError::InsufficientBalance)?;
+
 		let bridge_transfer_id = BridgeTransferId::<H>::gen_unique_hash(&mut self.rng);
 
 		tracing::trace!(
@@ -65,10 +79,6 @@ where
 			bridge_transfer_id
 		);
 
-		// // TODO: fix this
-		// let balance = self.accounts.entry(initiator.0.clone()).or_insert(Amount(0));
-		// **balance -= amount.0;
-
 		// initiate bridge transfer
 		self.initiated_transfers.insert(
 			bridge_transfer_id.clone(),
@@ -116,6 +126,40 @@ where
 			return Err(SmartContractInitiatorError::InvalidHashLockPreImage);
 		}
 
+		// a completed transfer can no longer be refunded
+		self.initiated_transfers.remove(&transfer_id);
+
 		Ok(SmartContractInitiatorEvent::CompletedBridgeTransfer(transfer_id, pre_image))
 	}
+
+	/// Refunds the initiator of every transfer whose time lock is before `time`.
+	pub fn refund_expired_transfers(
+		&mut self,
+		accounts: &mut HashMap<A, Amount>,
+		time: u64,
+	) -> Vec<SCIResult<A, H>> {
+		let expired: Vec<_> = self
+			.initiated_transfers
+			.iter()
+			.filter(|(_, transfer)| transfer.time_lock.0 < time)
+			.map(|(bridge_transfer_id, _)| bridge_transfer_id.clone())
+			.collect();
+
+		expired
+			.into_iter()
+			.filter_map(|bridge_transfer_id| self.initiated_transfers.remove(&bridge_transfer_id))
+			.map(|transfer| {
+				tracing::trace!(
+					"SmartContractInitiator: Refunding bridge transfer: {:?}",
+					transfer
+				);
+				let balance =
+					accounts.entry(transfer.initiator_address.0.clone()).or_insert(Amount(0));
+				*balance = balance
+					.checked_add(transfer.amount)
+					.ok_or(SmartContractInitiatorError::BalanceOverflow)?;
+				Ok(SmartContractInitiatorEvent::RefundedBridgeTransfer(transfer.bridge_transfer_id))
+			})
+			.collect()
+	}
 }
@@ -0,0 +1,62 @@
+use bridge_shared::types::{GenUniqueHash, HashLockPreImage, RecipientAddress};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use std::fmt::{Debug, Formatter};
+
+use super::{blockchain::AbstractBlockchain, rng::TestRng};
+
+/// An abstract blockchain over address and hash types shaped after Ethereum's, ready to use
+/// without inventing types of its own.
+pub type EthAbstractBlockchain = AbstractBlockchain<EthAddress, EthHash, TestRng>;
+
+fn write_hex(f: &mut Formatter<'_>, name: &str, bytes: &[u8]) -> std::fmt::Result {
+	write!(f, "{name}(0x")?;
+	for byte in bytes {
+		write!(f, "{byte:02x}")?;
+	}
+	write!(f, ")")
+}
+
+/// A 20-byte account address.
+#[derive(Clone, Copy, Eq, PartialEq, Hash)]
+pub struct EthAddress(pub [u8; 20]);
+
+impl From<RecipientAddress> for EthAddress {
+	fn from(value: RecipientAddress) -> Self {
+		Self(value.0.try_into().expect("Invalid Ethereum address length"))
+	}
+}
+
+impl From<EthAddress> for RecipientAddress {
+	fn from(value: EthAddress) -> Self {
+		RecipientAddress(value.0.to_vec())
+	}
+}
+
+impl Debug for EthAddress {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write_hex(f, "EthAddress", &self.0)
+	}
+}
+
+/// A 32-byte hash. The hash lock of a secret is its SHA-256 digest.
+#[derive(Clone, Copy, Eq, PartialEq, Hash)]
+pub struct EthHash(pub [u8; 32]);
+
+impl From<HashLockPreImage> for EthHash {
+	fn from(value: HashLockPreImage) -> Self {
+		Self(Sha256::digest(&value.0).into())
+	}
+}
+
+impl GenUniqueHash for EthHash {
+	fn gen_unique_hash<R: Rng>(rng: &mut R) -> Self {
+		Self(rng.gen())
+	}
+}
+
+impl Debug for EthHash {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write_hex(f, "EthHash", &self.0)
+	}
+}
@@ -1,5 +1,6 @@
 #![allow(dead_code)]
 
 pub mod blockchain;
+pub mod eth;
 pub mod mocks;
 pub mod rng;
@@ -35,7 +35,7 @@ impl LightNodeV1Operations for LightNodeV1 {
 		let memseq_path = pass_through.config.try_memseq_path()?;
 		info!("Memseq path: {:?}", memseq_path);
 
-		let memseq = memseq::Memseq::try_move_rocks(PathBuf::from(memseq_path))?;
+		let memseq = memseq::Memseq::try_move_rocks(PathBuf::from(memseq_path)).await?;
 		info!("Initialized Memseq with Move Rocks for LightNodeV1 in sequencer mode.");
 
 		Ok(Self { pass_through, memseq })
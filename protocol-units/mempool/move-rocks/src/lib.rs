@@ -1,43 +1,507 @@
 use anyhow::Error;
-use mempool_util::{MempoolBlockOperations, MempoolTransaction, MempoolTransactionOperations};
-use movement_types::{Block, Id};
-use rocksdb::{ColumnFamilyDescriptor, Options, DB};
+use futures::{Stream, StreamExt};
+use mempool_util::{
+	MempoolBlockOperations, MempoolTransaction, MempoolTransactionOperations, TransactionOrdering,
+};
+use movement_types::{Block, Id, Transaction};
+use rocksdb::{ColumnFamily, ColumnFamilyDescriptor, Options, WriteBatch, WriteOptions, DB};
+use serde::{de::DeserializeOwned, Serialize};
 use serde_json;
+use std::collections::{BTreeSet, HashMap};
+use std::io::Read;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+/// Magic number starting every zstd frame. Serialized JSON never starts with it, which lets
+/// compressed and uncompressed values coexist.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Default limit on the decoded size of persisted blocks and block imports, see
+/// [`RocksdbMempool::with_max_decoded_size`].
+pub const DEFAULT_MAX_DECODED_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Key of the stored parent block in the `chain_state` column family, whose value is the
+/// big-endian height followed by the id.
+const PARENT_BLOCK_KEY: &[u8] = b"parent_block";
+
+/// Key of the next arrival stamp in the `chain_state` column family.
+const NEXT_ARRIVAL_KEY: &[u8] = b"next_arrival";
+
+/// Column families of a mempool, opened once per chain in a database shared by chains.
+const COLUMN_FAMILIES: [&str; 9] = [
+	"mempool_transactions",
+	"transaction_truths",
+	"blocks",
+	"transaction_lookups",
+	"sender_sequence_lookups",
+	"block_heights",
+	"chain_state",
+	"block_labels",
+	"quarantine",
+];
+
+/// First inconsistency found by [`RocksdbMempool::verify_persisted_chain`].
+#[derive(Debug, thiserror::Error)]
+pub enum ChainIntegrityError {
+	#[error("Block at height {height} is indexed but not stored")]
+	MissingBlock { height: u64 },
+	#[error("Block stored at height {height} does not match its indexed id")]
+	CorruptBlock { height: u64 },
+	#[error("Block at height {height} does not link to the block before it")]
+	BrokenParent { height: u64 },
+	#[error("Expected a block at height {expected}, found height {found}")]
+	HeightGap { expected: u64, found: u64 },
+	#[error("Chain tip at height {height} is not the stored parent block")]
+	TipMismatch { height: u64 },
+	#[error("Failed to read the persisted chain: {0}")]
+	Storage(#[from] Error),
+}
+
+/// A payload whose decoded, i.e. decompressed, size exceeds
+/// [`RocksdbMempool::with_max_decoded_size`].
+#[derive(Debug, thiserror::Error)]
+#[error("Decoded payload exceeds the limit of {limit} bytes")]
+pub struct DecodedSizeLimitExceeded {
+	pub limit: u64,
+}
+
 #[derive(Debug, Clone)]
 pub struct RocksdbMempool {
 	db: Arc<RwLock<DB>>,
+	// whether persisted blocks and block exports are zstd-compressed
+	compress_blocks: bool,
+	// payloads decoding to more bytes are rejected
+	max_decoded_size: u64,
+	// pop order of transactions added from now on, encoded in their keys
+	ordering: TransactionOrdering,
+	// prepended to column family names, empty unless the database is shared by chains
+	cf_prefix: String,
+	// whether undeserializable mempool entries are quarantined instead of failing pops
+	quarantine_corrupt: bool,
+	// whether adding transactions waits for the write-ahead log to be synced to disk
+	sync_writes: bool,
+	// next arrival stamp, persisted with every added transaction so that it survives restarts
+	next_arrival: Arc<AtomicU64>,
+	// number of mempool entries, counted on open and kept up to date by every write to them,
+	// so that counting the mempool does not scan it
+	pending: Arc<AtomicU64>,
 }
 impl RocksdbMempool {
 	pub fn try_new(path: &str) -> Result<Self, Error> {
+		let db = Self::open(path, COLUMN_FAMILIES.iter().map(|name| (*name).to_string()))?;
+		RocksdbMempool {
+			db: Arc::new(RwLock::new(db)),
+			compress_blocks: false,
+			max_decoded_size: DEFAULT_MAX_DECODED_SIZE,
+			ordering: TransactionOrdering::default(),
+			cf_prefix: String::new(),
+			quarantine_corrupt: false,
+			sync_writes: false,
+			next_arrival: Arc::default(),
+			pending: Arc::default(),
+		}
+		.with_stored_arrival()?
+		.with_counted_pending()
+	}
+
+	/// Opens one mempool per chain in a single database, keyed by chain id. Each chain has
+	/// its own column families, named after the chain id, so the chains share the database
+	/// instance and its file handles but none of their data.
+	pub fn try_new_chains(path: &str, chain_ids: &[&str]) -> Result<HashMap<String, Self>, Error> {
+		for chain_id in chain_ids {
+			anyhow::ensure!(
+				!chain_id.is_empty() && !chain_id.contains('/'),
+				"Invalid chain id {chain_id:?}"
+			);
+		}
+		let column_families = chain_ids.iter().flat_map(|chain_id| {
+			COLUMN_FAMILIES.iter().map(move |name| format!("{chain_id}/{name}"))
+		});
+		let db = Arc::new(RwLock::new(Self::open(path, column_families)?));
+		chain_ids
+			.iter()
+			.map(|chain_id| {
+				let mempool = RocksdbMempool {
+					db: Arc::clone(&db),
+					compress_blocks: false,
+					max_decoded_size: DEFAULT_MAX_DECODED_SIZE,
+					ordering: TransactionOrdering::default(),
+					cf_prefix: format!("{chain_id}/"),
+					quarantine_corrupt: false,
+					sync_writes: false,
+					next_arrival: Arc::default(),
+					pending: Arc::default(),
+				}
+				.with_stored_arrival()?
+				.with_counted_pending()?;
+				Ok(((*chain_id).to_string(), mempool))
+			})
+			.collect()
+	}
+
+	/// Resumes the arrival stamps after the stored next arrival. Databases written before it
+	/// was stored resume after the highest arrival of their pending transactions.
+	fn with_stored_arrival(self) -> Result<Self, Error> {
+		let next_arrival = {
+			let db = self.db.try_read()?;
+			let cf_handle = self.cf(&db, "chain_state")?;
+			match db.get_cf(&cf_handle, NEXT_ARRIVAL_KEY)? {
+				Some(bytes) => u64::from_be_bytes(
+					bytes.as_slice().try_into().map_err(|_| Error::msg("Corrupt next arrival"))?,
+				),
+				None => {
+					let cf_handle = self.cf(&db, "mempool_transactions")?;
+					let mut next_arrival = 0;
+					for res in db.iterator_cf(&cf_handle, rocksdb::IteratorMode::Start) {
+						let (_, value) = res?;
+						if let Ok(tx) = serde_json::from_slice::<MempoolTransaction>(&value) {
+							next_arrival = next_arrival.max(tx.arrival + 1);
+						}
+					}
+					next_arrival
+				}
+			}
+		};
+		self.next_arrival.store(next_arrival, Ordering::SeqCst);
+		Ok(self)
+	}
+
+	/// Counts the mempool entries once, for [`MempoolTransactionOperations::size`] to keep
+	/// track of them from then on.
+	fn with_counted_pending(self) -> Result<Self, Error> {
+		let pending = {
+			let db = self.db.try_read()?;
+			let cf_handle = self.cf(&db, "mempool_transactions")?;
+			let mut pending = 0;
+			for res in db.iterator_cf(&cf_handle, rocksdb::IteratorMode::Start) {
+				res?;
+				pending += 1;
+			}
+			pending
+		};
+		self.pending.store(pending, Ordering::SeqCst);
+		Ok(self)
+	}
+
+	fn open(path: &str, column_families: impl IntoIterator<Item = String>) -> Result<DB, Error> {
 		let mut options = Options::default();
 		options.create_if_missing(true);
 		options.create_missing_column_families(true);
 
-		let mempool_transactions_cf =
-			ColumnFamilyDescriptor::new("mempool_transactions", Options::default());
-		let transaction_truths_cf =
-			ColumnFamilyDescriptor::new("transaction_truths", Options::default());
-		let blocks_cf = ColumnFamilyDescriptor::new("blocks", Options::default());
-		let transaction_lookups_cf =
-			ColumnFamilyDescriptor::new("transaction_lookups", Options::default());
+		// every column family of an existing database must be opened, e.g. those of chains
+		// not requested this time; listing fails if the database does not exist yet
+		let mut names: BTreeSet<String> =
+			DB::list_cf(&options, path).unwrap_or_default().into_iter().collect();
+		names.extend(column_families);
 
-		let db = DB::open_cf_descriptors(
+		DB::open_cf_descriptors(
 			&options,
 			path,
-			vec![mempool_transactions_cf, transaction_truths_cf, blocks_cf, transaction_lookups_cf],
+			names
+				.into_iter()
+				.map(|name| ColumnFamilyDescriptor::new(name, Options::default())),
 		)
-		.map_err(|e| Error::new(e))?;
+		.map_err(|e| Error::new(e))
+	}
+
+	fn cf<'a>(&self, db: &'a DB, name: &str) -> Result<&'a ColumnFamily, Error> {
+		db.cf_handle(&format!("{}{name}", self.cf_prefix))
+			.ok_or_else(|| Error::msg("CF handle not found"))
+	}
+
+	/// Compresses blocks persisted from now on, as well as block exports, with zstd.
+	/// Blocks persisted uncompressed remain readable.
+	pub fn with_block_compression(mut self, compress_blocks: bool) -> Self {
+		self.compress_blocks = compress_blocks;
+		self
+	}
+
+	/// Sets the order in which transactions of the same time slot are popped. Defaults to
+	/// [`TransactionOrdering::Arrival`]. The order is encoded in the keys of transactions as
+	/// they are added, so transactions already in the mempool keep their order.
+	pub fn with_ordering(mut self, ordering: TransactionOrdering) -> Self {
+		self.ordering = ordering;
+		self
+	}
+
+	/// Limits the decoded size of persisted blocks and of blobs passed to
+	/// [`RocksdbMempool::import_blocks`]. Compressed payloads are decompressed incrementally
+	/// and abandoned as soon as they exceed the limit, so that a decompression bomb from an
+	/// untrusted source cannot exhaust memory. Defaults to [`DEFAULT_MAX_DECODED_SIZE`].
+	pub fn with_max_decoded_size(mut self, max_decoded_size: u64) -> Self {
+		self.max_decoded_size = max_decoded_size;
+		self
+	}
+
+	/// Moves mempool entries that fail to deserialize to the `quarantine` column family when
+	/// they are read, e.g. popped or found, along with their id lookups, and carries on as if
+	/// they were not there, instead of failing the read. One corrupted entry then no longer
+	/// blocks the assembly of every later block. The raw bytes of the quarantined entries are
+	/// returned by [`RocksdbMempool::quarantined`].
+	pub fn with_quarantine_corrupt(mut self, quarantine_corrupt: bool) -> Self {
+		self.quarantine_corrupt = quarantine_corrupt;
+		self
+	}
+
+	/// Makes adding transactions wait for the write-ahead log to be synced to disk. Without it,
+	/// an added transaction survives a crash of the process, as RocksDB hands the write to the
+	/// operating system before returning, but may be lost if the machine crashes.
+	pub fn with_sync_writes(mut self, sync_writes: bool) -> Self {
+		self.sync_writes = sync_writes;
+		self
+	}
+
+	/// Raw bytes of the quarantined mempool entries, in their former pop order.
+	pub async fn quarantined(&self) -> Result<Vec<Vec<u8>>, Error> {
+		let db = self.db.read().await;
+		let cf_handle = self.cf(&db, "quarantine")?;
+		db.iterator_cf(&cf_handle, rocksdb::IteratorMode::Start)
+			.map(|res| Ok(res?.1.into_vec()))
+			.collect()
+	}
+
+	fn encode_blocks<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, Error> {
+		let serialized = serde_json::to_vec(value)?;
+		if self.compress_blocks {
+			Ok(zstd::encode_all(serialized.as_slice(), 0)?)
+		} else {
+			Ok(serialized)
+		}
+	}
+
+	fn decode_blocks<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T, Error> {
+		let limit = self.max_decoded_size;
+		if data.starts_with(&ZSTD_MAGIC) {
+			// reading one byte past the limit tells an oversized payload apart
+			let mut decoded = Vec::new();
+			zstd::stream::read::Decoder::new(data)?
+				.take(limit.saturating_add(1))
+				.read_to_end(&mut decoded)?;
+			if decoded.len() as u64 > limit {
+				return Err(DecodedSizeLimitExceeded { limit }.into());
+			}
+			Ok(serde_json::from_slice(&decoded)?)
+		} else {
+			if data.len() as u64 > limit {
+				return Err(DecodedSizeLimitExceeded { limit }.into());
+			}
+			Ok(serde_json::from_slice(data)?)
+		}
+	}
+
+	/// Big-endian so that the lexicographic key order matches the height order.
+	fn construct_block_height_key(height: u64) -> [u8; 8] {
+		height.to_be_bytes()
+	}
+
+	/// Persists the block and indexes it at the given height.
+	pub async fn add_block_at_height(&self, height: u64, block: Block) -> Result<(), Error> {
+		let block_id = block.id();
+		self.add_block(block).await?;
+		let db = self.db.write().await;
+		let cf_handle = self.cf(&db, "block_heights")?;
+		db.put_cf(&cf_handle, Self::construct_block_height_key(height), block_id.to_vec())?;
+		Ok(())
+	}
+
+	/// Gets the block indexed at the given height.
+	pub async fn get_block_at_height(&self, height: u64) -> Result<Option<Block>, Error> {
+		let block_id = {
+			let db = self.db.read().await;
+			let cf_handle = self.cf(&db, "block_heights")?;
+			db.get_cf(&cf_handle, Self::construct_block_height_key(height))?
+		};
+		match block_id {
+			Some(block_id) => {
+				let block_id = Id(block_id
+					.try_into()
+					.map_err(|_| Error::msg("Invalid block id in height index"))?);
+				self.get_block(block_id).await
+			}
+			None => Ok(None),
+		}
+	}
+
+	/// Length-prefixed so that no label's keys are a prefix of another label's keys.
+	fn construct_block_label_prefix(label: &str) -> Vec<u8> {
+		let mut prefix = (label.len() as u32).to_be_bytes().to_vec();
+		prefix.extend_from_slice(label.as_bytes());
+		prefix
+	}
+
+	/// Tags a stored block with an application-defined label. Labels are metadata kept in a
+	/// secondary index, they are not part of the block and do not affect its id.
+	pub async fn label_block(&self, block_id: &Id, label: String) -> Result<(), Error> {
+		if !self.has_block(block_id.clone()).await? {
+			anyhow::bail!("Cannot label unknown block {}", block_id);
+		}
+		let mut key = Self::construct_block_label_prefix(&label);
+		key.extend_from_slice(block_id.as_ref());
+		let db = self.db.write().await;
+		let cf_handle = self.cf(&db, "block_labels")?;
+		db.put_cf(&cf_handle, key, b"")?;
+		Ok(())
+	}
+
+	/// Gets the ids of the blocks tagged with the label, ordered by id.
+	pub async fn blocks_with_label(&self, label: &str) -> Result<Vec<Id>, Error> {
+		let prefix = Self::construct_block_label_prefix(label);
+		let db = self.db.read().await;
+		let cf_handle = self.cf(&db, "block_labels")?;
+
+		let mut block_ids = Vec::new();
+		let mode = rocksdb::IteratorMode::From(&prefix, rocksdb::Direction::Forward);
+		for res in db.iterator_cf(&cf_handle, mode) {
+			let (key, _) = res?;
+			if !key.starts_with(&prefix) {
+				break;
+			}
+			block_ids.push(Id(key[prefix.len()..]
+				.try_into()
+				.map_err(|_| Error::msg("Invalid block id in label index"))?));
+		}
+		Ok(block_ids)
+	}
+
+	/// Checks that the blocks indexed by height form a well-formed chain: heights are
+	/// contiguous, each block's parent is the id of the block before it, and the tip is the
+	/// stored parent block, if any. Meant to be run on startup, before building on top of the
+	/// persisted chain.
+	pub async fn verify_persisted_chain(&self) -> Result<(), ChainIntegrityError> {
+		let indexed = {
+			let db = self.db.read().await;
+			let cf_handle = self.cf(&db, "block_heights")?;
+			db.iterator_cf(&cf_handle, rocksdb::IteratorMode::Start)
+				.map(|res| {
+					let (key, value) = res?;
+					let height = u64::from_be_bytes(
+						key.as_ref().try_into().map_err(|_| Error::msg("Invalid block height"))?,
+					);
+					let block_id = Id(value
+						.as_ref()
+						.try_into()
+						.map_err(|_| Error::msg("Invalid block id in height index"))?);
+					Ok((height, block_id))
+				})
+				.collect::<Result<Vec<_>, Error>>()?
+		};
+
+		let mut tip: Option<(u64, Id)> = None;
+		for (height, block_id) in indexed {
+			let block = self
+				.get_block(block_id.clone())
+				.await?
+				.ok_or(ChainIntegrityError::MissingBlock { height })?;
+			if block.id() != block_id {
+				return Err(ChainIntegrityError::CorruptBlock { height });
+			}
+			if let Some((tip_height, tip_id)) = &tip {
+				if height != tip_height + 1 {
+					return Err(ChainIntegrityError::HeightGap {
+						expected: tip_height + 1,
+						found: height,
+					});
+				}
+				if block.parent != tip_id.to_vec() {
+					return Err(ChainIntegrityError::BrokenParent { height });
+				}
+			}
+			tip = Some((height, block_id));
+		}
+
+		let parent_block = self.get_parent_block().await?;
+		if let (Some((height, tip_id)), Some((parent_height, parent_block))) = (tip, parent_block) {
+			if height != parent_height || tip_id != parent_block {
+				return Err(ChainIntegrityError::TipMismatch { height });
+			}
+		}
+		Ok(())
+	}
+
+	/// Number of write-ahead log syncs since the database was opened, as counted by RocksDB,
+	/// e.g. to check that [`RocksdbMempool::with_sync_writes`] is in effect.
+	pub async fn wal_syncs(&self) -> Result<u64, Error> {
+		let db = self.db.read().await;
+		let stats = db.property_value("rocksdb.dbstats")?.unwrap_or_default();
+		// e.g. "Cumulative WAL: 3 writes, 3 syncs, 1.00 writes per sync, ..."
+		stats
+			.lines()
+			.find_map(|line| line.strip_prefix("Cumulative WAL: "))
+			.and_then(|line| line.split(", ").nth(1))
+			.and_then(|syncs| syncs.strip_suffix(" syncs"))
+			.ok_or_else(|| Error::msg("No write-ahead log statistics"))?
+			.parse()
+			.map_err(Error::new)
+	}
+
+	/// Flushes the memtables of all column families of this mempool to disk.
+	pub async fn flush(&self) -> Result<(), Error> {
+		let db = self.db.read().await;
+		for name in COLUMN_FAMILIES {
+			db.flush_cf(&self.cf(&db, name)?)?;
+		}
+		Ok(())
+	}
+
+	/// Serializes the blocks indexed at heights in `start..end` into a single blob,
+	/// compressed if block compression is enabled. Heights without a block are skipped.
+	pub async fn export_blocks(&self, start: u64, end: u64) -> Result<Vec<u8>, Error> {
+		let mut blocks = Vec::new();
+		for height in start..end {
+			if let Some(block) = self.get_block_at_height(height).await? {
+				blocks.push((height, block));
+			}
+		}
+		self.encode_blocks(&blocks)
+	}
 
-		Ok(RocksdbMempool { db: Arc::new(RwLock::new(db)) })
+	/// Restores blocks from a blob produced by [`RocksdbMempool::export_blocks`], compressed
+	/// or not. The blob is rejected as a whole, before anything is written, unless its
+	/// heights are contiguous and each block's parent is the id of the block before it.
+	/// Blobs decoding to more than [`RocksdbMempool::with_max_decoded_size`] bytes are
+	/// rejected with [`DecodedSizeLimitExceeded`].
+	pub async fn import_blocks(&self, blob: &[u8]) -> Result<(), Error> {
+		let blocks: Vec<(u64, Block)> = self.decode_blocks(blob)?;
+		for pair in blocks.windows(2) {
+			let ((parent_height, parent), (height, block)) = (&pair[0], &pair[1]);
+			if *height != parent_height + 1 {
+				anyhow::bail!("Block height {} does not follow height {}", height, parent_height);
+			}
+			if block.parent != parent.id().to_vec() {
+				anyhow::bail!(
+					"Block at height {} does not link to the block at height {}",
+					height,
+					parent_height
+				);
+			}
+		}
+		for (height, block) in blocks {
+			self.add_block_at_height(height, block).await?;
+		}
+		Ok(())
 	}
 
-	pub fn construct_mempool_transaction_key(transaction: &MempoolTransaction) -> String {
+	/// Key of the mempool transactions table, whose lexicographic order is the pop order:
+	/// the time slot, then the arrival with [`TransactionOrdering::Arrival`], then the
+	/// sequence number.
+	pub fn construct_mempool_transaction_key(
+		transaction: &MempoolTransaction,
+		ordering: TransactionOrdering,
+	) -> String {
 		// pad to 32 characters for slot_seconds
 		let slot_seconds_str = format!("{:032}", transaction.timestamp);
 
+		if ordering == TransactionOrdering::Arrival {
+			return format!(
+				"{}:{:032}:{:032}:{}",
+				slot_seconds_str,
+				transaction.arrival,
+				transaction.transaction.sequence_number,
+				transaction.transaction.id()
+			);
+		}
+
 		// pad to 32 characters for sequence number
 		let sequence_number_str = format!("{:032}", transaction.transaction.sequence_number);
 
@@ -50,15 +514,186 @@ impl RocksdbMempool {
 		key
 	}
 
+	/// Key of the sender and sequence number lookup table: the sender followed by the
+	/// big-endian sequence number.
+	pub fn construct_sender_sequence_key(sender: &Id, sequence_number: u64) -> Vec<u8> {
+		let mut key = sender.to_vec();
+		key.extend_from_slice(&sequence_number.to_be_bytes());
+		key
+	}
+
+	/// Removes the sender and sequence number lookup of the transaction, unless the slot
+	/// has since been taken by another transaction.
+	fn remove_sender_sequence_lookup(&self, db: &DB, tx: &MempoolTransaction) -> Result<(), Error> {
+		let cf_handle = self.cf(&db, "sender_sequence_lookups")?;
+		let key = Self::construct_sender_sequence_key(
+			&tx.transaction.sender,
+			tx.transaction.sequence_number,
+		);
+		if db.get_cf(&cf_handle, &key)? == Some(tx.id().to_vec()) {
+			db.delete_cf(&cf_handle, &key)?;
+		}
+		Ok(())
+	}
+
+	/// Deserializes a mempool entry. An entry that fails to deserialize yields `None` if
+	/// [`RocksdbMempool::with_quarantine_corrupt`] is set, for the caller to quarantine it,
+	/// otherwise the error is returned.
+	fn decode_mempool_transaction(
+		&self,
+		value: &[u8],
+	) -> Result<Option<MempoolTransaction>, Error> {
+		match serde_json::from_slice(value) {
+			Ok(tx) => Ok(Some(tx)),
+			Err(_) if self.quarantine_corrupt => Ok(None),
+			Err(e) => Err(e.into()),
+		}
+	}
+
+	/// Id of the transaction of a mempool entry, decoded from the end of its key, see
+	/// [`RocksdbMempool::construct_mempool_transaction_key`].
+	fn mempool_transaction_key_id(key: &[u8]) -> Option<Id> {
+		let key = std::str::from_utf8(key).ok()?;
+		Id::from_hex(key.rsplit(':').next()?).ok()
+	}
+
+	/// Moves the corrupted mempool entry stored under `key` to the `quarantine` column family
+	/// and removes the id lookup pointing at it, in a single write batch. Must be called with
+	/// the database write lock held. Does nothing if the entry changed since `value` was read.
+	///
+	/// The sender of the entry is lost with it, so its sender and sequence number lookup is
+	/// left until the slot is taken again. It resolves to no transaction meanwhile, as the id
+	/// it refers to no longer has a lookup.
+	fn quarantine_entry(&self, db: &DB, key: &[u8], value: &[u8]) -> Result<(), Error> {
+		let cf_handle = self.cf(db, "mempool_transactions")?;
+		if db.get_cf(cf_handle, key)?.as_deref() != Some(value) {
+			return Ok(());
+		}
+		let mut batch = WriteBatch::default();
+		batch.put_cf(self.cf(db, "quarantine")?, key, value);
+		batch.delete_cf(cf_handle, key);
+
+		// unless the id was added again since, under another key
+		if let Some(id) = Self::mempool_transaction_key_id(key) {
+			let lookups_cf_handle = self.cf(db, "transaction_lookups")?;
+			if db.get_cf(lookups_cf_handle, id.to_vec())?.as_deref() == Some(key) {
+				batch.delete_cf(lookups_cf_handle, id.to_vec());
+			}
+		}
+		db.write(batch)?;
+		self.pending.fetch_sub(1, Ordering::SeqCst);
+		Ok(())
+	}
+
+	/// Quarantines the corrupted entries found by a read, which held only the read lock, see
+	/// [`RocksdbMempool::quarantine_entry`].
+	async fn quarantine_entries(&self, entries: Vec<(Vec<u8>, Vec<u8>)>) -> Result<(), Error> {
+		if entries.is_empty() {
+			return Ok(());
+		}
+		let db = self.db.write().await;
+		for (key, value) in entries {
+			self.quarantine_entry(&db, &key, &value)?;
+		}
+		Ok(())
+	}
+
+	/// Adds the writes persisting the transaction and its lookups to the batch, returning the
+	/// key of the mempool entry.
+	fn batch_mempool_transaction(
+		&self,
+		db: &DB,
+		batch: &mut WriteBatch,
+		tx: &MempoolTransaction,
+	) -> Result<String, Error> {
+		let serialized_tx = serde_json::to_vec(tx)?;
+		let mempool_transactions_cf_handle = self.cf(&db, "mempool_transactions")?;
+		let transaction_lookups_cf_handle = self.cf(&db, "transaction_lookups")?;
+		let sender_sequence_lookups_cf_handle = self.cf(&db, "sender_sequence_lookups")?;
+
+		let key = Self::construct_mempool_transaction_key(tx, self.ordering);
+		batch.put_cf(&mempool_transactions_cf_handle, &key, &serialized_tx);
+		batch.put_cf(&transaction_lookups_cf_handle, tx.transaction.id().to_vec(), &key);
+		batch.put_cf(
+			&sender_sequence_lookups_cf_handle,
+			Self::construct_sender_sequence_key(
+				&tx.transaction.sender,
+				tx.transaction.sequence_number,
+			),
+			tx.transaction.id().to_vec(),
+		);
+		Ok(key)
+	}
+
+	/// Stamps the transaction with the next arrival and adds the write persisting the arrival
+	/// after it to the batch. Must be called with the database write lock held, so that the
+	/// stamps are written in the order they are taken.
+	fn stamp_arrival(
+		&self,
+		db: &DB,
+		batch: &mut WriteBatch,
+		tx: &mut MempoolTransaction,
+	) -> Result<(), Error> {
+		tx.arrival = self.next_arrival.fetch_add(1, Ordering::SeqCst);
+		let cf_handle = self.cf(db, "chain_state")?;
+		batch.put_cf(&cf_handle, NEXT_ARRIVAL_KEY, (tx.arrival + 1).to_be_bytes());
+		Ok(())
+	}
+
+	/// Writes a batch of added transactions, synced according to
+	/// [`RocksdbMempool::with_sync_writes`], counting the entries it creates under `keys`
+	/// rather than overwrites.
+	fn write_transactions(
+		&self,
+		db: &DB,
+		batch: WriteBatch,
+		keys: BTreeSet<String>,
+	) -> Result<(), Error> {
+		let cf_handle = self.cf(db, "mempool_transactions")?;
+		let mut created = 0;
+		for key in keys {
+			if db.get_cf(cf_handle, key)?.is_none() {
+				created += 1;
+			}
+		}
+		let mut options = WriteOptions::default();
+		options.set_sync(self.sync_writes);
+		db.write_opt(batch, &options)?;
+		self.pending.fetch_add(created, Ordering::SeqCst);
+		Ok(())
+	}
+
+	/// Adds the transactions of the stream to the mempool, slotted at the time they are read,
+	/// writing them in RocksDB write batches of `batch` transactions. At most one batch is held
+	/// in memory, so that restoring a large dump does not require loading it whole.
+	pub async fn import_pending_stream<S: Stream<Item = Transaction>>(
+		&self,
+		stream: S,
+		batch: usize,
+	) -> Result<(), Error> {
+		anyhow::ensure!(batch > 0, "Import batch size must be positive");
+		let mut chunks = std::pin::pin!(stream.chunks(batch));
+		while let Some(transactions) = chunks.next().await {
+			let db = self.db.write().await;
+			let mut write_batch = WriteBatch::default();
+			let mut keys = BTreeSet::new();
+			for transaction in transactions {
+				let mut tx = MempoolTransaction::slot_now(transaction);
+				self.stamp_arrival(&db, &mut write_batch, &mut tx)?;
+				keys.insert(self.batch_mempool_transaction(&db, &mut write_batch, &tx)?);
+			}
+			self.write_transactions(&db, write_batch, keys)?;
+		}
+		Ok(())
+	}
+
 	/// Helper function to retrieve the key for mempool transaction from the lookup table.
 	async fn get_mempool_transaction_key(
 		&self,
 		transaction_id: &Id,
 	) -> Result<Option<Vec<u8>>, Error> {
 		let db = self.db.read().await;
-		let cf_handle = db
-			.cf_handle("transaction_lookups")
-			.ok_or_else(|| Error::msg("CF handle not found"))?;
+		let cf_handle = self.cf(&db, "transaction_lookups")?;
 		db.get_cf(&cf_handle, transaction_id.to_vec()).map_err(|e| Error::new(e))
 	}
 }
@@ -69,28 +704,30 @@ impl MempoolTransactionOperations for RocksdbMempool {
 		match key {
 			Some(k) => {
 				let db = self.db.read().await;
-				let cf_handle = db
-					.cf_handle("mempool_transactions")
-					.ok_or_else(|| Error::msg("CF handle not found"))?;
+				let cf_handle = self.cf(&db, "mempool_transactions")?;
 				Ok(db.get_cf(&cf_handle, k)?.is_some())
 			}
 			None => Ok(false),
 		}
 	}
 
-	async fn add_mempool_transaction(&self, tx: MempoolTransaction) -> Result<(), Error> {
-		let serialized_tx = serde_json::to_vec(&tx)?;
+	/// Adds the transaction, stamped with the next arrival of this mempool.
+	async fn add_mempool_transaction(&self, mut tx: MempoolTransaction) -> Result<(), Error> {
 		let db = self.db.write().await;
-		let mempool_transactions_cf_handle = db
-			.cf_handle("mempool_transactions")
-			.ok_or_else(|| Error::msg("CF handle not found"))?;
-		let transaction_lookups_cf_handle = db
-			.cf_handle("transaction_lookups")
-			.ok_or_else(|| Error::msg("CF handle not found"))?;
+		let mut batch = WriteBatch::default();
+		self.stamp_arrival(&db, &mut batch, &mut tx)?;
+		let key = self.batch_mempool_transaction(&db, &mut batch, &tx)?;
+		self.write_transactions(&db, batch, BTreeSet::from([key]))?;
 
-		let key = Self::construct_mempool_transaction_key(&tx);
-		db.put_cf(&mempool_transactions_cf_handle, &key, &serialized_tx)?;
-		db.put_cf(&transaction_lookups_cf_handle, tx.transaction.id().to_vec(), &key)?;
+		Ok(())
+	}
+
+	/// Puts the transaction back with the arrival it was popped with.
+	async fn reinsert_mempool_transaction(&self, tx: MempoolTransaction) -> Result<(), Error> {
+		let db = self.db.write().await;
+		let mut batch = WriteBatch::default();
+		let key = self.batch_mempool_transaction(&db, &mut batch, &tx)?;
+		self.write_transactions(&db, batch, BTreeSet::from([key]))?;
 
 		Ok(())
 	}
@@ -101,13 +738,18 @@ impl MempoolTransactionOperations for RocksdbMempool {
 		match key {
 			Some(k) => {
 				let db = self.db.write().await;
-				let cf_handle = db
-					.cf_handle("mempool_transactions")
-					.ok_or_else(|| Error::msg("CF handle not found"))?;
-				db.delete_cf(&cf_handle, k)?;
-				let lookups_cf_handle = db
-					.cf_handle("transaction_lookups")
-					.ok_or_else(|| Error::msg("CF handle not found"))?;
+				let cf_handle = self.cf(&db, "mempool_transactions")?;
+				if let Some(serialized_tx) = db.get_cf(&cf_handle, &k)? {
+					match self.decode_mempool_transaction(&serialized_tx)? {
+						Some(tx) => {
+							self.remove_sender_sequence_lookup(&db, &tx)?;
+							db.delete_cf(&cf_handle, k)?;
+							self.pending.fetch_sub(1, Ordering::SeqCst);
+						}
+						None => self.quarantine_entry(&db, &k, &serialized_tx)?,
+					}
+				}
+				let lookups_cf_handle = self.cf(&db, "transaction_lookups")?;
 				db.delete_cf(&lookups_cf_handle, transaction_id.to_vec())?;
 			}
 			None => (),
@@ -124,79 +766,161 @@ impl MempoolTransactionOperations for RocksdbMempool {
 			Some(k) => k,
 			None => return Ok(None), // If no key found in lookup, return None
 		};
-		let db = self.db.read().await;
-		let cf_handle = db
-			.cf_handle("mempool_transactions")
-			.ok_or_else(|| Error::msg("CF handle not found"))?;
-		match db.get_cf(&cf_handle, &key)? {
-			Some(serialized_tx) => {
-				let tx: MempoolTransaction = serde_json::from_slice(&serialized_tx)?;
-				Ok(Some(tx))
+		let serialized_tx = {
+			let db = self.db.read().await;
+			let cf_handle = self.cf(&db, "mempool_transactions")?;
+			match db.get_cf(&cf_handle, &key)? {
+				Some(serialized_tx) => serialized_tx,
+				None => return Ok(None),
+			}
+		};
+		let tx = self.decode_mempool_transaction(&serialized_tx)?;
+		if tx.is_none() {
+			self.quarantine_entries(vec![(key, serialized_tx)]).await?;
+		}
+		Ok(tx)
+	}
+
+	async fn get_mempool_transaction_by_sender_sequence(
+		&self,
+		sender: Id,
+		sequence_number: u64,
+	) -> Result<Option<MempoolTransaction>, Error> {
+		let transaction_id = {
+			let db = self.db.read().await;
+			let cf_handle = self.cf(&db, "sender_sequence_lookups")?;
+			let key = Self::construct_sender_sequence_key(&sender, sequence_number);
+			match db.get_cf(&cf_handle, key)? {
+				Some(transaction_id) => Id(transaction_id
+					.as_slice()
+					.try_into()
+					.map_err(|_| Error::msg("Invalid transaction id in lookup"))?),
+				None => return Ok(None),
+			}
+		};
+		self.get_mempool_transaction(transaction_id).await
+	}
+
+	async fn find_mempool_transactions<F>(
+		&self,
+		predicate: F,
+		limit: usize,
+	) -> Result<Vec<MempoolTransaction>, Error>
+	where
+		F: Fn(&MempoolTransaction) -> bool,
+	{
+		let mut found = Vec::new();
+		let mut corrupt = Vec::new();
+		{
+			let db = self.db.read().await;
+			let cf_handle = self.cf(&db, "mempool_transactions")?;
+			for res in db.iterator_cf(&cf_handle, rocksdb::IteratorMode::Start) {
+				if found.len() >= limit {
+					break;
+				}
+				let (key, value) = res?;
+				let Some(tx) = self.decode_mempool_transaction(&value)? else {
+					corrupt.push((key.into_vec(), value.into_vec()));
+					continue;
+				};
+				if predicate(&tx) {
+					found.push(tx);
+				}
 			}
-			None => Ok(None),
 		}
+		self.quarantine_entries(corrupt).await?;
+		Ok(found)
+	}
+
+	/// Number of mempool entries, kept up to date as they are written rather than counted.
+	/// Corrupted entries count until they are read and quarantined.
+	async fn size(&self) -> Result<u64, Error> {
+		Ok(self.pending.load(Ordering::SeqCst))
 	}
 
 	async fn pop_mempool_transaction(&self) -> Result<Option<MempoolTransaction>, Error> {
 		let db = self.db.write().await;
-		let cf_handle = db
-			.cf_handle("mempool_transactions")
-			.ok_or_else(|| Error::msg("CF handle not found"))?;
-		let mut iter = db.iterator_cf(&cf_handle, rocksdb::IteratorMode::Start);
-
-		match iter.next() {
-			None => return Ok(None), // No transactions to pop
-			Some(res) => {
-				let (key, value) = res?;
-				let tx: MempoolTransaction = serde_json::from_slice(&value)?;
-				db.delete_cf(&cf_handle, &key)?;
+		let cf_handle = self.cf(&db, "mempool_transactions")?;
+		let iter = db.iterator_cf(&cf_handle, rocksdb::IteratorMode::Start);
 
-				// Optionally, remove from the lookup table as well
-				let lookups_cf_handle = db
-					.cf_handle("transaction_lookups")
-					.ok_or_else(|| Error::msg("CF handle not found"))?;
-				db.delete_cf(&lookups_cf_handle, tx.transaction.id().to_vec())?;
+		for res in iter {
+			let (key, value) = res?;
+			let Some(tx) = self.decode_mempool_transaction(&value)? else {
+				self.quarantine_entry(&db, &key, &value)?;
+				continue;
+			};
+			db.delete_cf(&cf_handle, &key)?;
+			self.pending.fetch_sub(1, Ordering::SeqCst);
 
-				Ok(Some(tx))
-			}
+			// Optionally, remove from the lookup table as well
+			let lookups_cf_handle = self.cf(&db, "transaction_lookups")?;
+			db.delete_cf(&lookups_cf_handle, tx.transaction.id().to_vec())?;
+			self.remove_sender_sequence_lookup(&db, &tx)?;
+
+			return Ok(Some(tx));
 		}
+		Ok(None) // No transactions to pop
 	}
 }
 
 impl MempoolBlockOperations for RocksdbMempool {
 	async fn has_block(&self, block_id: Id) -> Result<bool, Error> {
 		let db = self.db.read().await;
-		let cf_handle = db.cf_handle("blocks").ok_or_else(|| Error::msg("CF handle not found"))?;
+		let cf_handle = self.cf(&db, "blocks")?;
 		Ok(db.get_cf(&cf_handle, block_id.to_vec())?.is_some())
 	}
 
 	async fn add_block(&self, block: Block) -> Result<(), Error> {
-		let serialized_block = serde_json::to_vec(&block)?;
+		let serialized_block = self.encode_blocks(&block)?;
 		let db = self.db.write().await;
-		let cf_handle = db.cf_handle("blocks").ok_or_else(|| Error::msg("CF handle not found"))?;
+		let cf_handle = self.cf(&db, "blocks")?;
 		db.put_cf(&cf_handle, block.id().to_vec(), &serialized_block)?;
 		Ok(())
 	}
 
 	async fn remove_block(&self, block_id: Id) -> Result<(), Error> {
 		let db = self.db.write().await;
-		let cf_handle = db.cf_handle("blocks").ok_or_else(|| Error::msg("CF handle not found"))?;
+		let cf_handle = self.cf(&db, "blocks")?;
 		db.delete_cf(&cf_handle, block_id.to_vec())?;
 		Ok(())
 	}
 
 	async fn get_block(&self, block_id: Id) -> Result<Option<Block>, Error> {
 		let db = self.db.read().await;
-		let cf_handle = db.cf_handle("blocks").ok_or_else(|| Error::msg("CF handle not found"))?;
+		let cf_handle = self.cf(&db, "blocks")?;
 		let serialized_block = db.get_cf(&cf_handle, block_id.to_vec())?;
 		match serialized_block {
 			Some(serialized_block) => {
-				let block: Block = serde_json::from_slice(&serialized_block)?;
+				let block: Block = self.decode_blocks(&serialized_block)?;
 				Ok(Some(block))
 			}
 			None => Ok(None),
 		}
 	}
+
+	/// Persists the parent block in the `chain_state` column family, its height and id in a
+	/// single value so that they are never out of step.
+	async fn set_parent_block(&self, height: u64, parent_block: Id) -> Result<(), Error> {
+		let db = self.db.write().await;
+		let cf_handle = self.cf(&db, "chain_state")?;
+		let value = [height.to_be_bytes().as_slice(), &parent_block.0].concat();
+		db.put_cf(&cf_handle, PARENT_BLOCK_KEY, value)?;
+		Ok(())
+	}
+
+	async fn get_parent_block(&self) -> Result<Option<(u64, Id)>, Error> {
+		let db = self.db.read().await;
+		let cf_handle = self.cf(&db, "chain_state")?;
+		match db.get_cf(&cf_handle, PARENT_BLOCK_KEY)? {
+			Some(value) if value.len() == 8 + 32 => {
+				let (height, parent_block) = value.split_at(8);
+				let height = u64::from_be_bytes(height.try_into()?);
+				Ok(Some((height, Id(parent_block.try_into()?))))
+			}
+			Some(_) => Err(Error::msg("Invalid stored parent block")),
+			None => Ok(None),
+		}
+	}
 }
 
 #[cfg(test)]
@@ -233,6 +957,31 @@ pub mod test {
 		Ok(())
 	}
 
+	#[tokio::test]
+	async fn test_chains_share_database_without_sharing_data() -> Result<(), Error> {
+		let temp_dir = tempdir().unwrap();
+		let path = temp_dir.path().to_str().unwrap();
+		let mut chains = RocksdbMempool::try_new_chains(path, &["a", "b"])?;
+		let a = chains.remove("a").unwrap();
+		let b = chains.remove("b").unwrap();
+
+		let tx = MempoolTransaction::test();
+		a.add_mempool_transaction(tx.clone()).await?;
+		assert!(a.has_mempool_transaction(tx.id()).await?);
+		assert!(!b.has_mempool_transaction(tx.id()).await?);
+		assert_eq!(b.pop_mempool_transaction().await?, None);
+		drop((a, b));
+
+		// reopening a single chain keeps the column families of the other
+		let mut chains = RocksdbMempool::try_new_chains(path, &["a"])?;
+		let a = chains.remove("a").unwrap();
+		assert_eq!(a.pop_mempool_transaction().await?, Some(tx));
+
+		assert!(RocksdbMempool::try_new_chains(path, &["a/b"]).is_err());
+
+		Ok(())
+	}
+
 	#[tokio::test]
 	async fn test_rocksdb_transaction_operations() -> Result<(), Error> {
 		let temp_dir = tempdir().unwrap();
@@ -251,6 +1000,41 @@ pub mod test {
 		Ok(())
 	}
 
+	#[tokio::test]
+	async fn test_sender_sequence_lookup() -> Result<(), Error> {
+		let temp_dir = tempdir().unwrap();
+		let path = temp_dir.path().to_str().unwrap();
+		let mempool = RocksdbMempool::try_new(path)?;
+
+		let sender = Id([1; 32]);
+		let tx = MempoolTransaction::at_time(
+			Transaction::new(vec![1], 3).with_sender(sender.clone()),
+			2,
+		);
+		mempool.add_mempool_transaction(tx.clone()).await?;
+
+		assert_eq!(
+			mempool
+				.get_mempool_transaction_by_sender_sequence(sender.clone(), 3)
+				.await?
+				.map(|tx| tx.transaction),
+			Some(tx.transaction.clone())
+		);
+		assert_eq!(
+			mempool.get_mempool_transaction_by_sender_sequence(sender.clone(), 4).await?,
+			None
+		);
+		assert_eq!(
+			mempool.get_mempool_transaction_by_sender_sequence(Id::default(), 3).await?,
+			None
+		);
+
+		mempool.pop_mempool_transaction().await?;
+		assert_eq!(mempool.get_mempool_transaction_by_sender_sequence(sender, 3).await?, None);
+
+		Ok(())
+	}
+
 	#[tokio::test]
 	async fn test_transaction_slot_based_ordering() -> Result<(), Error> {
 		let temp_dir = tempdir().unwrap();
@@ -266,9 +1050,9 @@ pub mod test {
 		mempool.add_mempool_transaction(tx3.clone()).await?;
 
 		let txs = mempool.pop_mempool_transactions(3).await?;
-		assert_eq!(txs[0], tx1);
-		assert_eq!(txs[1], tx2);
-		assert_eq!(txs[2], tx3);
+		assert_eq!(txs[0].transaction, tx1.transaction);
+		assert_eq!(txs[1].transaction, tx2.transaction);
+		assert_eq!(txs[2].transaction, tx3.transaction);
 
 		Ok(())
 	}
@@ -277,7 +1061,8 @@ pub mod test {
 	async fn test_transaction_sequence_number_based_ordering() -> Result<(), Error> {
 		let temp_dir = tempdir().unwrap();
 		let path = temp_dir.path().to_str().unwrap();
-		let mempool = RocksdbMempool::try_new(path)?;
+		let mempool =
+			RocksdbMempool::try_new(path)?.with_ordering(TransactionOrdering::SequenceNumber);
 
 		let tx1 = MempoolTransaction::at_time(Transaction::new(vec![1], 0), 2);
 		let tx2 = MempoolTransaction::at_time(Transaction::new(vec![2], 1), 2);
@@ -288,9 +1073,9 @@ pub mod test {
 		mempool.add_mempool_transaction(tx3.clone()).await?;
 
 		let txs = mempool.pop_mempool_transactions(3).await?;
-		assert_eq!(txs[0], tx1);
-		assert_eq!(txs[1], tx2);
-		assert_eq!(txs[2], tx3);
+		assert_eq!(txs[0].transaction, tx1.transaction);
+		assert_eq!(txs[1].transaction, tx2.transaction);
+		assert_eq!(txs[2].transaction, tx3.transaction);
 
 		Ok(())
 	}
@@ -299,7 +1084,8 @@ pub mod test {
 	async fn test_slot_and_transaction_based_ordering() -> Result<(), Error> {
 		let temp_dir = tempdir().unwrap();
 		let path = temp_dir.path().to_str().unwrap();
-		let mempool = RocksdbMempool::try_new(path)?;
+		let mempool =
+			RocksdbMempool::try_new(path)?.with_ordering(TransactionOrdering::SequenceNumber);
 
 		let tx1 = MempoolTransaction::at_time(Transaction::new(vec![1], 0), 0);
 		let tx2 = MempoolTransaction::at_time(Transaction::new(vec![2], 1), 0);
@@ -310,11 +1096,440 @@ pub mod test {
 		mempool.add_mempool_transaction(tx3.clone()).await?;
 
 		let txs = mempool.pop_mempool_transactions(3).await?;
-		assert_eq!(txs[0], tx1);
-		assert_eq!(txs[1], tx2);
-		assert_eq!(txs[2], tx3);
+		assert_eq!(txs[0].transaction, tx1.transaction);
+		assert_eq!(txs[1].transaction, tx2.transaction);
+		assert_eq!(txs[2].transaction, tx3.transaction);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_arrival_and_sequence_number_ordering() -> Result<(), Error> {
+		for (ordering, expected) in [
+			(TransactionOrdering::Arrival, [2, 0, 1]),
+			(TransactionOrdering::SequenceNumber, [0, 1, 2]),
+		] {
+			let temp_dir = tempdir().unwrap();
+			let path = temp_dir.path().to_str().unwrap();
+			let mempool = RocksdbMempool::try_new(path)?.with_ordering(ordering);
+
+			// arrive out of sequence number order, within the same slot
+			for sequence_number in [2, 0, 1] {
+				let transaction = Transaction::new(vec![sequence_number as u8], sequence_number);
+				mempool
+					.add_mempool_transaction(MempoolTransaction::at_time(transaction, 0))
+					.await?;
+			}
+
+			let popped: Vec<_> = mempool
+				.pop_transactions(3)
+				.await?
+				.into_iter()
+				.map(|transaction| transaction.sequence_number)
+				.collect();
+			assert_eq!(popped, expected, "{ordering:?}");
+		}
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_arrival_survives_reopen() -> Result<(), Error> {
+		let temp_dir = tempdir().unwrap();
+		let path = temp_dir.path().to_str().unwrap();
+
+		// the second transaction arrives after a restart, as the first in its process
+		let transactions: Vec<_> = (0..2u8).map(|i| Transaction::new(vec![i], 1)).collect();
+		for transaction in &transactions {
+			let mempool = RocksdbMempool::try_new(path)?;
+			let mut tx = MempoolTransaction::at_time(transaction.clone(), 0);
+			tx.arrival = 0;
+			mempool.add_mempool_transaction(tx).await?;
+		}
+
+		let mempool = RocksdbMempool::try_new(path)?;
+		let mut popped = mempool.pop_mempool_transactions(2).await?;
+		assert_eq!(popped.iter().map(|tx| tx.arrival).collect::<Vec<_>>(), vec![0, 1]);
+		assert_eq!(
+			popped.iter().map(|tx| tx.transaction.clone()).collect::<Vec<_>>(),
+			transactions
+		);
+
+		// a reinserted transaction keeps its arrival, new ones keep counting
+		mempool.add_mempool_transaction(popped.pop().unwrap()).await?;
+		mempool.reinsert_mempool_transaction(popped.pop().unwrap()).await?;
+		let arrivals: Vec<_> =
+			mempool.pop_mempool_transactions(2).await?.iter().map(|tx| tx.arrival).collect();
+		assert_eq!(arrivals, vec![0, 2]);
+
+		Ok(())
+	}
+
+	fn linked_blocks(count: u8) -> Vec<Block> {
+		let mut parent = Id::genesis_block().to_vec();
+		let mut blocks = Vec::new();
+		for i in 0..count {
+			let block =
+				Block::new(Default::default(), parent.clone(), vec![Transaction::new(vec![i], 0)]);
+			parent = block.id().to_vec();
+			blocks.push(block);
+		}
+		blocks
+	}
+
+	#[tokio::test]
+	async fn test_export_import_blocks_round_trip() -> Result<(), Error> {
+		for compress in [false, true] {
+			let temp_dir = tempdir().unwrap();
+			let path = temp_dir.path().to_str().unwrap();
+			let mempool = RocksdbMempool::try_new(path)?.with_block_compression(compress);
+
+			let blocks = linked_blocks(5);
+			for (height, block) in blocks.iter().enumerate() {
+				mempool.add_block_at_height(height as u64 + 1, block.clone()).await?;
+			}
+			let blob = mempool.export_blocks(2, 5).await?;
+			assert_eq!(blob.starts_with(&ZSTD_MAGIC), compress);
+
+			let restore_dir = tempdir().unwrap();
+			let restore_path = restore_dir.path().to_str().unwrap();
+			let restored = RocksdbMempool::try_new(restore_path)?;
+			restored.import_blocks(&blob).await?;
+
+			assert_eq!(restored.get_block_at_height(1).await?, None);
+			for height in 2..5 {
+				let block = restored.get_block_at_height(height).await?;
+				assert_eq!(block.as_ref(), Some(&blocks[height as usize - 1]));
+			}
+			assert_eq!(restored.get_block_at_height(5).await?, None);
+			let (parent, child) =
+				(restored.get_block_at_height(2).await?, restored.get_block_at_height(3).await?);
+			assert_eq!(child.unwrap().parent, parent.unwrap().id().to_vec());
+		}
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_import_blocks_rejects_broken_linkage() -> Result<(), Error> {
+		let temp_dir = tempdir().unwrap();
+		let path = temp_dir.path().to_str().unwrap();
+		let mempool = RocksdbMempool::try_new(path)?;
+
+		let blocks = linked_blocks(3);
+		let unlinked = vec![(1, blocks[0].clone()), (2, blocks[2].clone())];
+		let blob = serde_json::to_vec(&unlinked)?;
+		assert!(mempool.import_blocks(&blob).await.is_err());
+		assert_eq!(mempool.get_block_at_height(1).await?, None);
+
+		let gap = vec![(1, blocks[0].clone()), (3, blocks[1].clone())];
+		let blob = serde_json::to_vec(&gap)?;
+		assert!(mempool.import_blocks(&blob).await.is_err());
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_import_blocks_rejects_oversized_payload() -> Result<(), Error> {
+		for compress in [false, true] {
+			let temp_dir = tempdir().unwrap();
+			let path = temp_dir.path().to_str().unwrap();
+			let mempool = RocksdbMempool::try_new(path)?.with_block_compression(compress);
+
+			// highly compressible, decoding to far more than the limit
+			let mut parent = vec![0; 32];
+			for height in 1..=4 {
+				let block = Block::new(
+					Default::default(),
+					parent,
+					vec![Transaction::new(vec![0; 1 << 16], 0)],
+				);
+				parent = block.id().to_vec();
+				mempool.add_block_at_height(height, block).await?;
+			}
+			let blob = mempool.export_blocks(1, 5).await?;
+
+			let restore_dir = tempdir().unwrap();
+			let restore_path = restore_dir.path().to_str().unwrap();
+			let restored = RocksdbMempool::try_new(restore_path)?.with_max_decoded_size(1024);
+			let error = restored.import_blocks(&blob).await.unwrap_err();
+			let exceeded = error.downcast_ref::<DecodedSizeLimitExceeded>().expect("size limit");
+			assert_eq!(exceeded.limit, 1024);
+			assert_eq!(restored.get_block_at_height(1).await?, None);
+
+			// the same blob is accepted under the default limit
+			let restored = RocksdbMempool::try_new(restore_path)?;
+			restored.import_blocks(&blob).await?;
+			assert!(restored.get_block_at_height(4).await?.is_some());
+		}
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_verify_persisted_chain() -> Result<(), Error> {
+		let temp_dir = tempdir().unwrap();
+		let path = temp_dir.path().to_str().unwrap();
+		let mempool = RocksdbMempool::try_new(path)?;
+
+		let blocks = linked_blocks(3);
+		for (height, block) in blocks.iter().enumerate() {
+			mempool.add_block_at_height(height as u64 + 1, block.clone()).await?;
+		}
+		mempool.verify_persisted_chain().await.map_err(Error::new)?;
+		mempool.set_parent_block(3, blocks[2].id()).await?;
+		mempool.verify_persisted_chain().await.map_err(Error::new)?;
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_verify_persisted_chain_reports_corruption() -> Result<(), Error> {
+		let blocks = linked_blocks(4);
+
+		// broken parent: height 2 holds a block that does not link to height 1
+		let temp_dir = tempdir().unwrap();
+		let mempool = RocksdbMempool::try_new(temp_dir.path().to_str().unwrap())?;
+		mempool.add_block_at_height(1, blocks[0].clone()).await?;
+		mempool.add_block_at_height(2, blocks[2].clone()).await?;
+		assert!(matches!(
+			mempool.verify_persisted_chain().await,
+			Err(ChainIntegrityError::BrokenParent { height: 2 })
+		));
+
+		// height gap: height 3 is missing
+		let temp_dir = tempdir().unwrap();
+		let mempool = RocksdbMempool::try_new(temp_dir.path().to_str().unwrap())?;
+		for height in [1, 2, 4] {
+			mempool.add_block_at_height(height, blocks[height as usize - 1].clone()).await?;
+		}
+		assert!(matches!(
+			mempool.verify_persisted_chain().await,
+			Err(ChainIntegrityError::HeightGap { expected: 3, found: 4 })
+		));
+
+		// tip mismatch: the stored parent is not the last block
+		let temp_dir = tempdir().unwrap();
+		let mempool = RocksdbMempool::try_new(temp_dir.path().to_str().unwrap())?;
+		for (height, block) in blocks.iter().enumerate() {
+			mempool.add_block_at_height(height as u64 + 1, block.clone()).await?;
+		}
+		mempool.set_parent_block(3, blocks[2].id()).await?;
+		assert!(matches!(
+			mempool.verify_persisted_chain().await,
+			Err(ChainIntegrityError::TipMismatch { height: 4 })
+		));
+		// the stored parent is the last block, at another height
+		mempool.set_parent_block(3, blocks[3].id()).await?;
+		assert!(matches!(
+			mempool.verify_persisted_chain().await,
+			Err(ChainIntegrityError::TipMismatch { height: 4 })
+		));
+
+		// missing block: the height index points to a removed block
+		mempool.remove_block(blocks[1].id()).await?;
+		assert!(matches!(
+			mempool.verify_persisted_chain().await,
+			Err(ChainIntegrityError::MissingBlock { height: 2 })
+		));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_import_pending_stream() -> Result<(), Error> {
+		let temp_dir = tempdir().unwrap();
+		let path = temp_dir.path().to_str().unwrap();
+		let mempool = RocksdbMempool::try_new(path)?;
+
+		let count = 10_000u64;
+		let transactions =
+			futures::stream::iter(0..count).map(|i| Transaction::new(i.to_le_bytes().to_vec(), i));
+		mempool.import_pending_stream(transactions, 512).await?;
+
+		let imported = mempool.find_mempool_transactions(|_| true, usize::MAX).await?;
+		assert_eq!(imported.len() as u64, count);
+		for i in [0, 511, 512, count - 1] {
+			let transaction = Transaction::new(i.to_le_bytes().to_vec(), i);
+			assert!(mempool.has_mempool_transaction(transaction.id()).await?);
+			let by_sequence = mempool
+				.get_mempool_transaction_by_sender_sequence(transaction.sender.clone(), i)
+				.await?;
+			assert_eq!(by_sequence.map(|tx| tx.transaction), Some(transaction));
+		}
+
+		assert!(mempool.import_pending_stream(futures::stream::empty(), 0).await.is_err());
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_blocks_with_label() -> Result<(), Error> {
+		let temp_dir = tempdir().unwrap();
+		let path = temp_dir.path().to_str().unwrap();
+		let mempool = RocksdbMempool::try_new(path)?;
+
+		let blocks = linked_blocks(3);
+		for block in &blocks {
+			mempool.add_block(block.clone()).await?;
+		}
+		mempool.label_block(&blocks[0].id(), "epoch-1".to_string()).await?;
+		mempool.label_block(&blocks[1].id(), "epoch-1".to_string()).await?;
+		mempool.label_block(&blocks[2].id(), "epoch-10".to_string()).await?;
+
+		let mut expected = vec![blocks[0].id(), blocks[1].id()];
+		expected.sort();
+		assert_eq!(mempool.blocks_with_label("epoch-1").await?, expected);
+		assert_eq!(mempool.blocks_with_label("epoch-10").await?, vec![blocks[2].id()]);
+		assert!(mempool.blocks_with_label("epoch-2").await?.is_empty());
+
+		// labels do not change the block or its id
+		assert_eq!(mempool.get_block(blocks[0].id()).await?, Some(blocks[0].clone()));
+		assert!(mempool.label_block(&Id([0xff; 32]), "epoch-1".to_string()).await.is_err());
 
 		Ok(())
 	}
 
+	#[tokio::test]
+	async fn test_quarantine_corrupt_transaction() -> Result<(), Error> {
+		let temp_dir = tempdir().unwrap();
+		let path = temp_dir.path().to_str().unwrap();
+		let mempool = RocksdbMempool::try_new(path)?;
+
+		let transactions: Vec<_> = (0..3u8).map(|i| Transaction::new(vec![i], i as u64)).collect();
+		let mut keys = Vec::new();
+		for (slot, transaction) in transactions.iter().enumerate() {
+			let tx = MempoolTransaction::at_time(transaction.clone(), slot as u64);
+			keys.push(RocksdbMempool::construct_mempool_transaction_key(&tx, mempool.ordering));
+			mempool.add_mempool_transaction(tx).await?;
+		}
+		// corrupt the second entry in pop order
+		let garbage = b"not a transaction".to_vec();
+		{
+			let db = mempool.db.write().await;
+			let cf_handle = mempool.cf(&db, "mempool_transactions")?;
+			db.put_cf(&cf_handle, &keys[1], &garbage)?;
+		}
+
+		// without quarantine, the corrupted entry fails the pop and stays in place
+		assert_eq!(mempool.pop_transaction().await?, Some(transactions[0].clone()));
+		assert!(mempool.pop_transactions(2).await.is_err());
+		assert_eq!(mempool.size().await?, 2);
+
+		let mempool = mempool.with_quarantine_corrupt(true);
+		assert_eq!(mempool.pop_transactions(2).await?, vec![transactions[2].clone()]);
+		assert_eq!(mempool.size().await?, 0);
+		assert_eq!(mempool.quarantined().await?, vec![garbage]);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_quarantine_corrupt_transaction_on_every_read() -> Result<(), Error> {
+		let temp_dir = tempdir().unwrap();
+		let path = temp_dir.path().to_str().unwrap();
+		let mempool = RocksdbMempool::try_new(path)?.with_quarantine_corrupt(true);
+
+		let transactions: Vec<_> = (0..4u8).map(|i| Transaction::new(vec![i], i as u64)).collect();
+		for (slot, transaction) in transactions.iter().enumerate() {
+			let tx = MempoolTransaction::at_time(transaction.clone(), slot as u64);
+			mempool.add_mempool_transaction(tx).await?;
+		}
+		// keys of the entries in pop order, i.e. in the order of the transactions
+		let keys = {
+			let db = mempool.db.read().await;
+			let cf_handle = mempool.cf(&db, "mempool_transactions")?;
+			db.iterator_cf(cf_handle, rocksdb::IteratorMode::Start)
+				.map(|res| Ok(res?.0))
+				.collect::<Result<Vec<_>, Error>>()?
+		};
+		let garbage = b"not a transaction".to_vec();
+		let corrupt = |key: &[u8]| -> Result<(), Error> {
+			let db = mempool.db.try_write()?;
+			let cf_handle = mempool.cf(&db, "mempool_transactions")?;
+			db.put_cf(cf_handle, key, &garbage)?;
+			Ok(())
+		};
+
+		corrupt(&keys[0])?;
+		// counted until it is read
+		assert_eq!(mempool.size().await?, 4);
+		assert_eq!(mempool.find_transactions(|_| true, 1).await?, transactions[1..2]);
+		assert_eq!(mempool.size().await?, 3);
+		assert_eq!(mempool.quarantined().await?.len(), 1);
+
+		corrupt(&keys[1])?;
+		assert_eq!(mempool.find_transactions(|_| true, 4).await?, transactions[2..]);
+		assert_eq!(mempool.quarantined().await?.len(), 2);
+
+		corrupt(&keys[2])?;
+		assert_eq!(mempool.get_mempool_transaction(transactions[2].id()).await?, None);
+		assert_eq!(mempool.quarantined().await?.len(), 3);
+
+		corrupt(&keys[3])?;
+		mempool.remove_mempool_transaction(transactions[3].id()).await?;
+		assert!(!mempool.has_mempool_transaction(transactions[3].id()).await?);
+		assert_eq!(mempool.size().await?, 0);
+		assert_eq!(mempool.quarantined().await?, vec![garbage.clone(); 4]);
+
+		// no lookup is left resolving to a quarantined entry
+		for transaction in &transactions {
+			let sender = transaction.sender.clone();
+			let sequence_number = transaction.sequence_number;
+			assert_eq!(
+				mempool
+					.get_mempool_transaction_by_sender_sequence(sender, sequence_number)
+					.await?,
+				None
+			);
+		}
+		let db = mempool.db.read().await;
+		let cf_handle = mempool.cf(&db, "transaction_lookups")?;
+		assert_eq!(db.iterator_cf(cf_handle, rocksdb::IteratorMode::Start).count(), 0);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_size_is_kept_up_to_date() -> Result<(), Error> {
+		let temp_dir = tempdir().unwrap();
+		let path = temp_dir.path().to_str().unwrap();
+		let transactions: Vec<_> = (0..4u8).map(|i| Transaction::new(vec![i], i as u64)).collect();
+		{
+			let mempool = RocksdbMempool::try_new(path)?;
+			for transaction in &transactions[..3] {
+				mempool.add_transaction(transaction.clone()).await?;
+			}
+			assert_eq!(mempool.size().await?, 3);
+
+			let popped = mempool.pop_mempool_transaction().await?.expect("transaction");
+			assert_eq!(mempool.size().await?, 2);
+			mempool.reinsert_mempool_transaction(popped).await?;
+			assert_eq!(mempool.size().await?, 3);
+
+			mempool.remove_transaction(transactions[1].id()).await?;
+			mempool.remove_transaction(transactions[1].id()).await?;
+			assert_eq!(mempool.size().await?, 2);
+
+			mempool
+				.import_pending_stream(futures::stream::iter(transactions[3..].to_vec()), 2)
+				.await?;
+			assert_eq!(mempool.size().await?, 3);
+		}
+
+		// counted again on open
+		let mempool =
+			RocksdbMempool::try_new(path)?.with_ordering(TransactionOrdering::SequenceNumber);
+		assert_eq!(mempool.size().await?, 3);
+
+		// an entry overwritten under the same key is not counted twice
+		let tx = MempoolTransaction::at_time(Transaction::new(vec![9], 9), 0);
+		mempool.add_mempool_transaction(tx.clone()).await?;
+		mempool.add_mempool_transaction(tx).await?;
+		assert_eq!(mempool.size().await?, 4);
+		assert_eq!(mempool.pop_transactions(5).await?.len(), 4);
+		assert_eq!(mempool.size().await?, 0);
+
+		Ok(())
+	}
 }
@@ -2,6 +2,23 @@ use serde::{Deserialize, Serialize};
 
 use movement_types::{Block, Id, Transaction};
 use std::cmp::Ordering;
+use std::sync::atomic::{self, AtomicU64};
+
+/// Source of the arrival stamps of mempool transactions, shared by the whole process. It
+/// restarts at zero with the process, mempools persisting transactions restamp them.
+static ARRIVALS: AtomicU64 = AtomicU64::new(0);
+
+/// Order in which a mempool pops transactions of the same time slot. Transactions published
+/// with priority lead in either mode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TransactionOrdering {
+	/// Strict FIFO: transactions are popped in the order they arrived, see
+	/// [`MempoolTransaction::arrival`].
+	#[default]
+	Arrival,
+	/// Transactions are popped by sequence number.
+	SequenceNumber,
+}
 
 pub trait MempoolTransactionOperations {
 	// todo: move mempool_transaction methods into separate trait
@@ -24,6 +41,26 @@ pub trait MempoolTransactionOperations {
 		transaction_id: Id,
 	) -> Result<Option<MempoolTransaction>, anyhow::Error>;
 
+	/// Gets the mempool transaction occupying the given sender and sequence number slot.
+	async fn get_mempool_transaction_by_sender_sequence(
+		&self,
+		sender: Id,
+		sequence_number: u64,
+	) -> Result<Option<MempoolTransaction>, anyhow::Error>;
+
+	/// Scans the mempool in pop order without removing anything, returning up to `limit`
+	/// mempool transactions matching the predicate.
+	async fn find_mempool_transactions<F>(
+		&self,
+		predicate: F,
+		limit: usize,
+	) -> Result<Vec<MempoolTransaction>, anyhow::Error>
+	where
+		F: Fn(&MempoolTransaction) -> bool;
+
+	/// Number of transactions in the mempool.
+	async fn size(&self) -> Result<u64, anyhow::Error>;
+
 	/// Pops the next n mempool transactions from the mempool.
 	async fn pop_mempool_transactions(
 		&self,
@@ -40,6 +77,15 @@ pub trait MempoolTransactionOperations {
 		Ok(mempool_transactions)
 	}
 
+	/// Puts back a mempool transaction popped but not consumed, keeping its timestamp so that
+	/// it regains its place in the pop order.
+	async fn reinsert_mempool_transaction(
+		&self,
+		tx: MempoolTransaction,
+	) -> Result<(), anyhow::Error> {
+		self.add_mempool_transaction(tx).await
+	}
+
 	/// Checks whether the mempool has the transaction.
 	async fn has_transaction(&self, transaction_id: Id) -> Result<bool, anyhow::Error> {
 		self.has_mempool_transaction(transaction_id).await
@@ -75,6 +121,28 @@ pub trait MempoolTransactionOperations {
 		Ok(mempool_transaction.map(|mempool_transaction| mempool_transaction.transaction))
 	}
 
+	/// Scans the mempool without removing anything, returning up to `limit` transactions
+	/// matching the predicate.
+	async fn find_transactions<F>(
+		&self,
+		predicate: F,
+		limit: usize,
+	) -> Result<Vec<Transaction>, anyhow::Error>
+	where
+		F: Fn(&Transaction) -> bool,
+	{
+		let mempool_transactions = self
+			.find_mempool_transactions(
+				|mempool_transaction| predicate(&mempool_transaction.transaction),
+				limit,
+			)
+			.await?;
+		Ok(mempool_transactions
+			.into_iter()
+			.map(|mempool_transaction| mempool_transaction.transaction)
+			.collect())
+	}
+
 	/// Pops the next n transactions from the mempool.
 	async fn pop_transactions(&self, n: usize) -> Result<Vec<Transaction>, anyhow::Error> {
 		let mempool_transactions = self.pop_mempool_transactions(n).await?;
@@ -97,6 +165,17 @@ pub trait MempoolBlockOperations {
 
 	/// Gets a block from the mempool.
 	async fn get_block(&self, block_id: Id) -> Result<Option<Block>, anyhow::Error>;
+
+	/// Persists the height and id of the block that the next block will be built on.
+	/// Backends without persistence keep nothing.
+	async fn set_parent_block(&self, _height: u64, _parent_block: Id) -> Result<(), anyhow::Error> {
+		Ok(())
+	}
+
+	/// Gets the persisted height and id of the parent block, if one was ever set.
+	async fn get_parent_block(&self) -> Result<Option<(u64, Id)>, anyhow::Error> {
+		Ok(None)
+	}
 }
 
 /// Wraps a transaction with a timestamp for help ordering.
@@ -105,6 +184,23 @@ pub struct MempoolTransaction {
 	pub transaction: Transaction,
 	pub timestamp: u64,
 	pub slot_seconds: u64,
+	/// Identifies the indivisible group, e.g. an atomic bundle, the transaction belongs to.
+	#[serde(default)]
+	pub group: Option<Id>,
+	/// Time the mempool transaction was created, i.e. inserted, in milliseconds since the
+	/// Unix epoch. Only used to observe how long transactions wait, it is committed nowhere.
+	/// Zero for transactions persisted before it was recorded.
+	#[serde(default)]
+	pub inserted_at_ms: u64,
+	/// Monotonic stamp ordering transactions by arrival. Taken when the mempool transaction is
+	/// created, mempools persisting transactions replace it when adding the transaction with a
+	/// stamp from a sequence they persist, so that the order holds across restarts.
+	#[serde(default)]
+	pub arrival: u64,
+	/// Whether the transaction was published with priority by a trusted caller, which exempts
+	/// it from the block assembly rules that order and filter regular transactions.
+	#[serde(default)]
+	pub priority: bool,
 }
 
 impl PartialOrd for MempoolTransaction {
@@ -115,6 +211,7 @@ impl PartialOrd for MempoolTransaction {
 
 /// Ordered first by slot_seconds, then by transaction.
 /// This allows us to use a BTreeSet to order transactions by slot_seconds, and then by transaction and pop them off in order.
+/// The remaining fields break ties, so that only equal mempool transactions compare equal.
 impl Ord for MempoolTransaction {
 	fn cmp(&self, other: &Self) -> Ordering {
 		// First, compare by slot_seconds
@@ -130,7 +227,13 @@ impl Ord for MempoolTransaction {
 		}
 
 		// If sequence number is equal, then compare by transaction on the whole
-		self.transaction.cmp(&other.transaction)
+		self.transaction
+			.cmp(&other.transaction)
+			.then_with(|| self.timestamp.cmp(&other.timestamp))
+			.then_with(|| self.group.cmp(&other.group))
+			.then_with(|| self.inserted_at_ms.cmp(&other.inserted_at_ms))
+			.then_with(|| self.arrival.cmp(&other.arrival))
+			.then_with(|| self.priority.cmp(&other.priority))
 	}
 }
 
@@ -139,16 +242,48 @@ impl MempoolTransaction {
 
 	/// Creates a test MempoolTransaction.
 	pub fn test() -> Self {
-		Self { transaction: Transaction::test(), timestamp: 0, slot_seconds: Self::SLOT_SECONDS }
+		Self {
+			transaction: Transaction::test(),
+			timestamp: 0,
+			slot_seconds: Self::SLOT_SECONDS,
+			group: None,
+			inserted_at_ms: 0,
+			arrival: 0,
+			priority: false,
+		}
 	}
 
 	pub fn at_time(transaction: Transaction, timestamp: u64) -> Self {
 		let floor = (timestamp / Self::SLOT_SECONDS) * Self::SLOT_SECONDS;
-		Self { transaction, timestamp: floor, slot_seconds: Self::SLOT_SECONDS }
+		Self::new(transaction, floor, Self::SLOT_SECONDS)
 	}
 
 	pub fn new(transaction: Transaction, timestamp: u64, slot_seconds: u64) -> Self {
-		Self { transaction, timestamp, slot_seconds }
+		let inserted_at_ms = std::time::SystemTime::now()
+			.duration_since(std::time::UNIX_EPOCH)
+			.unwrap()
+			.as_millis() as u64;
+		let arrival = ARRIVALS.fetch_add(1, atomic::Ordering::Relaxed);
+		Self {
+			transaction,
+			timestamp,
+			slot_seconds,
+			group: None,
+			inserted_at_ms,
+			arrival,
+			priority: false,
+		}
+	}
+
+	pub fn with_group(mut self, group: Id) -> Self {
+		self.group = Some(group);
+		self
+	}
+
+	/// Marks the transaction as published with priority, see [`MempoolTransaction::priority`].
+	pub fn with_priority(mut self) -> Self {
+		self.priority = true;
+		self
 	}
 
 	/// Creates a new MempoolTransaction with the current timestamp floored to the nearest slot.
@@ -0,0 +1,124 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use mempool_util::{MempoolBlockOperations, MempoolTransactionOperations};
+use memseq::{Memseq, Sequencer, Transaction, VecMempool};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+
+/// Blocks drained from the mempool per measured iteration.
+const BLOCKS_PER_ITERATION: usize = 10;
+
+/// Transactions published per measured iteration.
+const PUBLISH_BATCH: u64 = 1_000;
+
+const BLOCK_SIZES: [u32; 3] = [10, 100, 1_000];
+
+// low enough that the timer never dominates, blocks are full when drained
+const BUILDING_TIME_MS: u64 = 1;
+
+/// Distinct transactions across all iterations, so that none is deduplicated by the mempool.
+fn next_transactions(counter: &AtomicU64, count: u64) -> Vec<Transaction> {
+	let start = counter.fetch_add(count, Ordering::Relaxed);
+	(start..start + count)
+		.map(|i| Transaction::new(i.to_le_bytes().to_vec(), i))
+		.collect()
+}
+
+/// Sequencer over a [`VecMempool`], which isolates block assembly from storage.
+fn in_memory(block_size: u32) -> Memseq<VecMempool> {
+	Memseq::try_in_memory(block_size, BUILDING_TIME_MS).unwrap()
+}
+
+/// Measures blocks per second assembled by `wait_for_next_block` from a mempool holding
+/// exactly [`BLOCKS_PER_ITERATION`] full blocks. Filling the mempool is not measured.
+async fn drain_blocks<T>(
+	memseq: &Memseq<T>,
+	block_size: u32,
+	counter: &AtomicU64,
+	iters: u64,
+) -> Duration
+where
+	T: MempoolBlockOperations + MempoolTransactionOperations,
+{
+	let block_size = block_size as u64;
+	let mut elapsed = Duration::ZERO;
+	for _ in 0..iters {
+		for transaction in next_transactions(counter, block_size * BLOCKS_PER_ITERATION as u64) {
+			memseq.publish(transaction).await.unwrap();
+		}
+		let start = Instant::now();
+		for _ in 0..BLOCKS_PER_ITERATION {
+			let block = memseq.wait_for_next_block().await.unwrap();
+			assert_eq!(block.map(|block| block.transactions.len() as u64), Some(block_size));
+		}
+		elapsed += start.elapsed();
+	}
+	elapsed
+}
+
+fn bench_wait_for_next_block(c: &mut Criterion) {
+	let runtime = Runtime::new().unwrap();
+	let counter = AtomicU64::new(0);
+	let mut group = c.benchmark_group("wait_for_next_block");
+	group.throughput(Throughput::Elements(BLOCKS_PER_ITERATION as u64));
+	for block_size in BLOCK_SIZES {
+		let memseq = in_memory(block_size);
+		group.bench_with_input(BenchmarkId::new("in_memory", block_size), &memseq, |b, memseq| {
+			b.to_async(&runtime)
+				.iter_custom(|iters| drain_blocks(memseq, block_size, &counter, iters));
+		});
+
+		let dir = tempfile::tempdir().unwrap();
+		let memseq = runtime
+			.block_on(Memseq::try_move_rocks(dir.path().to_path_buf()))
+			.unwrap()
+			.with_block_size(block_size)
+			.with_building_time_ms(BUILDING_TIME_MS);
+		group.bench_with_input(BenchmarkId::new("rocksdb", block_size), &memseq, |b, memseq| {
+			b.to_async(&runtime)
+				.iter_custom(|iters| drain_blocks(memseq, block_size, &counter, iters));
+		});
+	}
+	group.finish();
+}
+
+/// Measures transactions per second accepted by `publish`.
+async fn publish_batch<T>(memseq: &Memseq<T>, counter: &AtomicU64, iters: u64) -> Duration
+where
+	T: MempoolBlockOperations + MempoolTransactionOperations,
+{
+	let mut elapsed = Duration::ZERO;
+	for _ in 0..iters {
+		let transactions = next_transactions(counter, PUBLISH_BATCH);
+		let start = Instant::now();
+		for transaction in transactions {
+			memseq.publish(transaction).await.unwrap();
+		}
+		elapsed += start.elapsed();
+	}
+	elapsed
+}
+
+fn bench_publish(c: &mut Criterion) {
+	let runtime = Runtime::new().unwrap();
+	let counter = AtomicU64::new(0);
+	let mut group = c.benchmark_group("publish");
+	group.throughput(Throughput::Elements(PUBLISH_BATCH));
+
+	let memseq = in_memory(BLOCK_SIZES[0]);
+	group.bench_function("in_memory", |b| {
+		b.to_async(&runtime)
+			.iter_custom(|iters| publish_batch(&memseq, &counter, iters));
+	});
+
+	let dir = tempfile::tempdir().unwrap();
+	let memseq = runtime.block_on(Memseq::try_move_rocks(dir.path().to_path_buf())).unwrap();
+	group.bench_function("rocksdb", |b| {
+		b.to_async(&runtime)
+			.iter_custom(|iters| publish_batch(&memseq, &counter, iters));
+	});
+	group.finish();
+}
+
+criterion_group!(benches, bench_wait_for_next_block, bench_publish);
+criterion_main!(benches);
@@ -0,0 +1,305 @@
+use crate::{Block, Id, Memseq};
+use mempool_util::{MempoolBlockOperations, MempoolTransaction, MempoolTransactionOperations};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use tokio::sync::RwLock;
+
+/// Key whose order is the pop order, the same as the key of the RocksDB mempool.
+type PopOrder = (u64, u64, u64, Id);
+
+/// Mempool held in process memory, for tests and ephemeral nodes that need no persistence.
+///
+/// Behaves as a [`RocksdbMempool`](crate::RocksdbMempool) with the default
+/// [`TransactionOrdering::Arrival`](mempool_util::TransactionOrdering::Arrival): transactions
+/// are stamped with the next arrival of this mempool when added and popped by time slot, then
+/// by arrival. Adding a transaction already pending adds a second entry, which the id then
+/// refers to.
+#[derive(Debug, Default)]
+pub struct VecMempool {
+	pending: Mutex<Pending>,
+	blocks: Mutex<HashMap<Id, Block>>,
+}
+
+/// Pending transactions along with the lookups of the RocksDB mempool.
+#[derive(Debug, Default)]
+struct Pending {
+	transactions: BTreeMap<PopOrder, MempoolTransaction>,
+	// the entry last added for each id
+	lookups: HashMap<Id, PopOrder>,
+	// the id last added for each sender and sequence number
+	sender_sequence_lookups: HashMap<(Id, u64), Id>,
+	next_arrival: u64,
+}
+
+impl Pending {
+	fn insert(&mut self, tx: MempoolTransaction) {
+		let id = tx.id();
+		let key = (tx.timestamp, tx.arrival, tx.transaction.sequence_number, id.clone());
+		self.sender_sequence_lookups
+			.insert((tx.transaction.sender.clone(), tx.transaction.sequence_number), id.clone());
+		self.lookups.insert(id, key.clone());
+		self.transactions.insert(key, tx);
+	}
+
+	/// Removes the sender and sequence number lookup of the transaction, unless the slot
+	/// has since been taken by another transaction.
+	fn remove_sender_sequence_lookup(&mut self, tx: &MempoolTransaction) {
+		let key = (tx.transaction.sender.clone(), tx.transaction.sequence_number);
+		if self.sender_sequence_lookups.get(&key) == Some(&tx.id()) {
+			self.sender_sequence_lookups.remove(&key);
+		}
+	}
+
+	fn get(&self, transaction_id: &Id) -> Option<&MempoolTransaction> {
+		self.lookups.get(transaction_id).and_then(|key| self.transactions.get(key))
+	}
+}
+
+impl VecMempool {
+	pub fn new() -> Self {
+		Self::default()
+	}
+}
+
+impl MempoolTransactionOperations for VecMempool {
+	async fn has_mempool_transaction(&self, transaction_id: Id) -> Result<bool, anyhow::Error> {
+		Ok(self.pending.lock().unwrap().get(&transaction_id).is_some())
+	}
+
+	/// Adds the transaction, stamped with the next arrival of this mempool.
+	async fn add_mempool_transaction(
+		&self,
+		mut tx: MempoolTransaction,
+	) -> Result<(), anyhow::Error> {
+		let mut pending = self.pending.lock().unwrap();
+		tx.arrival = pending.next_arrival;
+		pending.next_arrival += 1;
+		pending.insert(tx);
+		Ok(())
+	}
+
+	/// Puts the transaction back with the arrival it was popped with.
+	async fn reinsert_mempool_transaction(
+		&self,
+		tx: MempoolTransaction,
+	) -> Result<(), anyhow::Error> {
+		self.pending.lock().unwrap().insert(tx);
+		Ok(())
+	}
+
+	async fn remove_mempool_transaction(&self, transaction_id: Id) -> Result<(), anyhow::Error> {
+		let mut pending = self.pending.lock().unwrap();
+		if let Some(key) = pending.lookups.remove(&transaction_id) {
+			if let Some(tx) = pending.transactions.remove(&key) {
+				pending.remove_sender_sequence_lookup(&tx);
+			}
+		}
+		Ok(())
+	}
+
+	async fn pop_mempool_transaction(&self) -> Result<Option<MempoolTransaction>, anyhow::Error> {
+		let mut pending = self.pending.lock().unwrap();
+		let Some((_, tx)) = pending.transactions.pop_first() else {
+			return Ok(None);
+		};
+		pending.lookups.remove(&tx.id());
+		pending.remove_sender_sequence_lookup(&tx);
+		Ok(Some(tx))
+	}
+
+	async fn get_mempool_transaction(
+		&self,
+		transaction_id: Id,
+	) -> Result<Option<MempoolTransaction>, anyhow::Error> {
+		Ok(self.pending.lock().unwrap().get(&transaction_id).cloned())
+	}
+
+	async fn get_mempool_transaction_by_sender_sequence(
+		&self,
+		sender: Id,
+		sequence_number: u64,
+	) -> Result<Option<MempoolTransaction>, anyhow::Error> {
+		let pending = self.pending.lock().unwrap();
+		Ok(pending
+			.sender_sequence_lookups
+			.get(&(sender, sequence_number))
+			.and_then(|transaction_id| pending.get(transaction_id))
+			.cloned())
+	}
+
+	async fn find_mempool_transactions<F>(
+		&self,
+		predicate: F,
+		limit: usize,
+	) -> Result<Vec<MempoolTransaction>, anyhow::Error>
+	where
+		F: Fn(&MempoolTransaction) -> bool,
+	{
+		let pending = self.pending.lock().unwrap();
+		Ok(pending
+			.transactions
+			.values()
+			.filter(|tx| predicate(tx))
+			.take(limit)
+			.cloned()
+			.collect())
+	}
+
+	async fn size(&self) -> Result<u64, anyhow::Error> {
+		Ok(self.pending.lock().unwrap().transactions.len() as u64)
+	}
+}
+
+impl MempoolBlockOperations for VecMempool {
+	async fn has_block(&self, block_id: Id) -> Result<bool, anyhow::Error> {
+		Ok(self.blocks.lock().unwrap().contains_key(&block_id))
+	}
+
+	async fn add_block(&self, block: Block) -> Result<(), anyhow::Error> {
+		self.blocks.lock().unwrap().insert(block.id(), block);
+		Ok(())
+	}
+
+	async fn remove_block(&self, block_id: Id) -> Result<(), anyhow::Error> {
+		self.blocks.lock().unwrap().remove(&block_id);
+		Ok(())
+	}
+
+	async fn get_block(&self, block_id: Id) -> Result<Option<Block>, anyhow::Error> {
+		Ok(self.blocks.lock().unwrap().get(&block_id).cloned())
+	}
+}
+
+impl Memseq<VecMempool> {
+	/// Creates a sequencer over an empty [`VecMempool`], whose pending transactions are lost
+	/// when the sequencer is dropped.
+	pub fn try_in_memory(block_size: u32, building_time_ms: u64) -> Result<Self, anyhow::Error> {
+		let mempool = Arc::new(RwLock::new(VecMempool::new()));
+		let parent_block = Arc::new(RwLock::new(Id::default()));
+		Ok(Self::new(mempool, block_size, parent_block, building_time_ms))
+	}
+}
+
+#[cfg(test)]
+pub mod test {
+
+	use super::*;
+	use crate::{RocksdbMempool, Sequencer, Transaction};
+	use tempfile::tempdir;
+
+	#[tokio::test]
+	async fn test_memseq() -> Result<(), anyhow::Error> {
+		let memseq = Memseq::try_in_memory(10, 1000)?;
+
+		let transaction = Transaction::new(vec![1, 2, 3], 0);
+		memseq.publish(transaction.clone()).await?;
+
+		let block = memseq.wait_for_next_block().await?;
+
+		assert_eq!(block.ok_or(anyhow::anyhow!("Block not found"))?.transactions[0], transaction);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_respects_size() -> Result<(), anyhow::Error> {
+		let block_size = 100;
+		let memseq = Memseq::try_in_memory(block_size, 1000)?;
+
+		let mut transactions = Vec::new();
+		for i in 0..block_size * 2 {
+			let transaction = Transaction::new(vec![i as u8], 0);
+			memseq.publish(transaction.clone()).await?;
+			transactions.push(transaction);
+		}
+
+		let block = memseq.wait_for_next_block().await?;
+		let block = block.ok_or(anyhow::anyhow!("Block not found"))?;
+		// in publish order, as with the RocksDB mempool
+		assert_eq!(block.transactions, transactions[..block_size as usize]);
+
+		let second_block = memseq.wait_for_next_block().await?;
+		let second_block = second_block.ok_or(anyhow::anyhow!("Second block not found"))?;
+		assert_eq!(second_block.transactions, transactions[block_size as usize..]);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_pop_order_matches_rocksdb() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let rocksdb = RocksdbMempool::try_new(dir.path().to_str().unwrap())?;
+		let in_memory = VecMempool::new();
+
+		// later slots, earlier slots and same-slot arrivals with decreasing sequence numbers
+		let mempool_transactions: Vec<_> = [4, 0, 2, 2, 0, 5]
+			.into_iter()
+			.enumerate()
+			.map(|(i, timestamp)| {
+				MempoolTransaction::at_time(
+					Transaction::new(vec![i as u8], 10 - i as u64),
+					timestamp,
+				)
+			})
+			.collect();
+		for mempool_transaction in mempool_transactions {
+			rocksdb.add_mempool_transaction(mempool_transaction.clone()).await?;
+			in_memory.add_mempool_transaction(mempool_transaction).await?;
+		}
+
+		let expected = rocksdb.pop_transactions(6).await?;
+		assert_eq!(expected.len(), 6);
+		assert_eq!(in_memory.pop_transactions(6).await?, expected);
+
+		Ok(())
+	}
+
+	/// Size of the mempool and whether it has the transaction after each step of adding the
+	/// same transaction several times, removing it and popping it, along with what was popped.
+	async fn duplicate_trace<T: MempoolTransactionOperations>(
+		mempool: &T,
+	) -> Result<Vec<(u64, bool, Option<Transaction>)>, anyhow::Error> {
+		let tx = MempoolTransaction::at_time(Transaction::new(vec![1], 0), 0);
+		let id = tx.id();
+		let mut trace = Vec::new();
+		for step in 0..6 {
+			let popped = match step {
+				0 | 1 | 3 => {
+					mempool.add_mempool_transaction(tx.clone()).await?;
+					None
+				}
+				2 => {
+					mempool.remove_mempool_transaction(id.clone()).await?;
+					None
+				}
+				_ => mempool.pop_transaction().await?,
+			};
+			trace.push((mempool.size().await?, mempool.has_transaction(id.clone()).await?, popped));
+		}
+		Ok(trace)
+	}
+
+	#[tokio::test]
+	async fn test_duplicates_match_rocksdb() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let rocksdb = RocksdbMempool::try_new(dir.path().to_str().unwrap())?;
+		let in_memory = VecMempool::new();
+
+		let expected = duplicate_trace(&rocksdb).await?;
+		let popped = Some(Transaction::new(vec![1], 0));
+		assert_eq!(
+			expected,
+			[
+				(1, true, None),
+				(2, true, None),
+				(1, false, None),
+				(2, true, None),
+				(1, false, popped.clone()),
+				(0, false, popped),
+			]
+		);
+		assert_eq!(duplicate_trace(&in_memory).await?, expected);
+
+		Ok(())
+	}
+}
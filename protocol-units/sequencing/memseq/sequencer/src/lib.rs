@@ -1,18 +1,147 @@
-use mempool_util::{MempoolBlockOperations, MempoolTransactionOperations};
+use futures::Stream;
+pub use in_memory::VecMempool;
+use mempool_util::{MempoolBlockOperations, MempoolTransaction, MempoolTransactionOperations};
+pub use metrics::{BlockBuildMetrics, MemseqMetrics, NoopBlockBuildMetrics};
 pub use move_rocks::RocksdbMempool;
-pub use movement_types::{Block, Id, Transaction};
+pub use movement_types::{
+	AtomicTransactionBundle, Block, BlockCommitment, BlockIdCommitmentProvider, BlockMetadata,
+	Commitment, CommitmentProvider, Id, Transaction, TransactionEntry,
+};
+pub use multi::{ChainConfig, MemseqMulti, UnknownChain};
 pub use sequencing_util::Sequencer;
+pub use snapshot::MempoolSnapshot;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
 use std::{path::PathBuf, sync::Arc};
-use tokio::sync::RwLock;
+use thiserror::Error;
+use tokio::sync::{watch, RwLock};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+pub use validation::{TransactionValidator, ValidationError};
+
+pub mod in_memory;
+pub mod metrics;
+pub mod multi;
+pub mod snapshot;
+pub mod validation;
+
+/// Number of transactions between two progress reports of
+/// [`Memseq::publish_many_with_progress`].
+pub const PUBLISH_PROGRESS_INTERVAL: usize = 100;
+
+/// Default number of times a transaction rejected by the assembly filter is re-queued before
+/// it is moved to the dead letters, see [`Memseq::with_max_requeue_attempts`].
+pub const DEFAULT_MAX_REQUEUE_ATTEMPTS: u32 = 10;
+
+/// Number of recently built block sizes retained for [`Memseq::recent_fill_ratios`].
+pub const RECENT_BLOCK_SIZES_CAPACITY: usize = 256;
+
+/// Computes the opaque `extra` metadata of a block from its transactions.
+pub type ExtraProvider = Arc<dyn Fn(&[Transaction]) -> Vec<u8> + Send + Sync>;
+
+/// Decides whether a transaction popped from the mempool can be included in the block being
+/// built. Rejected transactions are re-queued for a later block.
+pub type AssemblyFilter = Arc<dyn Fn(&Transaction) -> bool + Send + Sync>;
+
+/// Error returned when publishing to a sequencer after [`Memseq::shutdown`].
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("Memseq is shutting down and no longer accepts transactions")]
+pub struct ShuttingDown;
+
+/// Error returned by [`Sequencer::publish`] when the mempool holds as many transactions as
+/// allowed by [`Memseq::with_max_mempool_size`].
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("Mempool is full, it holds the maximum of {max_mempool_size} transactions")]
+pub struct MempoolFull {
+	pub max_mempool_size: u64,
+}
+
+/// Error returned by [`Sequencer::publish_batch`] when a transaction of the batch could not be
+/// published. The `published` transactions before it remain in the mempool.
+#[derive(Error, Debug)]
+#[error("Published {published} of {total} transactions: {source}")]
+pub struct PartialPublish {
+	pub published: usize,
+	pub total: usize,
+	#[source]
+	pub source: anyhow::Error,
+}
+
+/// Background flush of the mempool, see [`Memseq::with_periodic_flush`].
+struct PeriodicFlush {
+	cancel: CancellationToken,
+	task: std::sync::Mutex<Option<JoinHandle<()>>>,
+	closed: AtomicBool,
+}
+
+impl Drop for PeriodicFlush {
+	fn drop(&mut self) {
+		self.cancel.cancel();
+		if !self.closed.load(Ordering::SeqCst) {
+			tracing::warn!(
+				"Memseq with periodic flush dropped without close(), writes since the last flush may be lost"
+			);
+		}
+	}
+}
 
 #[derive(Clone)]
 pub struct Memseq<T: MempoolBlockOperations + MempoolTransactionOperations> {
+	/// Operations that mutate the mempool, publishing and draining it into blocks, hold the
+	/// write lock; read-only operations hold the read lock. Backends may therefore rely on
+	/// exclusive access for mutations rather than on interior mutability. Block building
+	/// releases the lock while it waits for more transactions.
 	pub mempool: Arc<RwLock<T>>,
+	// stamped on the bundles and as the proposer of the blocks built by this sequencer
+	sequencer_id: Id,
 	// this value should not be changed after initialization
 	block_size: u32,
 	pub parent_block: Arc<RwLock<Id>>,
 	// this value should not be changed after initialization
 	building_time_ms: u64,
+	// time between two attempts to drain the mempool while building a block
+	poll_interval_ms: u64,
+	// fraction of `block_size` at which a block is emitted before the building time expires,
+	// 1.0 unless set or min_block_size is set
+	min_fill_ratio: Option<f64>,
+	// transactions a block waits for before being emitted early, 0 if unset
+	min_block_size: u32,
+	// limit on the number of pending transactions accepted by publish
+	max_mempool_size: Option<u64>,
+	// limit on the summed size of the transactions of a block, see `Transaction::size_bytes`
+	max_block_bytes: Option<u64>,
+	// whether parent_block is set to each block built
+	auto_advance_parent: bool,
+	// whether a transaction popped twice is included in a block only once
+	dedup: bool,
+	// whether block_size may be exceeded to keep a transaction group in a single block
+	soft_block_size: bool,
+	// whether the transactions of a block are sorted by sequence number
+	sort_by_sequence: bool,
+	// whether the scheduled block stream emits a block when there are no transactions
+	heartbeat: bool,
+	// maximum fraction of block_size per transaction category, unlimited if absent
+	category_quotas: HashMap<u8, f64>,
+	extra_provider: Option<ExtraProvider>,
+	commitment_provider: Arc<dyn CommitmentProvider>,
+	assembly_filter: Option<AssemblyFilter>,
+	validator: Option<Arc<dyn TransactionValidator>>,
+	max_requeue_attempts: u32,
+	// times each pending transaction was rejected by the assembly filter and re-queued
+	requeue_attempts: Arc<RwLock<HashMap<Id, u32>>>,
+	dead_letters: Arc<RwLock<Vec<Transaction>>>,
+	// height and id of the last block built, heights start at 1
+	last_block: Arc<RwLock<Option<(u64, Id)>>>,
+	// transaction counts of the last blocks built, oldest first
+	recent_block_sizes: Arc<std::sync::Mutex<VecDeque<u32>>>,
+	// set by `shutdown`, shared by all clones
+	shutting_down: Arc<AtomicBool>,
+	// pending transactions, refreshed while watched, see `watch_pending_count`
+	pending_count: Arc<watch::Sender<u64>>,
+	metrics: Arc<metrics::Metrics>,
+	metrics_sink: Option<Arc<dyn BlockBuildMetrics>>,
+	periodic_flush: Option<Arc<PeriodicFlush>>,
 }
 
 impl<T: MempoolBlockOperations + MempoolTransactionOperations> Memseq<T> {
@@ -22,7 +151,62 @@ impl<T: MempoolBlockOperations + MempoolTransactionOperations> Memseq<T> {
 		parent_block: Arc<RwLock<Id>>,
 		building_time_ms: u64,
 	) -> Self {
-		Self { mempool, block_size, parent_block, building_time_ms }
+		Self {
+			mempool,
+			sequencer_id: Id::default(),
+			block_size,
+			parent_block,
+			building_time_ms,
+			poll_interval_ms: 1,
+			min_fill_ratio: None,
+			min_block_size: 0,
+			max_mempool_size: None,
+			max_block_bytes: None,
+			auto_advance_parent: true,
+			dedup: true,
+			soft_block_size: false,
+			sort_by_sequence: false,
+			heartbeat: false,
+			category_quotas: HashMap::new(),
+			extra_provider: None,
+			commitment_provider: Arc::new(BlockIdCommitmentProvider),
+			assembly_filter: None,
+			validator: None,
+			max_requeue_attempts: DEFAULT_MAX_REQUEUE_ATTEMPTS,
+			requeue_attempts: Arc::new(RwLock::new(HashMap::new())),
+			dead_letters: Arc::new(RwLock::new(Vec::new())),
+			last_block: Arc::new(RwLock::new(None)),
+			recent_block_sizes: Arc::new(std::sync::Mutex::new(VecDeque::with_capacity(
+				RECENT_BLOCK_SIZES_CAPACITY,
+			))),
+			shutting_down: Arc::new(AtomicBool::new(false)),
+			pending_count: Arc::new(watch::channel(0).0),
+			metrics: Arc::new(metrics::Metrics::default()),
+			metrics_sink: None,
+			periodic_flush: None,
+		}
+	}
+
+	/// Continues the chain persisted in the mempool, if any: the next block is built on the
+	/// persisted parent block, at the height following it.
+	pub(crate) async fn with_persisted_chain(self) -> Result<Self, anyhow::Error> {
+		let persisted = self.mempool.read().await.get_parent_block().await?;
+		if let Some((height, parent_block)) = persisted {
+			*self.parent_block.write().await = parent_block.clone();
+			*self.last_block.write().await = Some((height, parent_block));
+		}
+		Ok(self)
+	}
+
+	/// Sets the id identifying this sequencer, e.g. [`Id::from_name`] or
+	/// [`Id::from_public_key`]. Defaults to the all-zero id.
+	pub fn with_sequencer_id(mut self, sequencer_id: Id) -> Self {
+		self.sequencer_id = sequencer_id;
+		self
+	}
+
+	pub fn sequencer_id(&self) -> &Id {
+		&self.sequencer_id
 	}
 
 	pub fn with_block_size(mut self, block_size: u32) -> Self {
@@ -30,20 +214,670 @@ impl<T: MempoolBlockOperations + MempoolTransactionOperations> Memseq<T> {
 		self
 	}
 
+	pub fn block_size(&self) -> u32 {
+		self.block_size
+	}
+
 	pub fn with_building_time_ms(mut self, building_time_ms: u64) -> Self {
 		self.building_time_ms = building_time_ms;
 		self
 	}
+
+	pub fn building_time_ms(&self) -> u64 {
+		self.building_time_ms
+	}
+
+	/// Sets the time to wait for more transactions between two attempts to drain the
+	/// mempool while building a block. The last wait is cut short at the end of the building
+	/// time. Defaults to 1ms.
+	pub fn with_poll_interval_ms(mut self, poll_interval_ms: u64) -> Self {
+		self.poll_interval_ms = poll_interval_ms;
+		self
+	}
+
+	/// Sets the fraction of `block_size` a block must reach to be emitted before the building
+	/// time expires. Once the building time expires, whatever was collected is emitted
+	/// regardless. Defaults to `1.0`, i.e. only full blocks are emitted early, unless
+	/// [`Memseq::with_min_block_size`] is set. The ratio is clamped to `[0.0, 1.0]`.
+	pub fn with_min_fill_ratio(mut self, min_fill_ratio: f64) -> Self {
+		self.min_fill_ratio = Some(min_fill_ratio.clamp(0.0, 1.0));
+		self
+	}
+
+	/// Sets the number of transactions at which a block is emitted before the building time
+	/// expires, so that bursty traffic does not produce tiny blocks. Once the building time
+	/// expires, whatever was collected is emitted regardless. It replaces the default ratio of
+	/// [`Memseq::with_min_fill_ratio`]; if a ratio is set too, the larger of both applies.
+	/// A full block is always emitted, even below `min_block_size`.
+	pub fn with_min_block_size(mut self, min_block_size: u32) -> Self {
+		self.min_block_size = min_block_size;
+		self
+	}
+
+	/// Limits the number of pending transactions: once the mempool holds `max_mempool_size`
+	/// transactions, every publish method of the sequencer fails with [`MempoolFull`] until
+	/// blocks drain it, a bundle as a whole if it does not fit. Transactions added to the
+	/// backend directly, e.g. with `RocksdbMempool::import_pending_stream`, are not limited.
+	/// Checking the limit costs a count of the mempool per publication, see
+	/// [`MempoolTransactionOperations::size`]. Unlimited by default.
+	pub fn with_max_mempool_size(mut self, max_mempool_size: u64) -> Self {
+		self.max_mempool_size = Some(max_mempool_size);
+		self
+	}
+
+	/// Limits the size of a block in bytes, counting the [`Transaction::size_bytes`] of each
+	/// transaction, in addition to its transaction count. Block building stops at the first
+	/// transaction that would exceed the limit, which is put back at the head of the mempool
+	/// for the next block. A transaction exceeding the limit on its own can never be included
+	/// and is moved to the dead letters, see [`Memseq::dead_letters`].
+	///
+	/// Unlike `block_size`, the limit stays binding with [`Memseq::with_soft_block_size`]: a
+	/// transaction group is included only if it fits whole, and is otherwise left in place
+	/// for a later block, or moved to the dead letters if it exceeds the limit on its own.
+	pub fn with_max_block_bytes(mut self, max_block_bytes: u64) -> Self {
+		self.max_block_bytes = Some(max_block_bytes);
+		self
+	}
+
+	/// Sets whether `parent_block` is advanced to the id of each block built, so that
+	/// consecutive blocks form a chain. Defaults to `true`; when disabled, the parent is left
+	/// for the caller to update.
+	pub fn with_auto_advance_parent(mut self, auto_advance_parent: bool) -> Self {
+		self.auto_advance_parent = auto_advance_parent;
+		self
+	}
+
+	/// Sets whether a transaction popped again while its block is built, e.g. because it was
+	/// inserted twice, is skipped rather than included a second time. Defaults to `true`.
+	pub fn with_dedup(mut self, dedup: bool) -> Self {
+		self.dedup = dedup;
+		self
+	}
+
+	/// Makes `block_size` a soft target: a block that includes part of a transaction group,
+	/// see [`Memseq::publish_bundle`], includes the whole group even if that exceeds
+	/// `block_size`. Groups are never split across blocks: the first member popped decides
+	/// whether the whole group is included, filtered out, see [`Memseq::with_assembly_filter`],
+	/// or held back, see [`Memseq::with_category_quotas`], and the other members follow.
+	pub fn with_soft_block_size(mut self, soft_block_size: bool) -> Self {
+		self.soft_block_size = soft_block_size;
+		self
+	}
+
+	/// Sets whether the transactions of each block built are sorted by sequence number,
+	/// keeping the pop order among equal sequence numbers, rather than left in pop order.
+	/// This only orders the transactions within a block: a transaction popped for a later
+	/// block still lands there, whatever its sequence number. Defaults to `false`.
+	pub fn with_sort_by_sequence(mut self, sort_by_sequence: bool) -> Self {
+		self.sort_by_sequence = sort_by_sequence;
+		self
+	}
+
+	/// Sets whether [`Memseq::into_scheduled_block_stream`] emits an empty block on a boundary
+	/// with no pending transactions, rather than skipping it. Consecutive heartbeats have
+	/// distinct ids, as each block commits to its height and timestamp. Defaults to `false`.
+	pub fn with_heartbeat(mut self, heartbeat: bool) -> Self {
+		self.heartbeat = heartbeat;
+		self
+	}
+
+	/// Limits the transactions of each category, see [`Transaction::category`], to a
+	/// fraction of `block_size` per block, e.g. so that system transactions cannot crowd out
+	/// user ones. A transaction of a category over its quota is held back and put back in the
+	/// mempool, in its place, once the block is built. Categories without a quota are
+	/// unlimited. Fractions are clamped to `[0.0, 1.0]`.
+	///
+	/// With [`Memseq::with_soft_block_size`], the quota applies to the first member popped of
+	/// a transaction group; the members following it are not counted.
+	pub fn with_category_quotas(mut self, category_quotas: HashMap<u8, f64>) -> Self {
+		self.category_quotas = category_quotas
+			.into_iter()
+			.map(|(category, fraction)| (category, fraction.clamp(0.0, 1.0)))
+			.collect();
+		self
+	}
+
+	/// Maximum number of transactions of `category` in a block, if it has a quota.
+	fn category_limit(&self, category: u8) -> Option<u32> {
+		self.category_quotas
+			.get(&category)
+			.map(|fraction| (self.block_size as f64 * fraction).floor() as u32)
+	}
+
+	/// Number of transactions at which a block is emitted before the building time expires.
+	fn min_fill(&self) -> u32 {
+		let ratio_fill = self
+			.min_fill_ratio
+			.map(|min_fill_ratio| (self.block_size as f64 * min_fill_ratio).ceil() as u32);
+		let min_fill = match ratio_fill {
+			Some(ratio_fill) => ratio_fill.max(self.min_block_size),
+			None if self.min_block_size > 0 => self.min_block_size,
+			None => self.block_size,
+		};
+		// at least one transaction, so that an empty mempool still waits for the timer
+		min_fill.max(1).min(self.block_size)
+	}
+
+	/// Sets a provider invoked once per built block to compute its `extra` metadata.
+	/// Since `extra` is committed in the block id, the provider determines block ids as much
+	/// as the transactions do.
+	pub fn with_extra_provider(mut self, extra_provider: ExtraProvider) -> Self {
+		self.extra_provider = Some(extra_provider);
+		self
+	}
+
+	/// Sets the provider computing the state commitments of
+	/// [`Memseq::produce_commitment`]. Defaults to [`BlockIdCommitmentProvider`].
+	pub fn with_commitment_provider(
+		mut self,
+		commitment_provider: Arc<dyn CommitmentProvider>,
+	) -> Self {
+		self.commitment_provider = commitment_provider;
+		self
+	}
+
+	/// Reports each block built to `metrics_sink`. No reports are made by default.
+	pub fn with_metrics_sink(mut self, metrics_sink: Arc<dyn BlockBuildMetrics>) -> Self {
+		self.metrics_sink = Some(metrics_sink);
+		self
+	}
+
+	/// Sets a filter applied to the transactions popped while building a block. A rejected
+	/// transaction is re-queued at the back of the mempool, until it has been re-queued
+	/// [`Memseq::with_max_requeue_attempts`] times: it is then moved to the dead letters, so
+	/// that a transaction that is never accepted cannot circulate forever. With
+	/// [`Memseq::with_soft_block_size`], the filter applies to the first member popped of a
+	/// transaction group, whose members are all re-queued, together, if it is rejected.
+	pub fn with_assembly_filter(mut self, assembly_filter: AssemblyFilter) -> Self {
+		self.assembly_filter = Some(assembly_filter);
+		self
+	}
+
+	/// Sets a validator checking each transaction before it is published. Every publish
+	/// method fails with the [`ValidationError`] of the first rejected transaction, which
+	/// never reaches the mempool. Batches and bundles are validated as a whole before any of
+	/// their transactions is published, whereas [`Memseq::publish_many_with_progress`] stops
+	/// at the rejected transaction.
+	pub fn with_validator(mut self, validator: Arc<dyn TransactionValidator>) -> Self {
+		self.validator = Some(validator);
+		self
+	}
+
+	fn validate(&self, transaction: &Transaction) -> Result<(), ValidationError> {
+		match &self.validator {
+			Some(validator) => validator.validate(transaction),
+			None => Ok(()),
+		}
+	}
+
+	/// Sets how many times a transaction rejected by the assembly filter is re-queued before
+	/// it is moved to the dead letters. Defaults to [`DEFAULT_MAX_REQUEUE_ATTEMPTS`].
+	pub fn with_max_requeue_attempts(mut self, max_requeue_attempts: u32) -> Self {
+		self.max_requeue_attempts = max_requeue_attempts;
+		self
+	}
+
+	/// Transactions given up on after exhausting their re-queue attempts, oldest first. Dead
+	/// letters are kept in memory only.
+	pub async fn dead_letters(&self) -> Result<Vec<Transaction>, anyhow::Error> {
+		Ok(self.dead_letters.read().await.clone())
+	}
+
+	/// Re-queues the transactions rejected while building a block, or moves them to the dead
+	/// letters once they exhausted their attempts. Accepted transactions are forgotten.
+	/// Re-queued transactions stay in their group, so that the members of a rejected group,
+	/// re-queued together, are also given up on together.
+	async fn requeue_rejected(
+		&self,
+		mempool: &T,
+		accepted: &[Transaction],
+		rejected: Vec<MempoolTransaction>,
+	) -> Result<(), anyhow::Error> {
+		let mut requeue_attempts = self.requeue_attempts.write().await;
+		for transaction in accepted {
+			requeue_attempts.remove(&transaction.id());
+		}
+		for MempoolTransaction { transaction, group, .. } in rejected {
+			let id = transaction.id();
+			let attempts = requeue_attempts.entry(id.clone()).or_insert(0);
+			if *attempts >= self.max_requeue_attempts {
+				requeue_attempts.remove(&id);
+				self.dead_letters.write().await.push(transaction);
+				self.metrics.record_dead_lettered();
+			} else {
+				*attempts += 1;
+				if mempool.has_transaction(id).await? {
+					continue;
+				}
+				let mut requeued = MempoolTransaction::slot_now(transaction);
+				requeued.group = group;
+				mempool.add_mempool_transaction(requeued).await?;
+			}
+		}
+		Ok(())
+	}
+
+	/// Refuses every later publication with [`ShuttingDown`], so that the mempool can be
+	/// emptied with [`Memseq::drain_into_blocks`] during a graceful shutdown. Block production
+	/// is unaffected. This applies to all clones of this sequencer and cannot be undone.
+	pub fn shutdown(&self) {
+		self.shutting_down.store(true, Ordering::SeqCst);
+	}
+
+	pub fn is_shutting_down(&self) -> bool {
+		self.shutting_down.load(Ordering::SeqCst)
+	}
+
+	/// Watches the number of pending transactions, e.g. to build a block once it reaches
+	/// `block_size`. The count is refreshed after each publication and each block built by
+	/// this sequencer or its clones while at least one receiver is alive, so the first value
+	/// received may be stale. Changes made to the mempool directly are not observed.
+	pub fn watch_pending_count(&self) -> watch::Receiver<u64> {
+		self.pending_count.subscribe()
+	}
+
+	/// Publishes the size of the mempool to the receivers of
+	/// [`Memseq::watch_pending_count`], if there are any. Counting is skipped otherwise, as it
+	/// may cost a scan of the mempool.
+	async fn update_pending_count(&self, mempool: &T) {
+		if self.pending_count.receiver_count() == 0 {
+			return;
+		}
+		// the mempool has changed already, failing to count must not fail the change
+		match mempool.size().await {
+			Ok(size) => {
+				self.pending_count.send_replace(size);
+			}
+			Err(err) => tracing::warn!("Failed to count the pending transactions: {err}"),
+		}
+	}
+
+	/// Fails with [`MempoolFull`] unless the mempool has room for `additional` more
+	/// transactions, see [`Memseq::with_max_mempool_size`]. Must be called with the mempool
+	/// write lock held, so that the room is not taken meanwhile.
+	async fn ensure_room(&self, mempool: &T, additional: u64) -> Result<(), anyhow::Error> {
+		if let Some(max_mempool_size) = self.max_mempool_size {
+			if mempool.size().await? + additional > max_mempool_size {
+				return Err(MempoolFull { max_mempool_size }.into());
+			}
+		}
+		Ok(())
+	}
+
+	fn ensure_accepting(&self) -> Result<(), ShuttingDown> {
+		if self.is_shutting_down() {
+			Err(ShuttingDown)
+		} else {
+			Ok(())
+		}
+	}
+
+	/// Builds blocks until the mempool is empty and returns them in order.
+	pub async fn drain_into_blocks(&self) -> Result<Vec<Block>, anyhow::Error> {
+		let mut blocks = Vec::new();
+		while let Some(block) = self.wait_for_next_block().await? {
+			blocks.push(block);
+		}
+		Ok(blocks)
+	}
+
+	/// Publishes a transaction such that the next call to `wait_for_next_block` pops it
+	/// ahead of everything else, regardless of the normal slot ordering.
+	///
+	/// This bypasses fair ordering entirely: a caller can use it to starve every other
+	/// transaction in the mempool. It is intended for system messages and should only be
+	/// reachable by trusted callers, never exposed on a public endpoint.
+	pub async fn publish_priority(&self, transaction: Transaction) -> Result<(), anyhow::Error> {
+		self.ensure_accepting()?;
+		self.validate(&transaction)?;
+		let mempool = self.mempool.write().await;
+		// a regular entry of the transaction makes room for it
+		let pending = mempool.has_transaction(transaction.id()).await?;
+		self.ensure_room(&mempool, if pending { 0 } else { 1 }).await?;
+		// drop any regular entry first so the transaction is not included twice
+		mempool.remove_transaction(transaction.id()).await?;
+		// slot zero orders before any transaction slotted at the current time
+		mempool
+			.add_mempool_transaction(MempoolTransaction::at_time(transaction, 0).with_priority())
+			.await?;
+		self.metrics.record_published(1);
+		self.update_pending_count(&mempool).await;
+		Ok(())
+	}
+
+	/// Publishes the transactions of the bundle as an indivisible group. The group is only
+	/// guaranteed to land in a single block with [`Memseq::with_soft_block_size`].
+	pub async fn publish_bundle(
+		&self,
+		bundle: AtomicTransactionBundle,
+	) -> Result<(), anyhow::Error> {
+		self.ensure_accepting()?;
+		for entry in &bundle.transactions {
+			self.validate(&entry.data)?;
+		}
+		let group = bundle.id();
+		let mempool = self.mempool.write().await;
+		self.ensure_room(&mempool, bundle.transactions.len() as u64).await?;
+		for entry in bundle.transactions {
+			mempool
+				.add_mempool_transaction(
+					MempoolTransaction::slot_now(entry.data).with_group(group.clone()),
+				)
+				.await?;
+			self.metrics.record_published(1);
+		}
+		self.update_pending_count(&mempool).await;
+		Ok(())
+	}
+
+	/// Replaces the pending transaction occupying the same sender and sequence number slot,
+	/// e.g. to bump its fee. Returns `true` if a pending transaction was replaced, `false` if
+	/// there was none and the transaction was simply inserted.
+	pub async fn replace_transaction(
+		&self,
+		transaction: Transaction,
+	) -> Result<bool, anyhow::Error> {
+		self.ensure_accepting()?;
+		self.validate(&transaction)?;
+		// exclusive so that the slot cannot be observed empty or doubly occupied
+		let mempool = self.mempool.write().await;
+		let replaced = match mempool
+			.get_mempool_transaction_by_sender_sequence(
+				transaction.sender.clone(),
+				transaction.sequence_number,
+			)
+			.await?
+		{
+			Some(pending) => {
+				mempool.remove_mempool_transaction(pending.id()).await?;
+				true
+			}
+			None => {
+				self.ensure_room(&mempool, 1).await?;
+				false
+			}
+		};
+		mempool.add_transaction(transaction).await?;
+		self.metrics.record_published(1);
+		self.update_pending_count(&mempool).await;
+		Ok(replaced)
+	}
+
+	/// Publishes the transactions in order, reporting the number committed so far to
+	/// `progress` every [`PUBLISH_PROGRESS_INTERVAL`] transactions and once more at the end.
+	/// The mempool is locked for one such chunk at a time and released before reporting.
+	///
+	/// Stops before the next transaction once `cancel` is cancelled. Transactions already
+	/// committed stay in the mempool, the rest are not published. Returns the number of
+	/// committed transactions, which is always the last value passed to `progress`.
+	pub async fn publish_many_with_progress(
+		&self,
+		transactions: impl IntoIterator<Item = Transaction>,
+		cancel: CancellationToken,
+		mut progress: impl FnMut(usize),
+	) -> Result<usize, anyhow::Error> {
+		self.ensure_accepting()?;
+		let mut transactions = transactions.into_iter();
+		let mut committed = 0;
+		let mut done = false;
+		// the mempool is locked per chunk of transactions, so that block building and other
+		// publishers are not held up until a large import completes
+		while !done {
+			let mempool = self.mempool.write().await;
+			let chunk_end = committed + PUBLISH_PROGRESS_INTERVAL;
+			while committed < chunk_end {
+				let next = if cancel.is_cancelled() { None } else { transactions.next() };
+				let Some(transaction) = next else {
+					done = true;
+					break;
+				};
+				let published = async {
+					self.validate(&transaction)?;
+					self.ensure_room(&mempool, 1).await?;
+					mempool.add_transaction(transaction).await
+				}
+				.await;
+				if let Err(err) = published {
+					self.update_pending_count(&mempool).await;
+					return Err(err);
+				}
+				self.metrics.record_published(1);
+				committed += 1;
+			}
+			self.update_pending_count(&mempool).await;
+			drop(mempool);
+			if committed == chunk_end {
+				progress(committed);
+			}
+		}
+		if committed % PUBLISH_PROGRESS_INTERVAL != 0 || committed == 0 {
+			progress(committed);
+		}
+		Ok(committed)
+	}
+
+	/// Bundles the transactions on behalf of this sequencer, against the last block built.
+	pub async fn bundle(
+		&self,
+		transactions: impl IntoIterator<Item = Transaction>,
+	) -> AtomicTransactionBundle {
+		AtomicTransactionBundle {
+			sequencer_id: self.sequencer_id.clone(),
+			transactions: transactions
+				.into_iter()
+				.map(|data| TransactionEntry { consumer_id: Id::default(), data })
+				.collect(),
+			parent_height: Some(self.height().await),
+		}
+	}
+
+	/// Id of the block the next block will be built on.
+	pub async fn current_parent(&self) -> Id {
+		self.parent_block.read().await.clone()
+	}
+
+	/// Height of the last block built by this sequencer, or 0 if none was built yet.
+	pub async fn height(&self) -> u64 {
+		self.last_block.read().await.as_ref().map_or(0, |(height, _)| *height)
+	}
+
+	/// Milliseconds the oldest pending transaction has been waiting in the mempool, or `None`
+	/// if no pending transaction has a recorded insertion time, e.g. the mempool is empty.
+	/// Steadily growing values mean blocks are not being produced. Scans the whole mempool.
+	pub async fn oldest_pending_age_ms(&self) -> Result<Option<u64>, anyhow::Error> {
+		let oldest = std::cell::Cell::new(None::<u64>);
+		let mempool = self.mempool.read().await;
+		// the predicate only tracks the minimum, nothing is collected
+		mempool
+			.find_mempool_transactions(
+				|mempool_transaction| {
+					let inserted_at_ms = mempool_transaction.inserted_at_ms;
+					if inserted_at_ms > 0 {
+						oldest.set(Some(oldest.get().unwrap_or(u64::MAX).min(inserted_at_ms)));
+					}
+					false
+				},
+				usize::MAX,
+			)
+			.await?;
+		let now_ms =
+			std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_millis() as u64;
+		Ok(oldest.get().map(|inserted_at_ms| now_ms.saturating_sub(inserted_at_ms)))
+	}
+
+	/// Point-in-time copy of the counters of this sequencer and its clones, accumulated since
+	/// it was created or last reset. The counters are read one by one without locking, so a
+	/// snapshot taken while blocks are built or transactions published may reflect an
+	/// operation in some counters only. Snapshots taken between operations are exact.
+	pub fn metrics_snapshot(&self) -> MemseqMetrics {
+		self.metrics.snapshot()
+	}
+
+	/// Zeroes all counters, e.g. between the phases of a benchmark. Concurrent operations may
+	/// be partially counted across the reset.
+	pub fn reset_metrics(&self) {
+		self.metrics.reset();
+	}
+
+	/// Fill ratios of the last `window` blocks built, oldest first: each block's transaction
+	/// count divided by `block_size`. Fewer ratios are returned if fewer blocks were built,
+	/// and at most [`RECENT_BLOCK_SIZES_CAPACITY`] blocks are retained. Ratios above `1.0`
+	/// are possible with [`Memseq::with_soft_block_size`].
+	///
+	/// Ratios consistently near `1.0` suggest raising `block_size`, ratios near `0.0` suggest
+	/// lowering it or raising `building_time_ms`.
+	pub fn recent_fill_ratios(&self, window: usize) -> Vec<f64> {
+		let recent_block_sizes = self.recent_block_sizes.lock().unwrap();
+		let skip = recent_block_sizes.len().saturating_sub(window);
+		recent_block_sizes
+			.iter()
+			.skip(skip)
+			.map(|size| f64::from(*size) / f64::from(self.block_size))
+			.collect()
+	}
+
+	fn record_block_size(&self, size: usize) {
+		let mut recent_block_sizes = self.recent_block_sizes.lock().unwrap();
+		if recent_block_sizes.len() == RECENT_BLOCK_SIZES_CAPACITY {
+			recent_block_sizes.pop_front();
+		}
+		recent_block_sizes.push_back(size as u32);
+	}
+
+	/// Builds the settlement commitment of a block built by this sequencer, taking the height
+	/// from the sequencer state. Fails unless `block` is the last block built.
+	pub async fn block_commitment(
+		&self,
+		block: &Block,
+		commitment: Commitment,
+	) -> Result<BlockCommitment, anyhow::Error> {
+		let block_id = block.id();
+		match &*self.last_block.read().await {
+			Some((height, last_block_id)) if *last_block_id == block_id => {
+				Ok(BlockCommitment { height: *height, block_id, commitment })
+			}
+			_ => Err(anyhow::anyhow!("Block {} is not the last block built", block_id)),
+		}
+	}
+
+	/// Builds the settlement commitment of the last block built, with the state commitment
+	/// computed by the commitment provider, see [`Memseq::with_commitment_provider`].
+	pub async fn produce_commitment(
+		&self,
+		block: &Block,
+	) -> Result<BlockCommitment, anyhow::Error> {
+		let commitment = self.commitment_provider.commitment_for(block).await?;
+		self.block_commitment(block, commitment).await
+	}
+
+	/// Returns up to `limit` pending transactions matching the predicate, in the order they
+	/// would be included in blocks. Nothing is removed from the mempool.
+	pub async fn find_pending<F: Fn(&Transaction) -> bool>(
+		&self,
+		pred: F,
+		limit: usize,
+	) -> Result<Vec<Transaction>, anyhow::Error> {
+		let mempool = self.mempool.read().await;
+		mempool.find_transactions(pred, limit).await
+	}
+
+	/// Moves the pending transactions to the `new` backend and switches this sequencer to it,
+	/// e.g. to migrate the mempool to another RocksDB path without downtime.
+	///
+	/// Block production and publishing pause for the duration of the swap. The backends are
+	/// exchanged in place: afterwards `new` holds the drained previous backend, so that the
+	/// caller can close it. The chain state, i.e. the persisted parent block and its height,
+	/// is copied along, so that a sequencer reopened on `new` continues the same chain; stored
+	/// blocks are not
+	/// moved. If the drain fails, this sequencer keeps its previous backend with all pending
+	/// transactions.
+	pub async fn swap_mempool(&self, new: Arc<RwLock<T>>) -> Result<(), anyhow::Error> {
+		if Arc::ptr_eq(&self.mempool, &new) {
+			return Ok(());
+		}
+		// exclusive, so that no block is built from a half-drained mempool
+		let mut mempool = self.mempool.write().await;
+		let mut new = new.write().await;
+
+		new.set_parent_block(self.height().await, self.parent_block.read().await.clone())
+			.await?;
+		// copy before removing, so that a failure loses nothing
+		let pending = mempool.find_mempool_transactions(|_| true, usize::MAX).await?;
+		for mempool_transaction in &pending {
+			new.add_mempool_transaction(mempool_transaction.clone()).await?;
+		}
+		for mempool_transaction in pending {
+			mempool.remove_mempool_transaction(mempool_transaction.id()).await?;
+		}
+
+		std::mem::swap(&mut *mempool, &mut *new);
+		Ok(())
+	}
 }
 
 impl Memseq<RocksdbMempool> {
-	pub fn try_move_rocks(path: PathBuf) -> Result<Self, anyhow::Error> {
+	/// Opens a sequencer over the RocksDB mempool at `path`, building on the parent block
+	/// persisted by a previous sequencer on the same path, if any.
+	pub async fn try_move_rocks(path: PathBuf) -> Result<Self, anyhow::Error> {
 		let mempool = RocksdbMempool::try_new(
 			path.to_str().ok_or(anyhow::anyhow!("PathBuf to str failed"))?,
 		)?;
+		Self::from_move_rocks(mempool).await
+	}
+
+	/// Opens a sequencer over an already configured RocksDB mempool, e.g. one with
+	/// [`RocksdbMempool::with_sync_writes`], continuing its persisted chain, if any.
+	pub async fn from_move_rocks(mempool: RocksdbMempool) -> Result<Self, anyhow::Error> {
 		let mempool = Arc::new(RwLock::new(mempool));
 		let parent_block = Arc::new(RwLock::new(Id::default()));
-		Ok(Self::new(mempool, 10, parent_block, 1000))
+		Self::new(mempool, 10, parent_block, 1000).with_persisted_chain().await
+	}
+
+	/// Flushes the mempool to disk every `interval` in the background, so that writes do not
+	/// depend on RocksDB flushing on its own. Durability-sensitive deployments should enable
+	/// this, and must then call [`Memseq::close`] before dropping the sequencer: `Drop` cannot
+	/// flush, and dropping without closing is logged as a warning.
+	///
+	/// Must be called within a Tokio runtime.
+	pub fn with_periodic_flush(mut self, interval: Duration) -> Self {
+		let cancel = CancellationToken::new();
+		let task = tokio::spawn({
+			let mempool = Arc::clone(&self.mempool);
+			let cancel = cancel.clone();
+			async move {
+				let mut ticker = tokio::time::interval(interval);
+				// the first tick completes immediately
+				ticker.tick().await;
+				loop {
+					tokio::select! {
+						_ = cancel.cancelled() => break,
+						_ = ticker.tick() => {}
+					}
+					if let Err(err) = mempool.read().await.flush().await {
+						tracing::warn!("Periodic mempool flush failed: {err}");
+					}
+				}
+			}
+		});
+		self.periodic_flush = Some(Arc::new(PeriodicFlush {
+			cancel,
+			task: std::sync::Mutex::new(Some(task)),
+			closed: AtomicBool::new(false),
+		}));
+		self
+	}
+
+	/// Stops the periodic flush, if enabled, and flushes the mempool a last time.
+	pub async fn close(&self) -> Result<(), anyhow::Error> {
+		if let Some(periodic_flush) = &self.periodic_flush {
+			periodic_flush.cancel.cancel();
+			let task = periodic_flush.task.lock().unwrap().take();
+			if let Some(task) = task {
+				task.await?;
+			}
+			periodic_flush.closed.store(true, Ordering::SeqCst);
+		}
+		self.mempool.read().await.flush().await
 	}
 
 	pub fn try_from_env_toml_file() -> Result<Self, anyhow::Error> {
@@ -53,332 +887,2271 @@ impl Memseq<RocksdbMempool> {
 
 impl<T: MempoolBlockOperations + MempoolTransactionOperations> Sequencer for Memseq<T> {
 	async fn publish(&self, transaction: Transaction) -> Result<(), anyhow::Error> {
-		let mempool = self.mempool.read().await;
-		mempool.add_transaction(transaction).await?;
+		// a single transaction is never partially published, report its own error
+		self.publish_batch(vec![transaction]).await.map_err(|error| {
+			match error.downcast::<PartialPublish>() {
+				Ok(partial) => partial.source,
+				Err(error) => error,
+			}
+		})
+	}
+
+	/// Publishes the transactions under a single acquisition of the mempool lock.
+	async fn publish_batch(&self, transactions: Vec<Transaction>) -> Result<(), anyhow::Error> {
+		self.ensure_accepting()?;
+		for transaction in &transactions {
+			self.validate(transaction)?;
+		}
+		let mempool = self.mempool.write().await;
+		let total = transactions.len();
+		// counted once, the batch is the only writer while the lock is held
+		let size = match self.max_mempool_size {
+			Some(_) => mempool.size().await?,
+			None => 0,
+		};
+		for (published, transaction) in transactions.into_iter().enumerate() {
+			let full = self.max_mempool_size.filter(|max| size + published as u64 >= *max);
+			let result = match full {
+				Some(max_mempool_size) => Err(MempoolFull { max_mempool_size }.into()),
+				None => mempool.add_transaction(transaction).await,
+			};
+			if let Err(source) = result {
+				self.metrics.record_published(published as u64);
+				self.update_pending_count(&mempool).await;
+				return Err(PartialPublish { published, total, source }.into());
+			}
+		}
+		self.metrics.record_published(total as u64);
+		self.update_pending_count(&mempool).await;
 		Ok(())
 	}
 
 	async fn wait_for_next_block(&self) -> Result<Option<Block>, anyhow::Error> {
-		let mempool = self.mempool.read().await;
+		self.wait_for_next_block_with_cancel(CancellationToken::new()).await
+	}
+}
+
+impl<T: MempoolBlockOperations + MempoolTransactionOperations> Memseq<T> {
+	/// Builds the next block like [`Sequencer::wait_for_next_block`], but stops waiting for
+	/// more transactions as soon as `cancel` is cancelled, e.g. on shutdown. The transactions
+	/// gathered so far still make up a block, or `None` if there are none.
+	pub async fn wait_for_next_block_with_cancel(
+		&self,
+		cancel: CancellationToken,
+	) -> Result<Option<Block>, anyhow::Error> {
+		self.build_next_block(cancel, true, Vec::new()).await
+	}
+
+	/// Streams the blocks built on each boundary of `interval` in wall-clock time, i.e. each
+	/// multiple of `interval` since the Unix epoch, so that sequencers with synchronized
+	/// clocks emit blocks at the same instants. Each block takes the transactions pending at
+	/// its boundary, up to `block_size`, without waiting for more. A boundary with no pending
+	/// transactions is skipped, unless [`Memseq::with_heartbeat`] is set.
+	///
+	/// Boundaries that pass while the previous block is built or consumed are skipped.
+	/// Panics if `interval` is zero.
+	pub fn into_scheduled_block_stream(
+		self,
+		interval: Duration,
+	) -> impl Stream<Item = Result<Block, anyhow::Error>> {
+		assert!(!interval.is_zero(), "the block interval must not be zero");
+		// the wall-clock time is read once, then advanced with the Tokio clock, so that the
+		// boundaries follow the Tokio timer, paused time included
+		let started_at = SystemTime::now();
+		let started = tokio::time::Instant::now();
+		futures::stream::unfold(self, move |memseq| async move {
+			loop {
+				let now = started_at + started.elapsed();
+				tokio::time::sleep(until_next_boundary(now, interval)).await;
+				match memseq.build_scheduled_block().await {
+					Ok(Some(block)) => return Some((Ok(block), memseq)),
+					Ok(None) => continue,
+					Err(err) => return Some((Err(err), memseq)),
+				}
+			}
+		})
+	}
+
+	/// Builds a block of the transactions pending right now, or a heartbeat block if there
+	/// are none and heartbeats are enabled.
+	async fn build_scheduled_block(&self) -> Result<Option<Block>, anyhow::Error> {
+		let block = self.build_next_block(CancellationToken::new(), false, Vec::new()).await?;
+		if block.is_some() || !self.heartbeat {
+			return Ok(block);
+		}
+		let mempool = self.mempool.write().await;
+		let started = tokio::time::Instant::now();
+		Ok(Some(self.seal_block(&mempool, Vec::new(), 0, started, false).await?))
+	}
+
+	/// Builds the next block, waiting for more transactions until the building time expires if
+	/// `wait` is set, or taking only those pending right now otherwise. The `pinned`
+	/// transactions, already taken out of the mempool, are included ahead of those popped and
+	/// are neither filtered nor counted against the category quotas, see
+	/// [`Memseq::take_pinned`].
+	async fn build_next_block(
+		&self,
+		cancel: CancellationToken,
+		wait: bool,
+		pinned: Vec<MempoolTransaction>,
+	) -> Result<Option<Block>, anyhow::Error> {
 		let mut transactions = Vec::new();
+		// ids of the transactions in the block, tracked when dedup is set
+		let mut included = HashSet::new();
+		// groups admitted into the block in order of appearance, with their members popped so
+		// far, tracked when soft_block_size is set
+		let mut groups: Vec<(Id, Vec<MempoolTransaction>)> = Vec::new();
+		// groups kept out of the block along with all their members, by the assembly filter
+		// or by a category quota
+		let mut rejected_groups = HashSet::new();
+		let mut held_back_groups = HashSet::new();
+		// re-queued once the block is built, so that they are not popped again meanwhile
+		let mut rejected = Vec::new();
+		// transactions of categories over quota, put back once the block is built
+		let mut held_back = Vec::new();
+		// transactions popped into the block per category
+		let mut category_counts: HashMap<u8, u32> = HashMap::new();
+		// ids of the transactions published with priority, which lead the block
+		let mut priority = HashSet::new();
+		// summed `Transaction::size_bytes` of the block, tracked when max_block_bytes is set
+		let mut block_bytes = 0;
+		let mut bytes_exhausted = false;
+		let mut hit_time_limit = false;
+
+		for mempool_transaction in pinned {
+			if self.max_block_bytes.is_some() {
+				block_bytes += mempool_transaction.transaction.size_bytes() as u64;
+			}
+			if self.dedup {
+				included.insert(mempool_transaction.id());
+			}
+			transactions.push(mempool_transaction.transaction);
+		}
 
-		let mut now = std::time::Instant::now();
+		let started = tokio::time::Instant::now();
+		let mut now = started;
 		let finish_by = now + std::time::Duration::from_millis(self.building_time_ms);
+		let min_fill = self.min_fill();
 
 		loop {
 			let current_block_size = transactions.len() as u32;
-			if current_block_size >= self.block_size {
+			if current_block_size >= min_fill || cancel.is_cancelled() {
 				break;
 			}
 
+			// exclusive while draining, released while waiting for more transactions
+			let mempool = self.mempool.write().await;
 			for _ in 0..self.block_size - current_block_size {
-				if let Some(transaction) = mempool.pop_transaction().await? {
-					transactions.push(transaction);
-				} else {
+				// popped with its metadata, which tells transactions published with priority apart
+				let popped = mempool.pop_mempool_transaction().await?;
+				let Some(mempool_transaction) = popped else {
 					break;
+				};
+				if self.dedup && included.contains(&mempool_transaction.id()) {
+					continue;
+				}
+				// the first member popped decides for its whole group, the others follow
+				let group = mempool_transaction.group.clone().filter(|_| self.soft_block_size);
+				if let Some(group) = &group {
+					if let Some((_, members)) = groups.iter_mut().find(|(id, _)| id == group) {
+						if self.max_block_bytes.is_some() {
+							// checked against max_block_bytes once the group is complete
+							block_bytes += mempool_transaction.transaction.size_bytes() as u64;
+						}
+						if self.dedup {
+							included.insert(mempool_transaction.id());
+						}
+						transactions.push(mempool_transaction.transaction.clone());
+						members.push(mempool_transaction);
+						continue;
+					}
+					if rejected_groups.contains(group) {
+						rejected.push(mempool_transaction);
+						self.metrics.record_rejected(1);
+						continue;
+					}
+					if held_back_groups.contains(group) {
+						held_back.push(mempool_transaction);
+						continue;
+					}
+				}
+				// trusted, so neither filtered nor counted against the category quotas
+				let is_priority = mempool_transaction.priority;
+				if !is_priority
+					&& self.assembly_filter.as_ref().is_some_and(|assembly_filter| {
+						!assembly_filter(&mempool_transaction.transaction)
+					}) {
+					rejected_groups.extend(group);
+					rejected.push(mempool_transaction);
+					self.metrics.record_rejected(1);
+					continue;
+				}
+				let category = mempool_transaction.transaction.category;
+				if !is_priority
+					&& self.category_limit(category).is_some_and(|limit| {
+						category_counts.get(&category).copied().unwrap_or(0) >= limit
+					}) {
+					held_back_groups.extend(group);
+					held_back.push(mempool_transaction);
+					continue;
+				}
+				if let Some(max_block_bytes) = self.max_block_bytes {
+					let size = mempool_transaction.transaction.size_bytes() as u64;
+					if group.is_some() {
+						// checked against max_block_bytes once the group is complete
+						block_bytes += size;
+					} else if size > max_block_bytes {
+						self.dead_letters.write().await.push(mempool_transaction.transaction);
+						self.metrics.record_dead_lettered();
+						continue;
+					} else if block_bytes + size > max_block_bytes {
+						mempool.reinsert_mempool_transaction(mempool_transaction).await?;
+						bytes_exhausted = true;
+						break;
+					} else {
+						block_bytes += size;
+					}
+				}
+				if self.dedup {
+					included.insert(mempool_transaction.id());
 				}
+				if is_priority {
+					priority.insert(mempool_transaction.id());
+				} else {
+					*category_counts.entry(category).or_insert(0) += 1;
+				}
+				transactions.push(mempool_transaction.transaction.clone());
+				if let Some(group) = group {
+					groups.push((group, vec![mempool_transaction]));
+				}
+			}
+
+			drop(mempool);
+
+			if bytes_exhausted || !wait {
+				break;
 			}
 
-			// sleep to yield to other tasks and wait for more transactions
-			tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+			// sleep to yield to other tasks and wait for more transactions, but not past the
+			// building time
+			let poll_interval = std::time::Duration::from_millis(self.poll_interval_ms);
+			let remaining = finish_by.saturating_duration_since(tokio::time::Instant::now());
+			tokio::select! {
+				_ = tokio::time::sleep(poll_interval.min(remaining)) => {}
+				_ = cancel.cancelled() => break,
+			}
 
-			now = std::time::Instant::now();
-			if now > finish_by {
+			now = tokio::time::Instant::now();
+			if now >= finish_by {
+				hit_time_limit = true;
 				break;
 			}
 		}
 
+		let mempool = self.mempool.write().await;
+		// the pending members of the admitted and rejected groups, found in a single scan
+		let mut pending_members: HashMap<Id, Vec<MempoolTransaction>> = HashMap::new();
+		if !groups.is_empty() || !rejected_groups.is_empty() {
+			let members = mempool
+				.find_mempool_transactions(
+					|mempool_transaction| {
+						mempool_transaction.group.as_ref().is_some_and(|group| {
+							rejected_groups.contains(group)
+								|| groups.iter().any(|(id, _)| id == group)
+						})
+					},
+					usize::MAX,
+				)
+				.await?;
+			for member in members {
+				if let Some(group) = member.group.clone() {
+					pending_members.entry(group).or_default().push(member);
+				}
+			}
+		}
+
+		// complete the groups the block has started, possibly overshooting block_size, as long
+		// as each complete group fits in max_block_bytes
+		for (group, popped) in groups {
+			let pending = pending_members.remove(&group).unwrap_or_default();
+			if let Some(max_block_bytes) = self.max_block_bytes {
+				let popped_bytes: u64 =
+					popped.iter().map(|member| member.transaction.size_bytes() as u64).sum();
+				let pending_bytes: u64 =
+					pending.iter().map(|member| member.transaction.size_bytes() as u64).sum();
+				if block_bytes + pending_bytes > max_block_bytes {
+					block_bytes -= popped_bytes;
+					let popped_ids: HashSet<_> = popped.iter().map(|member| member.id()).collect();
+					transactions.retain(|transaction| !popped_ids.contains(&transaction.id()));
+					if popped_bytes + pending_bytes > max_block_bytes {
+						// the group can never fit in a block
+						let mut dead_letters = self.dead_letters.write().await;
+						for member in pending {
+							mempool.remove_mempool_transaction(member.id()).await?;
+							dead_letters.push(member.transaction);
+							self.metrics.record_dead_lettered();
+						}
+						for member in popped {
+							dead_letters.push(member.transaction);
+							self.metrics.record_dead_lettered();
+						}
+					} else {
+						// left in place for a later block
+						held_back.extend(popped);
+					}
+					continue;
+				}
+				block_bytes += pending_bytes;
+			}
+			for member in pending {
+				mempool.remove_mempool_transaction(member.id()).await?;
+				if self.dedup && !included.insert(member.id()) {
+					continue;
+				}
+				transactions.push(member.transaction);
+			}
+		}
+		// rejected groups are re-queued whole
+		for (_, pending) in pending_members {
+			for member in pending {
+				mempool.remove_mempool_transaction(member.id()).await?;
+				self.metrics.record_rejected(1);
+				rejected.push(member);
+			}
+		}
+
+		for mempool_transaction in held_back {
+			mempool.reinsert_mempool_transaction(mempool_transaction).await?;
+		}
+
+		if self.assembly_filter.is_some() {
+			self.requeue_rejected(&mempool, &transactions, rejected).await?;
+		}
+		self.update_pending_count(&mempool).await;
+
 		if transactions.is_empty() {
-			Ok(None)
-		} else {
-			Ok(Some(Block::new(
-				Default::default(),
-				self.parent_block.read().await.clone().to_vec(),
-				transactions,
-			)))
+			return Ok(None);
 		}
+		// priority transactions popped while waiting lead the block too, in pop order
+		let (mut ordered, rest): (Vec<_>, Vec<_>) = transactions
+			.into_iter()
+			.partition(|transaction| priority.contains(&transaction.id()));
+		let priority_count = ordered.len();
+		ordered.extend(rest);
+		let block = self
+			.seal_block(&mempool, ordered, priority_count, started, hit_time_limit)
+			.await?;
+		Ok(Some(block))
 	}
-}
 
-#[cfg(test)]
-pub mod test {
+	/// Waits up to `timeout` for the transaction `tx_id` to be pending, then builds a block
+	/// right away that includes it, along with the transactions ahead of it in pop order up
+	/// to `block_size`. Returns `None` if the transaction did not show up in time, e.g.
+	/// because it was included in another block meanwhile, or if it can never fit in
+	/// `max_block_bytes`, in which case it is dead-lettered.
+	///
+	/// The block is assembled as by [`Memseq::wait_for_next_block`] without waiting, except
+	/// that the transaction is pinned into it ahead of the transactions popped, along with the
+	/// rest of its group with [`Memseq::with_soft_block_size`].
+	pub async fn build_block_containing(
+		&self,
+		tx_id: Id,
+		timeout: Duration,
+	) -> Result<Option<Block>, anyhow::Error> {
+		let finish_by = tokio::time::Instant::now() + timeout;
+		loop {
+			let mempool = self.mempool.write().await;
+			if let Some(target) = mempool.get_mempool_transaction(tx_id.clone()).await? {
+				// out of the mempool, so that the transaction cannot be taken by another block
+				// once the lock is released
+				let pinned = self.take_pinned(&mempool, target).await?;
+				drop(mempool);
+				if pinned.is_empty() {
+					return Ok(None);
+				}
+				return self.build_next_block(CancellationToken::new(), false, pinned).await;
+			}
+			drop(mempool);
 
-	use super::*;
-	use futures::stream::FuturesUnordered;
-	use futures::StreamExt;
-	use mempool_util::MempoolTransaction;
-	use tempfile::tempdir;
+			let remaining = finish_by.saturating_duration_since(tokio::time::Instant::now());
+			if remaining.is_zero() {
+				return Ok(None);
+			}
+			let poll_interval = Duration::from_millis(self.poll_interval_ms);
+			tokio::time::sleep(poll_interval.min(remaining)).await;
+		}
+	}
+
+	/// Removes the pending transaction from the mempool, along with the rest of its group with
+	/// [`Memseq::with_soft_block_size`], to pin them into the next block. Returns nothing if
+	/// they can never fit in `max_block_bytes`, in which case they are dead-lettered.
+	async fn take_pinned(
+		&self,
+		mempool: &T,
+		target: MempoolTransaction,
+	) -> Result<Vec<MempoolTransaction>, anyhow::Error> {
+		let pinned = match target.group.clone().filter(|_| self.soft_block_size) {
+			Some(group) => {
+				mempool
+					.find_mempool_transactions(
+						|mempool_transaction| mempool_transaction.group.as_ref() == Some(&group),
+						usize::MAX,
+					)
+					.await?
+			}
+			None => vec![target],
+		};
+		for mempool_transaction in &pinned {
+			mempool.remove_mempool_transaction(mempool_transaction.id()).await?;
+		}
+		let pinned_bytes: u64 =
+			pinned.iter().map(|member| member.transaction.size_bytes() as u64).sum();
+		if self
+			.max_block_bytes
+			.is_some_and(|max_block_bytes| pinned_bytes > max_block_bytes)
+		{
+			let mut dead_letters = self.dead_letters.write().await;
+			for mempool_transaction in pinned {
+				dead_letters.push(mempool_transaction.transaction);
+				self.metrics.record_dead_lettered();
+			}
+			self.update_pending_count(mempool).await;
+			return Ok(Vec::new());
+		}
+		Ok(pinned)
+	}
+
+	/// Makes a block of the transactions on top of the parent block and records it as the
+	/// last block built, persisting the parent block it advances to. With `sort_by_sequence`,
+	/// the transactions are sorted first, except the leading `priority_count` ones published
+	/// with priority, which keep their place. The block is stamped with its height, see
+	/// [`Memseq::height`], the current time and the sequencer id as its proposer. A block
+	/// without transactions is a heartbeat.
+	async fn seal_block(
+		&self,
+		mempool: &T,
+		mut transactions: Vec<Transaction>,
+		priority_count: usize,
+		started: tokio::time::Instant,
+		hit_time_limit: bool,
+	) -> Result<Block, anyhow::Error> {
+		if self.sort_by_sequence {
+			transactions[priority_count..].sort_by_key(|transaction| transaction.sequence_number);
+		}
+		let mut last_block = self.last_block.write().await;
+		let height = last_block.as_ref().map_or(0, |(height, _)| *height) + 1;
+		let parent = self.parent_block.read().await.clone();
+		let block = self.assemble_block(transactions, parent, height)?;
+		self.advance_chain(mempool, &mut last_block, &block).await?;
+		if let Some(metrics_sink) = &self.metrics_sink {
+			let tx_count = block.transactions.len() as u32;
+			metrics_sink.record_build(tx_count, started.elapsed(), hit_time_limit);
+		}
+		Ok(block)
+	}
+
+	/// Block of the transactions on top of `parent` at `height`, with the extra data of the
+	/// extra provider, proposed by this sequencer at the current time.
+	fn assemble_block(
+		&self,
+		transactions: Vec<Transaction>,
+		parent: Id,
+		height: u64,
+	) -> Result<Block, anyhow::Error> {
+		let extra = match &self.extra_provider {
+			Some(extra_provider) => extra_provider(&transactions),
+			None => Vec::new(),
+		};
+		let timestamp_ms =
+			SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_millis() as u64;
+		let metadata = BlockMetadata { proposer: self.sequencer_id.clone(), ..Default::default() };
+		let mut block = Block::new_with_extra(metadata, parent.to_vec(), transactions, extra);
+		block.height = height;
+		block.timestamp_ms = timestamp_ms;
+		Ok(block)
+	}
+
+	/// Records the block as the last one built and, with auto advance, as the parent of the
+	/// next one.
+	async fn advance_chain(
+		&self,
+		mempool: &T,
+		last_block: &mut Option<(u64, Id)>,
+		block: &Block,
+	) -> Result<(), anyhow::Error> {
+		self.record_block_size(block.transactions.len());
+		self.metrics.record_block(block.transactions.len() as u64);
+		*last_block = Some((block.height, block.id()));
+		if self.auto_advance_parent {
+			mempool.set_parent_block(block.height, block.id()).await?;
+			*self.parent_block.write().await = block.id();
+		}
+		Ok(())
+	}
+}
+
+/// Time from `now` to the next multiple of `interval` since the Unix epoch, a whole `interval`
+/// if `now` is on one.
+fn until_next_boundary(now: SystemTime, interval: Duration) -> Duration {
+	let since_epoch = now.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default();
+	let into_interval = since_epoch.as_nanos() % interval.as_nanos();
+	interval - Duration::from_nanos(into_interval as u64)
+}
+
+#[cfg(test)]
+pub mod test {
+
+	use super::*;
+	use futures::stream::FuturesUnordered;
+	use futures::StreamExt;
+	use std::path::Path;
+	use tempfile::tempdir;
+
+	// set for the child process of `crash_recover_roundtrip`
+	const CRASH_PATH_VAR: &str = "MEMSEQ_CRASH_PATH";
+	const CRASH_SYNC_VAR: &str = "MEMSEQ_CRASH_SYNC";
+	const CRASH_TRANSACTIONS_VAR: &str = "MEMSEQ_CRASH_TRANSACTIONS";
+
+	/// The transactions published by [`crash_recover_roundtrip`], one per value of `values`.
+	fn crash_transactions(values: std::ops::Range<u8>) -> Vec<Transaction> {
+		values.map(|i| Transaction::new(vec![i], i as u64)).collect()
+	}
+
+	/// Publishes the transactions of `values` to a sequencer over the RocksDB mempool at
+	/// `path` in a child process, which then aborts as a crash would, without closing or
+	/// flushing anything. Returns the pending transactions that survived, in pop order, and
+	/// the write-ahead log syncs of the child.
+	///
+	/// Published transactions survive a crash of the process whether or not
+	/// `sync_on_publish` is set: RocksDB hands every write to the operating system before
+	/// returning, and replays its write-ahead log on reopening. What `sync_on_publish` adds is
+	/// a sync of the log before publishing returns, which protects against a crash of the
+	/// machine; that crash cannot be simulated here, but the syncs can be counted.
+	async fn crash_recover_roundtrip(
+		path: &Path,
+		values: std::ops::Range<u8>,
+		sync_on_publish: bool,
+	) -> Result<(Vec<Transaction>, u64), anyhow::Error> {
+		let path = path.to_str().ok_or(anyhow::anyhow!("Path to str failed"))?;
+		let output = std::process::Command::new(std::env::current_exe()?)
+			.args(["--exact", "test::crash_child", "--nocapture", "--test-threads=1"])
+			.env(CRASH_PATH_VAR, path)
+			.env(CRASH_SYNC_VAR, sync_on_publish.to_string())
+			.env(CRASH_TRANSACTIONS_VAR, format!("{} {}", values.start, values.end))
+			.output()?;
+		let stdout = String::from_utf8_lossy(&output.stdout);
+		assert!(!output.status.success(), "the child did not crash: {stdout}");
+		let wal_syncs = stdout
+			.lines()
+			.find_map(|line| line.strip_prefix("wal_syncs="))
+			.ok_or(anyhow::anyhow!("the child crashed before publishing: {stdout}"))?
+			.parse()?;
+
+		let memseq = Memseq::from_move_rocks(RocksdbMempool::try_new(path)?).await?;
+		Ok((memseq.find_pending(|_| true, usize::MAX).await?, wal_syncs))
+	}
+
+	/// Child process of [`crash_recover_roundtrip`], which passes without doing anything when
+	/// run on its own.
+	#[tokio::test]
+	async fn crash_child() -> Result<(), anyhow::Error> {
+		use std::io::Write;
+		let Ok(path) = std::env::var(CRASH_PATH_VAR) else { return Ok(()) };
+		let sync_on_publish = std::env::var(CRASH_SYNC_VAR)?.parse()?;
+		let values = std::env::var(CRASH_TRANSACTIONS_VAR)?;
+		let (start, end) = values.split_once(' ').ok_or(anyhow::anyhow!("Bad range {values}"))?;
+
+		let memseq = Memseq::from_move_rocks(
+			RocksdbMempool::try_new(&path)?.with_sync_writes(sync_on_publish),
+		)
+		.await?;
+		memseq.publish_batch(crash_transactions(start.parse()?..end.parse()?)).await?;
+		println!("wal_syncs={}", memseq.mempool.read().await.wal_syncs().await?);
+		std::io::stdout().flush()?;
+		std::process::abort();
+	}
+
+	#[tokio::test]
+	async fn test_wait_for_next_block_building_time_expires() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let path = dir.path().to_path_buf();
+		let memseq = Memseq::try_move_rocks(path)
+			.await?
+			.with_block_size(10)
+			.with_building_time_ms(500);
+
+		// Add some transactions
+		for i in 0..5 {
+			let transaction = Transaction::new(vec![i as u8], 0);
+			memseq.publish(transaction).await?;
+		}
+
+		// Wait for the block to be built, not enough transactions as such
+		// the building time should expire
+		let block = memseq.wait_for_next_block().await?;
+		assert!(block.is_some());
+
+		let block = block.ok_or(anyhow::anyhow!("Block not found"))?;
+		assert_eq!(block.transactions.len(), 5);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_build_block_containing() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let path = dir.path().to_path_buf();
+		let memseq = Memseq::try_move_rocks(path)
+			.await?
+			.with_block_size(2)
+			.with_building_time_ms(10_000);
+		let transactions: Vec<_> = (0..3).map(|i| Transaction::new(vec![i], 0)).collect();
+		memseq.publish_batch(transactions[..2].to_vec()).await?;
+
+		// published while waiting
+		let target = transactions[2].clone();
+		tokio::spawn({
+			let memseq = memseq.clone();
+			async move {
+				tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+				memseq.publish(target).await
+			}
+		});
+		let start = std::time::Instant::now();
+		let block = memseq
+			.build_block_containing(transactions[2].id(), std::time::Duration::from_secs(5))
+			.await?
+			.expect("block containing the transaction");
+		assert!(start.elapsed() < std::time::Duration::from_secs(1));
+		// it leads the block, the transactions ahead of it fill the rest
+		assert_eq!(block.transactions, vec![transactions[2].clone(), transactions[0].clone()]);
+		assert_eq!(memseq.height().await, 1);
+		assert_eq!(memseq.find_pending(|_| true, usize::MAX).await?, transactions[1..2]);
+
+		let missing = Transaction::new(vec![9], 0).id();
+		let timeout = std::time::Duration::from_millis(50);
+		assert_eq!(memseq.build_block_containing(missing, timeout).await?, None);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_build_block_containing_follows_assembly() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let path = dir.path().to_path_buf();
+		let memseq = Memseq::try_move_rocks(path)
+			.await?
+			.with_block_size(5)
+			.with_soft_block_size(true)
+			.with_max_block_bytes(3 * Transaction::new(vec![0], 0).size_bytes() as u64)
+			.with_assembly_filter(Arc::new(|transaction: &Transaction| transaction.data() != [0]));
+		let timeout = std::time::Duration::from_millis(50);
+
+		let filtered = Transaction::new(vec![0], 0);
+		let regular: Vec<_> = (1..3).map(|i| Transaction::new(vec![i], 0)).collect();
+		memseq
+			.publish_batch(vec![regular[0].clone(), filtered.clone(), regular[1].clone()])
+			.await?;
+		let group: Vec<_> = (10..12).map(|i| Transaction::new(vec![i], 0)).collect();
+		memseq.publish_bundle(bundle_of(&group)).await?;
+
+		// the whole group is pinned, the filtered transaction is re-queued and the block stops
+		// at max_block_bytes
+		let block = memseq
+			.build_block_containing(group[1].id(), timeout)
+			.await?
+			.expect("block containing the transaction");
+		assert_eq!(block.transactions, [group.clone(), regular[..1].to_vec()].concat());
+		assert_eq!(
+			memseq.find_pending(|_| true, usize::MAX).await?,
+			[regular[1].clone(), filtered]
+		);
+
+		// a group that can never fit is dead-lettered
+		let oversized: Vec<_> = (20..24).map(|i| Transaction::new(vec![i], 0)).collect();
+		memseq.publish_bundle(bundle_of(&oversized)).await?;
+		assert_eq!(memseq.build_block_containing(oversized[0].id(), timeout).await?, None);
+		assert_eq!(memseq.dead_letters().await?, oversized);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_sort_by_sequence() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let path = dir.path().to_path_buf();
+		let memseq = Memseq::try_move_rocks(path)
+			.await?
+			.with_block_size(4)
+			.with_sort_by_sequence(true);
+
+		// inserted out of order, with two transactions sharing a sequence number
+		let transactions: Vec<_> = [5, 3, 4, 3]
+			.into_iter()
+			.enumerate()
+			.map(|(i, seq)| Transaction::new(vec![i as u8], seq))
+			.collect();
+		memseq.publish_batch(transactions.clone()).await?;
+
+		let block = memseq.wait_for_next_block().await?.expect("block");
+		let sorted: Vec<_> = [1, 3, 2, 0].into_iter().map(|i| transactions[i].clone()).collect();
+		assert_eq!(block.transactions, sorted);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_sort_by_sequence_keeps_priority_prefix() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let path = dir.path().to_path_buf();
+		let memseq = Memseq::try_move_rocks(path)
+			.await?
+			.with_block_size(6)
+			.with_sort_by_sequence(true)
+			.with_category_quotas(HashMap::from([(1, 0.2)]))
+			.with_assembly_filter(Arc::new(|transaction: &Transaction| transaction.data() != [0]));
+
+		let regular: Vec<_> = [5, 3, 4]
+			.into_iter()
+			.map(|seq| Transaction::new(vec![seq], seq as u64))
+			.collect();
+		memseq.publish_batch(regular.clone()).await?;
+		// rejected by the filter and over the category quota if they were regular
+		let filtered = Transaction::new(vec![0], 9);
+		let first = Transaction::new(vec![10], 8).with_category(1);
+		let second = Transaction::new(vec![11], 7).with_category(1);
+		for transaction in [filtered.clone(), first.clone(), second.clone()] {
+			memseq.publish_priority(transaction).await?;
+		}
+
+		let block = memseq.wait_for_next_block().await?.expect("block");
+		let priority = &block.transactions[..3];
+		for transaction in [&filtered, &first, &second] {
+			assert!(priority.contains(transaction));
+		}
+		let rest: Vec<_> = [1, 2, 0].into_iter().map(|i| regular[i].clone()).collect();
+		assert_eq!(block.transactions[3..], rest);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_slot_zero_is_not_priority() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let path = dir.path().to_path_buf();
+		let memseq = Memseq::try_move_rocks(path)
+			.await?
+			.with_assembly_filter(Arc::new(|transaction: &Transaction| transaction.data() != [0]));
+
+		// slotted at time zero like a priority transaction, but not published with priority
+		let filtered = Transaction::new(vec![0], 0);
+		let mempool_transaction = MempoolTransaction::at_time(filtered.clone(), 0);
+		memseq.mempool.write().await.add_mempool_transaction(mempool_transaction).await?;
+		let regular = Transaction::new(vec![1], 0);
+		memseq.publish(regular.clone()).await?;
+
+		let block = memseq.wait_for_next_block().await?.expect("block");
+		assert_eq!(block.transactions, vec![regular]);
+
+		Ok(())
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn test_scheduled_blocks_align_to_interval() -> Result<(), anyhow::Error> {
+		let interval = Duration::from_millis(200);
+		let memseq = Memseq::try_in_memory(2, 1000)?.with_heartbeat(true);
+		let transactions: Vec<_> = (0..3).map(|i| Transaction::new(vec![i], 0)).collect();
+		memseq.publish_batch(transactions.clone()).await?;
+
+		let started = tokio::time::Instant::now();
+		let mut blocks = Box::pin(memseq.into_scheduled_block_stream(interval));
+		// read after the stream, the wall-clock time is never behind the stream's
+		let started_at = SystemTime::now();
+		let mut emitted = Vec::new();
+		for _ in 0..3 {
+			let block = blocks.next().await.expect("block")?;
+			emitted.push((block, tokio::time::Instant::now()));
+		}
+
+		// the first block waits for the first boundary only
+		assert!(emitted[0].1 - started <= interval);
+		for (_, emitted_at) in &emitted {
+			// emitted on a boundary, give or take the real time elapsed while the test runs
+			let since_epoch =
+				(started_at + (*emitted_at - started)).duration_since(SystemTime::UNIX_EPOCH)?;
+			let into_interval = since_epoch.as_millis() % interval.as_millis();
+			assert!(into_interval < 50, "emitted {into_interval}ms after a boundary");
+		}
+		for pair in emitted.windows(2) {
+			assert_eq!(pair[1].1 - pair[0].1, interval);
+		}
+
+		// drains up to block_size, then emits a heartbeat once the mempool is empty
+		assert_eq!(emitted[0].0.transactions, transactions[..2]);
+		assert_eq!(emitted[1].0.transactions, transactions[2..]);
+		assert!(emitted[2].0.transactions.is_empty());
+		assert_eq!(emitted[2].0.parent, emitted[1].0.id().to_vec());
+		// stamped with their height and the wall-clock build time, which paused time does not
+		// hold back
+		let now_ms = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_millis() as u64;
+		let started_ms = started_at.duration_since(SystemTime::UNIX_EPOCH)?.as_millis() as u64;
+		for (height, (block, _)) in emitted.iter().enumerate() {
+			assert_eq!(block.height, height as u64 + 1);
+			assert!((started_ms..=now_ms).contains(&block.timestamp_ms));
+		}
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_watch_pending_count() -> Result<(), anyhow::Error> {
+		let memseq = Memseq::try_in_memory(2, 10)?;
+		let mut pending_count = memseq.watch_pending_count();
+		assert_eq!(*pending_count.borrow(), 0);
+
+		memseq.publish(Transaction::new(vec![1], 0)).await?;
+		assert!(pending_count.has_changed()?);
+		assert_eq!(*pending_count.borrow_and_update(), 1);
+		memseq
+			.publish_batch((2..4).map(|i| Transaction::new(vec![i], 0)).collect())
+			.await?;
+		assert_eq!(*pending_count.borrow_and_update(), 3);
+
+		// a consumer awaiting the count reacts to the block being built
+		let watcher = tokio::spawn(async move {
+			pending_count.wait_for(|count| *count < 2).await.map(|count| *count)
+		});
+		let block = memseq.wait_for_next_block().await?.expect("block");
+		assert_eq!(block.transactions.len(), 2);
+		assert_eq!(watcher.await??, 1);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_parent_block_survives_restart() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let path = dir.path().to_path_buf();
+		let memseq = Memseq::try_move_rocks(path.clone()).await?;
+		assert_eq!(memseq.current_parent().await, Id::default());
+		memseq.publish(Transaction::new(vec![1], 0)).await?;
+		let block = memseq.wait_for_next_block().await?.expect("block");
+		drop(memseq);
+
+		let memseq = Memseq::try_move_rocks(path).await?;
+		assert_eq!(memseq.current_parent().await, block.id());
+		assert_eq!(memseq.height().await, block.height);
+
+		// the next block extends the chain instead of starting an orphan one
+		memseq.publish(Transaction::new(vec![2], 0)).await?;
+		let next = memseq.wait_for_next_block().await?.expect("next block");
+		assert_eq!(next.parent, block.id().to_vec());
+		assert_eq!(next.height, block.height + 1);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_crash_recovery_with_sync_on_publish() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let (survived, wal_syncs) = crash_recover_roundtrip(dir.path(), 0..100, true).await?;
+		assert_eq!(survived, crash_transactions(0..100));
+		assert!(wal_syncs > 0);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_crash_recovery_without_sync_on_publish() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let (survived, wal_syncs) = crash_recover_roundtrip(dir.path(), 0..100, false).await?;
+		assert_eq!(survived, crash_transactions(0..100));
+		// survived the crash of the process without ever being synced
+		assert_eq!(wal_syncs, 0);
+
+		// transactions published after a recovery are added to the survivors
+		let (survived, _) = crash_recover_roundtrip(dir.path(), 100..101, false).await?;
+		assert_eq!(survived, crash_transactions(0..101));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_wait_for_next_block_with_cancel() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let path = dir.path().to_path_buf();
+		let memseq = Memseq::try_move_rocks(path).await?.with_building_time_ms(10_000);
+		let transaction = Transaction::new(vec![1], 0);
+		memseq.publish(transaction.clone()).await?;
+
+		let cancel = CancellationToken::new();
+		tokio::spawn({
+			let cancel = cancel.clone();
+			async move {
+				tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+				cancel.cancel();
+			}
+		});
+		let start = std::time::Instant::now();
+		let block = memseq.wait_for_next_block_with_cancel(cancel.clone()).await?;
+		assert!(start.elapsed() < std::time::Duration::from_secs(1));
+		assert_eq!(block.expect("gathered transactions").transactions, vec![transaction]);
+
+		// nothing gathered once cancelled
+		assert_eq!(memseq.wait_for_next_block_with_cancel(cancel).await?, None);
+
+		Ok(())
+	}
+
+	/// Captures the reports of the blocks built.
+	#[derive(Default)]
+	struct CapturingSink {
+		builds: std::sync::Mutex<Vec<(u32, bool)>>,
+	}
+
+	impl BlockBuildMetrics for CapturingSink {
+		fn record_build(&self, tx_count: u32, _duration: Duration, hit_time_limit: bool) {
+			self.builds.lock().unwrap().push((tx_count, hit_time_limit));
+		}
+	}
+
+	#[tokio::test]
+	async fn test_metrics_sink_records_build() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let path = dir.path().to_path_buf();
+		let sink = Arc::new(CapturingSink::default());
+		let memseq = Memseq::try_move_rocks(path)
+			.await?
+			.with_building_time_ms(50)
+			.with_metrics_sink(Arc::clone(&sink) as Arc<dyn BlockBuildMetrics>);
+
+		let transactions = (0..3).map(|i| Transaction::new(vec![i], 0)).collect();
+		memseq.publish_batch(transactions).await?;
+		let block = memseq.wait_for_next_block().await?.expect("block built");
+
+		// below block_size, building ran until the building time expired
+		assert_eq!(*sink.builds.lock().unwrap(), vec![(block.transactions.len() as u32, true)]);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_swap_mempool() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let memseq =
+			Memseq::try_move_rocks(dir.path().join("old")).await?.with_building_time_ms(10);
+		memseq.publish(Transaction::new(vec![6], 0)).await?;
+		let first = memseq.wait_for_next_block().await?.expect("block built before the swap");
+		let mut transactions: Vec<_> = (0..5).map(|i| Transaction::new(vec![i], 0)).collect();
+		transactions.sort();
+		for transaction in &transactions {
+			memseq.publish(transaction.clone()).await?;
+		}
+
+		let new_path = dir.path().join("new");
+		let new = RocksdbMempool::try_new(new_path.to_str().unwrap())?;
+		let new = Arc::new(RwLock::new(new));
+		memseq.swap_mempool(Arc::clone(&new)).await?;
+
+		// the previous backend was drained and handed back
+		assert!(new.read().await.pop_transaction().await?.is_none());
+		// the new backend continues the chain
+		assert_eq!(
+			memseq.mempool.read().await.get_parent_block().await?,
+			Some((first.height, first.id()))
+		);
+
+		let mut block = memseq.wait_for_next_block().await?.expect("block built after the swap");
+		block.transactions.sort();
+		assert_eq!(block.transactions, transactions);
+
+		memseq.publish(Transaction::new(vec![5], 0)).await?;
+		let block = memseq.wait_for_next_block().await?.expect("production resumed");
+		assert_eq!(block.transactions, vec![Transaction::new(vec![5], 0)]);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_shutdown_refuses_publish_and_drains() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let memseq = Memseq::try_move_rocks(dir.path().to_path_buf())
+			.await?
+			.with_building_time_ms(10);
+		for i in 0..25u8 {
+			memseq.publish(Transaction::new(vec![i], 0)).await?;
+		}
+
+		memseq.shutdown();
+		let err = memseq.publish(Transaction::new(vec![25], 0)).await.unwrap_err();
+		assert_eq!(err.downcast_ref::<ShuttingDown>(), Some(&ShuttingDown));
+		let err = memseq.publish_priority(Transaction::new(vec![26], 0)).await.unwrap_err();
+		assert_eq!(err.downcast_ref::<ShuttingDown>(), Some(&ShuttingDown));
+
+		let blocks = memseq.drain_into_blocks().await?;
+		let sizes: Vec<_> = blocks.iter().map(|block| block.transactions.len()).collect();
+		assert_eq!(sizes, vec![10, 10, 5]);
+		assert!(memseq.find_pending(|_| true, 100).await?.is_empty());
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_periodic_flush_persists_without_explicit_flush() -> Result<(), anyhow::Error> {
+		// reopening the database would replay the write-ahead log whether or not the mempool
+		// was flushed, only a flush writes table files
+		let table_files = |path: &std::path::Path| -> Result<usize, anyhow::Error> {
+			let mut count = 0;
+			for entry in std::fs::read_dir(path)? {
+				if entry?.path().extension().is_some_and(|extension| extension == "sst") {
+					count += 1;
+				}
+			}
+			Ok(count)
+		};
+		let dir = tempdir()?;
+		let path = dir.path().to_path_buf();
+		let interval = Duration::from_millis(20);
+		let memseq = Memseq::try_move_rocks(path.clone()).await?.with_periodic_flush(interval);
+		let transaction = Transaction::new(vec![1], 0);
+		memseq.publish(transaction.clone()).await?;
+		assert_eq!(table_files(&path)?, 0);
+
+		tokio::time::sleep(interval * 3).await;
+		assert!(table_files(&path)? > 0);
+		drop(memseq);
+		// let the flush task observe the cancellation and release the database
+		tokio::time::sleep(interval).await;
+
+		let mempool = RocksdbMempool::try_new(path.to_str().unwrap())?;
+		assert_eq!(mempool.pop_transaction().await?, Some(transaction));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_close_stops_periodic_flush() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let memseq = Memseq::try_move_rocks(dir.path().to_path_buf())
+			.await?
+			.with_periodic_flush(Duration::from_millis(20));
+		memseq.publish(Transaction::new(vec![1], 0)).await?;
+		memseq.close().await?;
+
+		let periodic_flush = memseq.periodic_flush.as_ref().unwrap();
+		assert!(periodic_flush.closed.load(Ordering::SeqCst));
+		assert!(periodic_flush.task.lock().unwrap().is_none());
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_rejected_transaction_becomes_dead_letter() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let poison = Transaction::new(vec![0], 0);
+		let accepted = Transaction::new(vec![1], 0);
+		let memseq = Memseq::try_move_rocks(dir.path().to_path_buf())
+			.await?
+			.with_building_time_ms(10)
+			.with_max_requeue_attempts(2)
+			.with_assembly_filter({
+				let poison = poison.clone();
+				Arc::new(move |transaction| *transaction != poison)
+			});
+		memseq.publish(poison.clone()).await?;
+		memseq.publish(accepted.clone()).await?;
+
+		let block = memseq.wait_for_next_block().await?.expect("accepted transaction");
+		assert_eq!(block.transactions, vec![accepted]);
+		// rejected along with the first block and once more, re-queued both times
+		assert_eq!(memseq.wait_for_next_block().await?, None);
+		assert!(memseq.dead_letters().await?.is_empty());
+		assert_eq!(memseq.find_pending(|_| true, 10).await?, vec![poison.clone()]);
+
+		// then given up on
+		assert_eq!(memseq.wait_for_next_block().await?, None);
+		assert_eq!(memseq.find_pending(|_| true, 10).await?, vec![]);
+		assert_eq!(memseq.dead_letters().await?, vec![poison]);
+		assert!(memseq.requeue_attempts.read().await.is_empty());
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_publish_error_propagation() -> Result<(), anyhow::Error> {
+		let mempool = Arc::new(RwLock::new(MockMempool));
+		let parent_block = Arc::new(RwLock::new(Id::default()));
+		let memseq = Memseq::new(mempool, 10, parent_block, 1000);
+
+		let transaction = Transaction::new(vec![1, 2, 3], 0);
+		let result = memseq.publish(transaction).await;
+		assert!(result.is_err());
+		assert_eq!(result.unwrap_err().to_string(), "Mock add_transaction");
+
+		let result = memseq.wait_for_next_block().await;
+		assert!(result.is_err());
+		assert_eq!(result.unwrap_err().to_string(), "Mock pop_transaction");
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_concurrent_access_spawn() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let path = dir.path().to_path_buf();
+		let memseq = Arc::new(Memseq::try_move_rocks(path).await?);
+
+		let mut handles = vec![];
+
+		for i in 0..100 {
+			let memseq_clone = Arc::clone(&memseq);
+			let handle = tokio::spawn(async move {
+				let transaction = Transaction::new(vec![i as u8], 0);
+				memseq_clone.publish(transaction).await.unwrap();
+			});
+			handles.push(handle);
+		}
+
+		for handle in handles {
+			handle.await.expect("Task failed");
+		}
+
+		Ok(())
+	}
+
+	#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+	async fn test_concurrent_publish_drops_nothing() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let path = dir.path().to_path_buf();
+		let memseq = Arc::new(Memseq::try_move_rocks(path).await?.with_block_size(1000));
+
+		let mut handles = vec![];
+		for task in 0..50u8 {
+			let memseq_clone = Arc::clone(&memseq);
+			handles.push(tokio::spawn(async move {
+				for i in 0..20u8 {
+					memseq_clone.publish(Transaction::new(vec![task, i], 0)).await?;
+				}
+				Ok::<_, anyhow::Error>(())
+			}));
+		}
+		for handle in handles {
+			handle.await??;
+		}
+
+		let included: std::collections::HashSet<_> = memseq
+			.drain_into_blocks()
+			.await?
+			.into_iter()
+			.flat_map(|block| block.transactions)
+			.map(|transaction| transaction.id())
+			.collect();
+		assert_eq!(included.len(), 50 * 20);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_publish_batch() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let path = dir.path().to_path_buf();
+		let memseq = Memseq::try_move_rocks(path).await?.with_block_size(100);
+
+		let transactions: Vec<_> =
+			(0..1000u64).map(|i| Transaction::new(i.to_le_bytes().to_vec(), i)).collect();
+		memseq.publish_batch(transactions.clone()).await?;
+		assert_eq!(memseq.metrics_snapshot().transactions_published, 1000);
+
+		let blocks = memseq.drain_into_blocks().await?;
+		assert_eq!(blocks.len(), 10);
+		let included: Vec<_> = blocks.into_iter().flat_map(|block| block.transactions).collect();
+		assert_eq!(included, transactions);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_publish_batch_reports_partial_insert() -> Result<(), anyhow::Error> {
+		let mempool = Arc::new(RwLock::new(MockMempool));
+		let parent_block = Arc::new(RwLock::new(Id::default()));
+		let memseq = Memseq::new(mempool, 10, parent_block, 1000);
+
+		let error = memseq.publish_batch(vec![Transaction::test(); 3]).await.unwrap_err();
+		let partial = error.downcast_ref::<PartialPublish>().expect("partial publish");
+		assert_eq!((partial.published, partial.total), (0, 3));
+		assert_eq!(partial.source.to_string(), "Mock add_transaction");
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_concurrent_access_futures() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let path = dir.path().to_path_buf();
+		let memseq = Arc::new(Memseq::try_move_rocks(path).await?);
+
+		let futures = FuturesUnordered::new();
+
+		for i in 0..10 {
+			let memseq_clone = Arc::clone(&memseq);
+			let handle = async move {
+				for n in 0..10 {
+					let transaction = Transaction::new(vec![i * 10 + n as u8], 0);
+					memseq_clone.publish(transaction).await?;
+				}
+				Ok::<_, anyhow::Error>(())
+			};
+			futures.push(handle);
+		}
+
+		let all_executed_correctly = futures.all(|result| async move { result.is_ok() }).await;
+		assert!(all_executed_correctly);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_try_move_rocks() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let path = dir.path().to_path_buf();
+		let memseq = Memseq::try_move_rocks(path.clone()).await?;
+
+		assert_eq!(memseq.block_size, 10);
+		assert_eq!(memseq.building_time_ms, 1000);
+
+		// Test invalid path
+		let invalid_path = PathBuf::from("");
+		let result = Memseq::try_move_rocks(invalid_path).await;
+		assert!(result.is_err());
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_memseq_initialization() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let path = dir.path().to_path_buf();
+
+		let mem_pool = Arc::new(RwLock::new(RocksdbMempool::try_new(
+			path.to_str().ok_or(anyhow::anyhow!("PathBuf to str failed"))?,
+		)?));
+		let block_size = 50;
+		let building_time_ms = 2000;
+		let parent_block = Arc::new(RwLock::new(Id::default()));
+
+		let memseq = Memseq::new(mem_pool, block_size, Arc::clone(&parent_block), building_time_ms);
+
+		assert_eq!(memseq.block_size, block_size);
+		assert_eq!(memseq.building_time_ms, building_time_ms);
+		assert_eq!(*memseq.parent_block.read().await, *parent_block.read().await);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_memseq_with_methods() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let path = dir.path().to_path_buf();
+
+		let mem_pool = Arc::new(RwLock::new(RocksdbMempool::try_new(
+			path.to_str().ok_or(anyhow::anyhow!("PathBuf to str failed"))?,
+		)?));
+		let block_size = 50;
+		let building_time_ms = 2000;
+		let parent_block = Arc::new(RwLock::new(Id::default()));
+
+		let memseq = Memseq::new(mem_pool, block_size, Arc::clone(&parent_block), building_time_ms);
+		assert_eq!(memseq.block_size(), block_size);
+		assert_eq!(memseq.building_time_ms(), building_time_ms);
+
+		// Test with_block_size
+		let new_block_size = 100;
+		let memseq = memseq.with_block_size(new_block_size);
+		assert_eq!(memseq.block_size, new_block_size);
+
+		// Test with_building_time_ms
+		let new_building_time_ms = 5000;
+		let memseq = memseq.with_building_time_ms(new_building_time_ms);
+		assert_eq!(memseq.building_time_ms, new_building_time_ms);
+
+		// Test with_poll_interval_ms
+		let memseq = memseq.with_poll_interval_ms(50);
+		assert_eq!(memseq.poll_interval_ms, 50);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_wait_for_next_block_no_transactions() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let path = dir.path().to_path_buf();
+		let memseq = Memseq::try_move_rocks(path)
+			.await?
+			.with_block_size(10)
+			.with_building_time_ms(500);
+
+		let block = memseq.wait_for_next_block().await?;
+		assert!(block.is_none());
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_memseq() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let path = dir.path().to_path_buf();
+		let memseq = Memseq::try_move_rocks(path).await?;
+
+		let transaction : Transaction = Transaction::new(vec![1, 2, 3], 0);
+		memseq.publish(transaction.clone()).await?;
+
+		let block = memseq.wait_for_next_block().await?;
+
+		assert_eq!(block.ok_or(anyhow::anyhow!("Block not found"))?.transactions[0], transaction);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_respects_size() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let path = dir.path().to_path_buf();
+		let block_size = 100;
+		let memseq = Memseq::try_move_rocks(path).await?.with_block_size(block_size);
+
+		let mut transactions = Vec::new();
+		for i in 0..block_size * 2 {
+			let transaction : Transaction = Transaction::new( vec![i as u8], 0);
+			memseq.publish(transaction.clone()).await?;
+			transactions.push(transaction);
+		}
+
+		let block = memseq.wait_for_next_block().await?;
+
+		assert!(block.is_some());
+
+		let block = block.ok_or(anyhow::anyhow!("Block not found"))?;
+
+		assert_eq!(block.transactions.len(), block_size as usize);
+
+		let second_block = memseq.wait_for_next_block().await?;
+
+		assert!(second_block.is_some());
+
+		let second_block = second_block.ok_or(anyhow::anyhow!("Second block not found"))?;
+
+		assert_eq!(second_block.transactions.len(), block_size as usize);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_wait_next_block_respects_time() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let path = dir.path().to_path_buf();
+		let block_size = 100;
+		let memseq = Memseq::try_move_rocks(path)
+			.await?
+			.with_block_size(block_size)
+			.with_building_time_ms(500);
+
+		let building_memseq = Arc::new(memseq);
+		let waiting_memseq = Arc::clone(&building_memseq);
+
+		let building_task = async move {
+			let memseq = building_memseq;
+
+			// add half of the transactions
+			for i in 0..block_size / 2 {
+				let transaction : Transaction = Transaction::new(vec![i as u8], 0);
+				memseq.publish(transaction.clone()).await?;
+			}
+
+			tokio::time::sleep(std::time::Duration::from_millis(600)).await;
+
+			// add the rest of the transactions
+			for i in block_size / 2..block_size - 2 {
+				let transaction : Transaction = Transaction::new(vec![i as u8], 0);
+				memseq.publish(transaction.clone()).await?;
+			}
+
+			Ok::<_, anyhow::Error>(())
+		};
+
+		let waiting_task = async move {
+			let memseq = waiting_memseq;
+
+			// first block
+			let block = memseq.wait_for_next_block().await?;
+			assert!(block.is_some());
+			let block = block.ok_or(anyhow::anyhow!("Block not found"))?;
+			assert_eq!(block.transactions.len(), (block_size / 2) as usize);
+
+			tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+			// second block
+			let block = memseq.wait_for_next_block().await?;
+			assert!(block.is_some());
+			let block = block.ok_or(anyhow::anyhow!("Block not found"))?;
+			assert_eq!(block.transactions.len(), ((block_size / 2) - 2) as usize);
+
+			Ok::<_, anyhow::Error>(())
+		};
+
+		tokio::try_join!(building_task, waiting_task)?;
+
+		Ok(())
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn test_poll_interval_respects_time() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let path = dir.path().to_path_buf();
+		let memseq = Arc::new(
+			Memseq::try_move_rocks(path)
+				.await?
+				.with_block_size(100)
+				.with_building_time_ms(120)
+				.with_poll_interval_ms(50),
+		);
+
+		memseq.publish(Transaction::new(vec![1], 0)).await?;
+		let waiting_memseq = Arc::clone(&memseq);
+		let waiting = tokio::spawn(async move {
+			let started = tokio::time::Instant::now();
+			let block = waiting_memseq.wait_for_next_block().await?;
+			Ok::<_, anyhow::Error>((block, started.elapsed()))
+		});
+		// let the block building start and take the first transaction
+		tokio::task::yield_now().await;
+
+		// arrives between two polls
+		tokio::time::advance(Duration::from_millis(30)).await;
+		memseq.publish(Transaction::new(vec![2], 0)).await?;
+
+		let (block, elapsed) = waiting.await??;
+		let block = block.ok_or(anyhow::anyhow!("Block not found"))?;
+		assert_eq!(block.transactions.len(), 2);
+		// polled at 50ms and 100ms, then the final sleep is clamped to the building time, the
+		// next poll would only be at 150ms
+		assert_eq!(elapsed, Duration::from_millis(120));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_max_block_bytes() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let memseq = Memseq::try_move_rocks(dir.path().to_path_buf())
+			.await?
+			.with_block_size(100)
+			.with_building_time_ms(50)
+			.with_max_block_bytes(2500);
+
+		// three fit under the byte cap, far fewer than block_size
+		for i in 0..5u8 {
+			memseq.publish(Transaction::new(vec![i; 800], 0)).await?;
+		}
+		let oversized = Transaction::new(vec![9; 3000], 0);
+		memseq.publish(oversized.clone()).await?;
+
+		let mut sizes = Vec::new();
+		while let Some(block) = memseq.wait_for_next_block().await? {
+			let bytes: u64 = block
+				.transactions
+				.iter()
+				.map(|transaction| transaction.size_bytes() as u64)
+				.sum();
+			assert!(bytes <= 2500, "block of {bytes} bytes");
+			sizes.push(block.transactions.len());
+		}
+		assert_eq!(sizes, vec![3, 2]);
+		assert_eq!(memseq.dead_letters().await?, vec![oversized]);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_blocks_follow_arrival_order() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let memseq = Memseq::try_move_rocks(dir.path().to_path_buf())
+			.await?
+			.with_block_size(4)
+			.with_building_time_ms(50);
+
+		let transactions: Vec<_> = [7, 3, 9, 1, 8, 2, 6, 0]
+			.into_iter()
+			.map(|sequence_number| Transaction::new(vec![sequence_number as u8], sequence_number))
+			.collect();
+		for transaction in &transactions {
+			memseq.publish(transaction.clone()).await?;
+		}
+
+		let mut included = Vec::new();
+		while let Some(block) = memseq.wait_for_next_block().await? {
+			included.extend(block.transactions);
+		}
+		assert_eq!(included, transactions);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_auto_advance_parent() -> Result<(), anyhow::Error> {
+		for auto_advance_parent in [true, false] {
+			let dir = tempdir()?;
+			let memseq = Memseq::try_move_rocks(dir.path().to_path_buf())
+				.await?
+				.with_block_size(1)
+				.with_auto_advance_parent(auto_advance_parent);
+			memseq.publish(Transaction::new(vec![1], 0)).await?;
+			memseq.publish(Transaction::new(vec![2], 0)).await?;
+
+			let first = memseq.wait_for_next_block().await?.expect("first block");
+			let second = memseq.wait_for_next_block().await?.expect("second block");
+			if auto_advance_parent {
+				assert_eq!(second.parent, first.id().to_vec());
+				assert_eq!(*memseq.parent_block.read().await, second.id());
+			} else {
+				assert_eq!(second.parent, first.parent);
+			}
+		}
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_metrics_snapshot_and_reset() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let memseq = Memseq::try_move_rocks(dir.path().to_path_buf())
+			.await?
+			.with_block_size(2)
+			.with_building_time_ms(20)
+			.with_max_requeue_attempts(0)
+			.with_assembly_filter(Arc::new(|transaction: &Transaction| transaction.data() != [0]));
+		assert_eq!(memseq.metrics_snapshot(), MemseqMetrics::default());
+
+		for i in 0..3u8 {
+			memseq.publish(Transaction::new(vec![i], 0)).await?;
+		}
+		memseq.publish_priority(Transaction::new(vec![3], 0)).await?;
+		memseq.drain_into_blocks().await?;
+
+		let snapshot = memseq.metrics_snapshot();
+		assert_eq!(
+			snapshot,
+			MemseqMetrics {
+				transactions_published: 4,
+				blocks_built: 2,
+				transactions_included: 3,
+				transactions_rejected: 1,
+				transactions_dead_lettered: 1,
+			}
+		);
+
+		memseq.reset_metrics();
+		assert_eq!(memseq.metrics_snapshot(), MemseqMetrics::default());
+		// the snapshot is a copy, unaffected by the reset
+		assert_eq!(snapshot.blocks_built, 2);
+
+		memseq.publish(Transaction::new(vec![4], 0)).await?;
+		assert_eq!(memseq.metrics_snapshot().transactions_published, 1);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_dedup_within_block() -> Result<(), anyhow::Error> {
+		for dedup in [true, false] {
+			let dir = tempdir()?;
+			let memseq = Memseq::try_move_rocks(dir.path().to_path_buf())
+				.await?
+				.with_building_time_ms(50)
+				.with_dedup(dedup);
+
+			// the same transaction inserted twice, in different slots
+			let transaction = Transaction::new(vec![1], 0);
+			for timestamp in [0, 2] {
+				let copy = MempoolTransaction::at_time(transaction.clone(), timestamp);
+				memseq.mempool.write().await.add_mempool_transaction(copy).await?;
+			}
+			memseq.publish(Transaction::new(vec![2], 0)).await?;
+
+			let block = memseq.wait_for_next_block().await?.expect("block built");
+			let copies = block.transactions.iter().filter(|included| **included == transaction);
+			assert_eq!(copies.count(), if dedup { 1 } else { 2 });
+			assert!(block.transactions.contains(&Transaction::new(vec![2], 0)));
+		}
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_publish_priority_leads_next_block() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let path = dir.path().to_path_buf();
+		let memseq = Memseq::try_move_rocks(path)
+			.await?
+			.with_block_size(10)
+			.with_building_time_ms(100);
+
+		for i in 0..5 {
+			let transaction = Transaction::new(vec![i as u8], 0);
+			memseq.publish(transaction).await?;
+		}
+
+		let priority = Transaction::new(vec![42], 7);
+		memseq.publish_priority(priority.clone()).await?;
+
+		let block = memseq.wait_for_next_block().await?;
+		let block = block.ok_or(anyhow::anyhow!("Block not found"))?;
+		assert_eq!(block.transactions.len(), 6);
+		assert_eq!(block.transactions[0], priority);
+
+		Ok(())
+	}
 
 	#[tokio::test]
-	async fn test_wait_for_next_block_building_time_expires() -> Result<(), anyhow::Error> {
+	async fn test_find_pending_filters_without_removing() -> Result<(), anyhow::Error> {
 		let dir = tempdir()?;
 		let path = dir.path().to_path_buf();
-		let memseq = Memseq::try_move_rocks(path)?.with_block_size(10).with_building_time_ms(500);
+		let memseq = Memseq::try_move_rocks(path)
+			.await?
+			.with_block_size(10)
+			.with_building_time_ms(100);
 
-		// Add some transactions
-		for i in 0..5 {
+		for i in 0..6 {
 			let transaction = Transaction::new(vec![i as u8], 0);
 			memseq.publish(transaction).await?;
 		}
 
-		// Wait for the block to be built, not enough transactions as such
-		// the building time should expire
-		let block = memseq.wait_for_next_block().await?;
-		assert!(block.is_some());
+		let even = memseq.find_pending(|transaction| transaction.data()[0] % 2 == 0, 10).await?;
+		assert_eq!(even.len(), 3);
+		assert!(even.iter().all(|transaction| transaction.data()[0] % 2 == 0));
+
+		let limited = memseq.find_pending(|_| true, 2).await?;
+		assert_eq!(limited.len(), 2);
 
+		// nothing was removed
+		let block = memseq.wait_for_next_block().await?;
 		let block = block.ok_or(anyhow::anyhow!("Block not found"))?;
-		assert_eq!(block.transactions.len(), 5);
+		assert_eq!(block.transactions.len(), 6);
 
 		Ok(())
 	}
 
 	#[tokio::test]
-	async fn test_publish_error_propagation() -> Result<(), anyhow::Error> {
-		let mempool = Arc::new(RwLock::new(MockMempool));
-		let parent_block = Arc::new(RwLock::new(Id::default()));
-		let memseq = Memseq::new(mempool, 10, parent_block, 1000);
+	async fn test_extra_provider_changes_block_id() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let path = dir.path().to_path_buf();
+		let counter = Arc::new(std::sync::atomic::AtomicU8::new(0));
+		let provider_counter = Arc::clone(&counter);
+		let memseq = Memseq::try_move_rocks(path)
+			.await?
+			.with_block_size(10)
+			.with_building_time_ms(100)
+			.with_extra_provider(Arc::new(move |_| {
+				vec![provider_counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst)]
+			}));
 
 		let transaction = Transaction::new(vec![1, 2, 3], 0);
-		let result = memseq.publish(transaction).await;
-		assert!(result.is_err());
-		assert_eq!(result.unwrap_err().to_string(), "Mock add_transaction");
 
-		let result = memseq.wait_for_next_block().await;
-		assert!(result.is_err());
-		assert_eq!(result.unwrap_err().to_string(), "Mock pop_transaction");
+		memseq.publish(transaction.clone()).await?;
+		let first = memseq.wait_for_next_block().await?;
+		let first = first.ok_or(anyhow::anyhow!("Block not found"))?;
+
+		memseq.publish(transaction.clone()).await?;
+		let second = memseq.wait_for_next_block().await?;
+		let second = second.ok_or(anyhow::anyhow!("Block not found"))?;
+
+		assert_eq!(first.transactions, second.transactions);
+		assert_eq!(first.extra, vec![0]);
+		assert_eq!(second.extra, vec![1]);
+		assert_ne!(first.id(), second.id());
 
 		Ok(())
 	}
 
 	#[tokio::test]
-	async fn test_concurrent_access_spawn() -> Result<(), anyhow::Error> {
+	async fn test_publish_priority_while_waiting_leads_block() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let memseq = Memseq::try_move_rocks(dir.path().to_path_buf())
+			.await?
+			.with_block_size(10)
+			.with_building_time_ms(500);
+		let regular: Vec<_> = (0..2u8).map(|i| Transaction::new(vec![i], 0)).collect();
+		memseq.publish_batch(regular.clone()).await?;
+
+		// published after the regular transactions were popped into the block
+		let priority = Transaction::new(vec![42], 7);
+		let (block, published) = tokio::join!(memseq.wait_for_next_block(), async {
+			tokio::time::sleep(Duration::from_millis(50)).await;
+			memseq.publish_priority(priority.clone()).await
+		});
+		published?;
+
+		let block = block?.expect("block");
+		assert_eq!(block.transactions, [vec![priority], regular].concat());
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_publish_many_with_progress_cancelled() -> Result<(), anyhow::Error> {
 		let dir = tempdir()?;
 		let path = dir.path().to_path_buf();
-		let memseq = Arc::new(Memseq::try_move_rocks(path)?);
+		let memseq = Memseq::try_move_rocks(path).await?;
+
+		let transactions: Vec<_> =
+			(0..1000u64).map(|i| Transaction::new(i.to_le_bytes().to_vec(), 0)).collect();
+
+		let cancel = CancellationToken::new();
+		let mut last_reported = None;
+		let committed = memseq
+			.publish_many_with_progress(transactions, cancel.clone(), |committed| {
+				last_reported = Some(committed);
+				if committed >= 2 * PUBLISH_PROGRESS_INTERVAL {
+					cancel.cancel();
+				}
+			})
+			.await?;
 
-		let mut handles = vec![];
+		assert_eq!(committed, 2 * PUBLISH_PROGRESS_INTERVAL);
+		assert_eq!(last_reported, Some(committed));
 
-		for i in 0..100 {
-			let memseq_clone = Arc::clone(&memseq);
-			let handle = tokio::spawn(async move {
-				let transaction = Transaction::new(vec![i as u8], 0);
-				memseq_clone.publish(transaction).await.unwrap();
-			});
-			handles.push(handle);
-		}
+		let pending = memseq.find_pending(|_| true, 1000).await?;
+		assert_eq!(pending.len(), committed);
 
-		for handle in handles {
-			handle.await.expect("Task failed");
-		}
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_publish_many_with_progress_releases_mempool() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let memseq = Memseq::try_move_rocks(dir.path().to_path_buf()).await?;
+		let transactions: Vec<_> = (0..3 * PUBLISH_PROGRESS_INTERVAL as u64)
+			.map(|i| Transaction::new(i.to_le_bytes().to_vec(), 0))
+			.collect();
+
+		let mut unlocked = Vec::new();
+		memseq
+			.publish_many_with_progress(transactions, CancellationToken::new(), |committed| {
+				// others can take the mempool between chunks
+				unlocked.push((committed, memseq.mempool.try_write().is_ok()));
+			})
+			.await?;
+
+		let expected: Vec<_> =
+			(1..=3).map(|chunk| (chunk * PUBLISH_PROGRESS_INTERVAL, true)).collect();
+		assert_eq!(unlocked, expected);
 
 		Ok(())
 	}
 
 	#[tokio::test]
-	async fn test_concurrent_access_futures() -> Result<(), anyhow::Error> {
+	async fn test_replace_transaction() -> Result<(), anyhow::Error> {
 		let dir = tempdir()?;
 		let path = dir.path().to_path_buf();
-		let memseq = Arc::new(Memseq::try_move_rocks(path)?);
+		let memseq = Memseq::try_move_rocks(path)
+			.await?
+			.with_block_size(10)
+			.with_building_time_ms(100);
 
-		let futures = FuturesUnordered::new();
+		let sender = Id([7; 32]);
+		let low_priority = Transaction::new(vec![1], 5).with_sender(sender.clone());
+		let high_priority = Transaction::new(vec![2], 5).with_sender(sender.clone());
+		let other = Transaction::new(vec![3], 6).with_sender(sender.clone());
 
-		for i in 0..10 {
-			let memseq_clone = Arc::clone(&memseq);
-			let handle = async move {
-				for n in 0..10 {
-					let transaction = Transaction::new(vec![i * 10 + n as u8], 0);
-					memseq_clone.publish(transaction).await?;
-				}
-				Ok::<_, anyhow::Error>(())
-			};
-			futures.push(handle);
-		}
+		memseq.publish(low_priority).await?;
+		memseq.publish(other.clone()).await?;
 
-		let all_executed_correctly = futures.all(|result| async move { result.is_ok() }).await;
-		assert!(all_executed_correctly);
+		assert!(memseq.replace_transaction(high_priority.clone()).await?);
+		let unrelated = Transaction::new(vec![4], 5).with_sender(Id([8; 32]));
+		assert!(!memseq.replace_transaction(unrelated.clone()).await?);
+
+		let block = memseq.wait_for_next_block().await?;
+		let block = block.ok_or(anyhow::anyhow!("Block not found"))?;
+		assert_eq!(block.transactions.len(), 3);
+		assert!(block.transactions.contains(&high_priority));
+		assert!(block.transactions.contains(&other));
+		assert!(block.transactions.contains(&unrelated));
 
 		Ok(())
 	}
 
 	#[tokio::test]
-	async fn test_try_move_rocks() -> Result<(), anyhow::Error> {
+	async fn test_min_block_size_waits_for_building_time() -> Result<(), anyhow::Error> {
 		let dir = tempdir()?;
 		let path = dir.path().to_path_buf();
-		let memseq = Memseq::try_move_rocks(path.clone())?;
+		let building_time = std::time::Duration::from_millis(300);
+		// the ratio alone would emit a block as soon as one transaction is drained
+		let memseq = Memseq::try_move_rocks(path)
+			.await?
+			.with_block_size(20)
+			.with_building_time_ms(building_time.as_millis() as u64)
+			.with_min_fill_ratio(0.0)
+			.with_min_block_size(10);
+
+		let transactions: Vec<_> = (0..3).map(|i| Transaction::new(vec![i], 0)).collect();
+		memseq.publish_batch(transactions.clone()).await?;
+		let start = std::time::Instant::now();
+		let block = memseq.wait_for_next_block().await?.expect("block after the building time");
+		assert!(start.elapsed() >= building_time);
+		assert_eq!(block.transactions, transactions);
 
-		assert_eq!(memseq.block_size, 10);
-		assert_eq!(memseq.building_time_ms, 1000);
+		Ok(())
+	}
 
-		// Test invalid path
-		let invalid_path = PathBuf::from("");
-		let result = Memseq::try_move_rocks(invalid_path);
-		assert!(result.is_err());
+	#[tokio::test]
+	async fn test_min_block_size_replaces_default_ratio() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let path = dir.path().to_path_buf();
+		let building_time = std::time::Duration::from_millis(1000);
+		let memseq = Memseq::try_move_rocks(path)
+			.await?
+			.with_block_size(20)
+			.with_building_time_ms(building_time.as_millis() as u64)
+			.with_min_block_size(5);
+
+		// above min_block_size, far below a full block: emitted without waiting for the timer
+		let transactions: Vec<_> = (0..6).map(|i| Transaction::new(vec![i], 0)).collect();
+		memseq.publish_batch(transactions.clone()).await?;
+		let start = std::time::Instant::now();
+		let block = memseq.wait_for_next_block().await?.expect("block before the building time");
+		assert!(start.elapsed() < building_time);
+		assert_eq!(block.transactions, transactions);
 
 		Ok(())
 	}
 
 	#[tokio::test]
-	async fn test_memseq_initialization() -> Result<(), anyhow::Error> {
+	async fn test_category_quotas() -> Result<(), anyhow::Error> {
 		let dir = tempdir()?;
 		let path = dir.path().to_path_buf();
+		let memseq = Memseq::try_move_rocks(path)
+			.await?
+			.with_block_size(10)
+			.with_building_time_ms(200)
+			.with_category_quotas(HashMap::from([(1, 0.2), (2, 0.5)]));
+
+		let category = |category: u8, count: u8| -> Vec<Transaction> {
+			(0..count)
+				.map(|i| Transaction::new(vec![i], 0).with_category(category))
+				.collect()
+		};
+		let (system, bulk, user) = (category(1, 5), category(2, 8), category(0, 3));
+		memseq
+			.publish_batch([system.clone(), bulk.clone(), user.clone()].concat())
+			.await?;
 
-		let mem_pool = Arc::new(RwLock::new(RocksdbMempool::try_new(
-			path.to_str().ok_or(anyhow::anyhow!("PathBuf to str failed"))?,
-		)?));
-		let block_size = 50;
-		let building_time_ms = 2000;
-		let parent_block = Arc::new(RwLock::new(Id::default()));
-
-		let memseq = Memseq::new(mem_pool, block_size, Arc::clone(&parent_block), building_time_ms);
+		// at most 2 and 5 of the categories with a quota, the default category fills the rest
+		let block = memseq.wait_for_next_block().await?.expect("first block");
+		assert_eq!(block.transactions, [&system[..2], &bulk[..5], &user[..]].concat());
 
-		assert_eq!(memseq.block_size, block_size);
-		assert_eq!(memseq.building_time_ms, building_time_ms);
-		assert_eq!(*memseq.parent_block.read().await, *parent_block.read().await);
+		// the held back transactions are back in the mempool, still subject to the quotas
+		let block = memseq.wait_for_next_block().await?.expect("second block");
+		assert_eq!(block.transactions, [&system[2..4], &bulk[5..]].concat());
+		assert_eq!(memseq.find_pending(|_| true, usize::MAX).await?, system[4..]);
+		assert_eq!(memseq.metrics_snapshot().transactions_rejected, 0);
 
 		Ok(())
 	}
 
+	struct NonEmptyValidator;
+
+	impl TransactionValidator for NonEmptyValidator {
+		fn validate(&self, tx: &Transaction) -> Result<(), ValidationError> {
+			if tx.data().is_empty() {
+				return Err(ValidationError::new("empty data"));
+			}
+			Ok(())
+		}
+	}
+
 	#[tokio::test]
-	async fn test_memseq_with_methods() -> Result<(), anyhow::Error> {
+	async fn test_validator_rejects_before_mempool() -> Result<(), anyhow::Error> {
 		let dir = tempdir()?;
 		let path = dir.path().to_path_buf();
+		let memseq =
+			Memseq::try_move_rocks(path).await?.with_validator(Arc::new(NonEmptyValidator));
 
-		let mem_pool = Arc::new(RwLock::new(RocksdbMempool::try_new(
-			path.to_str().ok_or(anyhow::anyhow!("PathBuf to str failed"))?,
-		)?));
-		let block_size = 50;
-		let building_time_ms = 2000;
-		let parent_block = Arc::new(RwLock::new(Id::default()));
-
-		let memseq = Memseq::new(mem_pool, block_size, Arc::clone(&parent_block), building_time_ms);
+		let empty = Transaction::new(vec![], 0);
+		let err = memseq.publish(empty.clone()).await.unwrap_err();
+		assert_eq!(
+			err.downcast_ref::<ValidationError>(),
+			Some(&ValidationError::new("empty data"))
+		);
 
-		// Test with_block_size
-		let new_block_size = 100;
-		let memseq = memseq.with_block_size(new_block_size);
-		assert_eq!(memseq.block_size, new_block_size);
+		let transaction = Transaction::new(vec![1], 0);
+		memseq.publish(transaction.clone()).await?;
+		// a batch with an invalid transaction is rejected as a whole
+		assert!(memseq.publish_batch(vec![Transaction::new(vec![2], 0), empty]).await.is_err());
 
-		// Test with_building_time_ms
-		let new_building_time_ms = 5000;
-		let memseq = memseq.with_building_time_ms(new_building_time_ms);
-		assert_eq!(memseq.building_time_ms, new_building_time_ms);
+		assert_eq!(memseq.find_pending(|_| true, usize::MAX).await?, vec![transaction]);
+		assert_eq!(memseq.metrics_snapshot().transactions_published, 1);
 
 		Ok(())
 	}
 
 	#[tokio::test]
-	async fn test_wait_for_next_block_no_transactions() -> Result<(), anyhow::Error> {
+	async fn test_max_mempool_size() -> Result<(), anyhow::Error> {
 		let dir = tempdir()?;
 		let path = dir.path().to_path_buf();
-		let memseq = Memseq::try_move_rocks(path)?.with_block_size(10).with_building_time_ms(500);
+		let memseq =
+			Memseq::try_move_rocks(path).await?.with_block_size(2).with_max_mempool_size(3);
 
-		let block = memseq.wait_for_next_block().await?;
-		assert!(block.is_none());
+		let transactions: Vec<_> = (0..6).map(|i| Transaction::new(vec![i], 0)).collect();
+		for transaction in &transactions[..3] {
+			memseq.publish(transaction.clone()).await?;
+		}
+		let err = memseq.publish(transactions[3].clone()).await.unwrap_err();
+		assert_eq!(err.downcast_ref::<MempoolFull>(), Some(&MempoolFull { max_mempool_size: 3 }));
+		assert_eq!(memseq.find_pending(|_| true, usize::MAX).await?, transactions[..3]);
+
+		// draining a block makes room again, a batch fills up to the limit
+		memseq.wait_for_next_block().await?.expect("block");
+		let err = memseq.publish_batch(transactions[3..].to_vec()).await.unwrap_err();
+		let partial = err.downcast::<PartialPublish>()?;
+		assert_eq!((partial.published, partial.total), (2, 3));
+		assert!(partial.source.is::<MempoolFull>());
 
 		Ok(())
 	}
 
 	#[tokio::test]
-	async fn test_memseq() -> Result<(), anyhow::Error> {
+	async fn test_max_mempool_size_bounds_every_publish() -> Result<(), anyhow::Error> {
 		let dir = tempdir()?;
 		let path = dir.path().to_path_buf();
-		let memseq = Memseq::try_move_rocks(path)?;
+		let memseq = Memseq::try_move_rocks(path).await?.with_max_mempool_size(2);
+		let is_full = |result: Result<_, anyhow::Error>| {
+			result.unwrap_err().downcast_ref::<MempoolFull>()
+				== Some(&MempoolFull { max_mempool_size: 2 })
+		};
 
-		let transaction : Transaction = Transaction::new(vec![1, 2, 3], 0);
-		memseq.publish(transaction.clone()).await?;
+		let transactions: Vec<_> = (0..2).map(|i| Transaction::new(vec![i], i as u64)).collect();
+		memseq.publish_batch(transactions.clone()).await?;
+
+		assert!(is_full(memseq.publish_priority(Transaction::new(vec![7], 7)).await));
+		assert!(is_full(memseq.publish_bundle(bundle_of(&[Transaction::new(vec![8], 8)])).await));
+		assert!(is_full(
+			memseq.replace_transaction(Transaction::new(vec![9], 9)).await.map(|_| ())
+		));
+		let many = vec![Transaction::new(vec![10], 10)];
+		assert!(is_full(
+			memseq
+				.publish_many_with_progress(many, CancellationToken::new(), |_| {})
+				.await
+				.map(|_| ())
+		));
+		assert_eq!(memseq.find_pending(|_| true, usize::MAX).await?, transactions);
+
+		// taking the place of a pending transaction needs no room
+		memseq.publish_priority(transactions[0].clone()).await?;
+		let replacement = Transaction::new(vec![11], 1);
+		assert!(memseq.replace_transaction(replacement.clone()).await?);
+		assert_eq!(
+			memseq.find_pending(|_| true, usize::MAX).await?,
+			[transactions[0].clone(), replacement]
+		);
+
+		Ok(())
+	}
 
+	#[tokio::test]
+	async fn test_min_fill_ratio_waits_for_building_time() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let path = dir.path().to_path_buf();
+		let building_time = std::time::Duration::from_millis(1000);
+		let memseq = Memseq::try_move_rocks(path)
+			.await?
+			.with_block_size(10)
+			.with_building_time_ms(building_time.as_millis() as u64)
+			.with_min_fill_ratio(0.8);
+
+		// half full, below the ratio: no block until the building time expires
+		for i in 0..5 {
+			memseq.publish(Transaction::new(vec![i as u8], 0)).await?;
+		}
+		let start = std::time::Instant::now();
 		let block = memseq.wait_for_next_block().await?;
+		assert!(start.elapsed() >= building_time);
+		let block = block.ok_or(anyhow::anyhow!("Block not found"))?;
+		assert_eq!(block.transactions.len(), 5);
 
-		assert_eq!(block.ok_or(anyhow::anyhow!("Block not found"))?.transactions[0], transaction);
+		// at the ratio: the block is emitted without waiting for the timer
+		for i in 0..8 {
+			memseq.publish(Transaction::new(vec![i as u8], 1)).await?;
+		}
+		let start = std::time::Instant::now();
+		let block = memseq.wait_for_next_block().await?;
+		assert!(start.elapsed() < building_time);
+		let block = block.ok_or(anyhow::anyhow!("Block not found"))?;
+		assert_eq!(block.transactions.len(), 8);
 
 		Ok(())
 	}
 
 	#[tokio::test]
-	async fn test_respects_size() -> Result<(), anyhow::Error> {
+	async fn test_block_commitment_from_built_block() -> Result<(), anyhow::Error> {
 		let dir = tempdir()?;
 		let path = dir.path().to_path_buf();
-		let block_size = 100;
-		let memseq = Memseq::try_move_rocks(path)?.with_block_size(block_size);
-
-		let mut transactions = Vec::new();
-		for i in 0..block_size * 2 {
-			let transaction : Transaction = Transaction::new( vec![i as u8], 0);
-			memseq.publish(transaction.clone()).await?;
-			transactions.push(transaction);
+		let memseq = Memseq::try_move_rocks(path)
+			.await?
+			.with_block_size(10)
+			.with_building_time_ms(100);
+		assert_eq!(memseq.height().await, 0);
+
+		let mut blocks = Vec::new();
+		for i in 0..2 {
+			memseq.publish(Transaction::new(vec![i], 0)).await?;
+			let block = memseq.wait_for_next_block().await?;
+			blocks.push(block.ok_or(anyhow::anyhow!("Block not found"))?);
 		}
+		assert_eq!(memseq.height().await, 2);
 
-		let block = memseq.wait_for_next_block().await?;
+		let block_commitment = memseq.block_commitment(&blocks[1], Commitment::test()).await?;
+		assert_eq!(
+			block_commitment,
+			BlockCommitment { height: 2, block_id: blocks[1].id(), commitment: Commitment::test() }
+		);
 
-		assert!(block.is_some());
+		// only the last built block has an authoritative height
+		assert!(memseq.block_commitment(&blocks[0], Commitment::test()).await.is_err());
 
-		let block = block.ok_or(anyhow::anyhow!("Block not found"))?;
+		Ok(())
+	}
 
-		assert_eq!(block.transactions.len(), block_size as usize);
+	/// Commits to the number of transactions of the block.
+	struct CountingCommitmentProvider;
 
-		let second_block = memseq.wait_for_next_block().await?;
+	#[async_trait::async_trait]
+	impl CommitmentProvider for CountingCommitmentProvider {
+		async fn commitment_for(&self, block: &Block) -> Result<Commitment, anyhow::Error> {
+			Ok(Commitment([block.transactions.len() as u8; 32]))
+		}
+	}
 
-		assert!(second_block.is_some());
+	#[tokio::test]
+	async fn test_produce_commitment_with_custom_provider() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let memseq = Memseq::try_move_rocks(dir.path().to_path_buf())
+			.await?
+			.with_building_time_ms(10)
+			.with_commitment_provider(Arc::new(CountingCommitmentProvider));
 
-		let second_block = second_block.ok_or(anyhow::anyhow!("Second block not found"))?;
+		let transactions = (0..3).map(|i| Transaction::new(vec![i], 0)).collect();
+		memseq.publish_batch(transactions).await?;
+		let block = memseq.wait_for_next_block().await?.expect("block built");
 
-		assert_eq!(second_block.transactions.len(), block_size as usize);
+		let block_commitment = memseq.produce_commitment(&block).await?;
+		assert_eq!(
+			block_commitment,
+			BlockCommitment { height: 1, block_id: block.id(), commitment: Commitment([3; 32]) }
+		);
 
 		Ok(())
 	}
 
 	#[tokio::test]
-	async fn test_wait_next_block_respects_time() -> Result<(), anyhow::Error> {
+	async fn test_bundle_carries_sequencer_id() -> Result<(), anyhow::Error> {
 		let dir = tempdir()?;
-		let path = dir.path().to_path_buf();
-		let block_size = 100;
-		let memseq = Memseq::try_move_rocks(path)?
-			.with_block_size(block_size)
-			.with_building_time_ms(500);
+		let memseq = Memseq::try_move_rocks(dir.path().to_path_buf())
+			.await?
+			.with_sequencer_id(Id::from_name("sequencer-a"));
+		let transaction = Transaction::new(vec![1], 0);
 
-		let building_memseq = Arc::new(memseq);
-		let waiting_memseq = Arc::clone(&building_memseq);
+		let bundle = memseq.bundle([transaction.clone()]).await;
+		assert_eq!(bundle.sequencer_id, Id::from_name("sequencer-a"));
+		assert_eq!(bundle.parent_height, Some(0));
+		assert_eq!(Transaction::try_from(bundle)?, transaction);
 
-		let building_task = async move {
-			let memseq = building_memseq;
+		// and proposes the blocks it builds
+		memseq.publish(transaction).await?;
+		let block = memseq.wait_for_next_block().await?.expect("block");
+		assert_eq!(block.metadata.proposer, Id::from_name("sequencer-a"));
 
-			// add half of the transactions
-			for i in 0..block_size / 2 {
-				let transaction : Transaction = Transaction::new(vec![i as u8], 0);
-				memseq.publish(transaction.clone()).await?;
-			}
+		Ok(())
+	}
 
-			tokio::time::sleep(std::time::Duration::from_millis(600)).await;
+	#[tokio::test]
+	async fn test_oldest_pending_age_ms() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let memseq = Memseq::try_move_rocks(dir.path().to_path_buf()).await?;
+		assert_eq!(memseq.oldest_pending_age_ms().await?, None);
 
-			// add the rest of the transactions
-			for i in block_size / 2..block_size - 2 {
-				let transaction : Transaction = Transaction::new(vec![i as u8], 0);
-				memseq.publish(transaction.clone()).await?;
+		let published = std::time::Instant::now();
+		memseq.publish(Transaction::new(vec![1], 0)).await?;
+		tokio::time::sleep(Duration::from_millis(50)).await;
+		memseq.publish(Transaction::new(vec![2], 0)).await?;
+
+		let age = memseq.oldest_pending_age_ms().await?.expect("pending transactions");
+		assert!(age >= 50, "age {age}");
+		assert!(u128::from(age) <= published.elapsed().as_millis() + 1, "age {age}");
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_recent_fill_ratios() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let memseq = Memseq::try_move_rocks(dir.path().to_path_buf())
+			.await?
+			.with_block_size(4)
+			.with_building_time_ms(20);
+		assert!(memseq.recent_fill_ratios(10).is_empty());
+
+		let mut data = 0u8;
+		for size in [4, 2, 1] {
+			for _ in 0..size {
+				memseq.publish(Transaction::new(vec![data], 0)).await?;
+				data += 1;
 			}
+			let block = memseq.wait_for_next_block().await?.expect("block built");
+			assert_eq!(block.transactions.len(), size);
+		}
+		// no block, nothing recorded
+		assert_eq!(memseq.wait_for_next_block().await?, None);
 
-			Ok::<_, anyhow::Error>(())
-		};
+		assert_eq!(memseq.recent_fill_ratios(10), vec![1.0, 0.5, 0.25]);
+		assert_eq!(memseq.recent_fill_ratios(2), vec![0.5, 0.25]);
+		assert!(memseq.recent_fill_ratios(0).is_empty());
 
-		let waiting_task = async move {
-			let memseq = waiting_memseq;
+		Ok(())
+	}
 
-			// first block
-			let block = memseq.wait_for_next_block().await?;
-			assert!(block.is_some());
-			let block = block.ok_or(anyhow::anyhow!("Block not found"))?;
-			assert_eq!(block.transactions.len(), (block_size / 2) as usize);
+	#[tokio::test]
+	async fn test_soft_block_size_keeps_groups_together() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let path = dir.path().to_path_buf();
+		let memseq = Memseq::try_move_rocks(path)
+			.await?
+			.with_block_size(3)
+			.with_building_time_ms(100)
+			.with_soft_block_size(true);
+
+		for i in 0..2 {
+			memseq.publish(Transaction::new(vec![i], 0)).await?;
+		}
+		// the group straddles the block size boundary
+		let group: Vec<_> = (1..=3).map(|i| Transaction::new(vec![10 + i as u8], i)).collect();
+		memseq
+			.publish_bundle(AtomicTransactionBundle {
+				transactions: group
+					.iter()
+					.map(|transaction| movement_types::TransactionEntry {
+						consumer_id: Id::default(),
+						data: transaction.clone(),
+					})
+					.collect(),
+				..Default::default()
+			})
+			.await?;
 
-			tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+		let block = memseq.wait_for_next_block().await?;
+		let block = block.ok_or(anyhow::anyhow!("Block not found"))?;
+		assert_eq!(block.transactions.len(), 5);
+		for transaction in &group {
+			assert!(block.transactions.contains(transaction));
+		}
 
-			// second block
-			let block = memseq.wait_for_next_block().await?;
-			assert!(block.is_some());
-			let block = block.ok_or(anyhow::anyhow!("Block not found"))?;
-			assert_eq!(block.transactions.len(), ((block_size / 2) - 2) as usize);
+		let block = memseq.wait_for_next_block().await?;
+		assert!(block.is_none());
 
-			Ok::<_, anyhow::Error>(())
-		};
+		Ok(())
+	}
 
-		tokio::try_join!(building_task, waiting_task)?;
+	fn bundle_of(transactions: &[Transaction]) -> AtomicTransactionBundle {
+		AtomicTransactionBundle {
+			transactions: transactions
+				.iter()
+				.map(|transaction| movement_types::TransactionEntry {
+					consumer_id: Id::default(),
+					data: transaction.clone(),
+				})
+				.collect(),
+			..Default::default()
+		}
+	}
+
+	#[tokio::test]
+	async fn test_groups_are_held_back_and_rejected_whole() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let memseq = Memseq::try_move_rocks(dir.path().to_path_buf())
+			.await?
+			.with_block_size(4)
+			.with_building_time_ms(50)
+			.with_soft_block_size(true)
+			.with_category_quotas(HashMap::from([(1, 0.25)]));
+
+		// the first member of the group is over the quota of its category
+		let system = Transaction::new(vec![0], 0).with_category(1);
+		let group = vec![
+			Transaction::new(vec![1], 1).with_category(1),
+			Transaction::new(vec![2], 2),
+			Transaction::new(vec![3], 3),
+		];
+		memseq.publish(system.clone()).await?;
+		memseq.publish_bundle(bundle_of(&group)).await?;
+
+		let block = memseq.wait_for_next_block().await?.expect("block");
+		assert_eq!(block.transactions, vec![system]);
+		let block = memseq.wait_for_next_block().await?.expect("block");
+		assert_eq!(block.transactions, group);
+
+		// the first member of the group is rejected once by the assembly filter
+		let dir = tempdir()?;
+		let rejected_once = Arc::new(AtomicBool::new(false));
+		let memseq = Memseq::try_move_rocks(dir.path().to_path_buf())
+			.await?
+			.with_block_size(4)
+			.with_building_time_ms(50)
+			.with_soft_block_size(true)
+			.with_assembly_filter({
+				let rejected_once = rejected_once.clone();
+				Arc::new(move |transaction| {
+					transaction.data() != [1] || rejected_once.swap(true, Ordering::SeqCst)
+				})
+			});
+		memseq.publish_bundle(bundle_of(&group)).await?;
+
+		assert_eq!(memseq.wait_for_next_block().await?, None);
+		assert_eq!(memseq.metrics_snapshot().transactions_rejected, 3);
+		let block = memseq.wait_for_next_block().await?.expect("block");
+		assert_eq!(block.transactions, group);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_groups_fit_max_block_bytes_whole() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		// 18 bytes per transaction, see `Transaction::size_bytes`
+		let memseq = Memseq::try_move_rocks(dir.path().to_path_buf())
+			.await?
+			.with_block_size(2)
+			.with_building_time_ms(50)
+			.with_soft_block_size(true)
+			.with_max_block_bytes(60);
+
+		let single = Transaction::new(vec![0; 10], 0);
+		let group: Vec<_> = (1..=3).map(|i| Transaction::new(vec![i; 10], i as u64)).collect();
+		memseq.publish(single.clone()).await?;
+		memseq.publish_bundle(bundle_of(&group)).await?;
+
+		// the complete group does not fit next to the single transaction, it is left in place
+		let block = memseq.wait_for_next_block().await?.expect("block");
+		assert_eq!(block.transactions, vec![single]);
+		let block = memseq.wait_for_next_block().await?.expect("block");
+		assert_eq!(block.transactions, group);
+
+		// a group that can never fit is given up on whole
+		let oversized: Vec<_> = (4..=7).map(|i| Transaction::new(vec![i; 10], i as u64)).collect();
+		memseq.publish_bundle(bundle_of(&oversized)).await?;
+		assert_eq!(memseq.wait_for_next_block().await?, None);
+		let mut dead_letters = memseq.dead_letters().await?;
+		dead_letters.sort_by_key(|transaction| transaction.sequence_number);
+		assert_eq!(dead_letters, oversized);
+		assert_eq!(memseq.find_pending(|_| true, 10).await?, vec![]);
 
 		Ok(())
 	}
@@ -420,6 +3193,29 @@ pub mod test {
 			Err(anyhow::anyhow!("Mock get_mempool_transaction"))
 		}
 
+		async fn get_mempool_transaction_by_sender_sequence(
+			&self,
+			_sender: Id,
+			_sequence_number: u64,
+		) -> Result<Option<MempoolTransaction>, anyhow::Error> {
+			Err(anyhow::anyhow!("Mock get_mempool_transaction_by_sender_sequence"))
+		}
+
+		async fn find_mempool_transactions<F>(
+			&self,
+			_predicate: F,
+			_limit: usize,
+		) -> Result<Vec<MempoolTransaction>, anyhow::Error>
+		where
+			F: Fn(&MempoolTransaction) -> bool,
+		{
+			Err(anyhow::anyhow!("Mock find_mempool_transactions"))
+		}
+
+		async fn size(&self) -> Result<u64, anyhow::Error> {
+			Err(anyhow::anyhow!("Mock size"))
+		}
+
 		async fn add_transaction(&self, _transaction: Transaction) -> Result<(), anyhow::Error> {
 			Err(anyhow::anyhow!("Mock add_transaction"))
 		}
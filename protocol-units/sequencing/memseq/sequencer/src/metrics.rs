@@ -0,0 +1,84 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Point-in-time copy of the counters of a [`Memseq`](crate::Memseq), see
+/// [`Memseq::metrics_snapshot`](crate::Memseq::metrics_snapshot).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemseqMetrics {
+	/// Transactions accepted into the mempool by the publish methods.
+	pub transactions_published: u64,
+	pub blocks_built: u64,
+	/// Transactions included in the blocks built.
+	pub transactions_included: u64,
+	/// Transactions rejected by the assembly filter, counted once per rejection.
+	pub transactions_rejected: u64,
+	/// Transactions moved to the dead letters.
+	pub transactions_dead_lettered: u64,
+}
+
+/// Counters behind [`MemseqMetrics`], shared by the clones of a sequencer.
+#[derive(Debug, Default)]
+pub(crate) struct Metrics {
+	transactions_published: AtomicU64,
+	blocks_built: AtomicU64,
+	transactions_included: AtomicU64,
+	transactions_rejected: AtomicU64,
+	transactions_dead_lettered: AtomicU64,
+}
+
+impl Metrics {
+	pub(crate) fn record_published(&self, count: u64) {
+		self.transactions_published.fetch_add(count, Ordering::Relaxed);
+	}
+
+	pub(crate) fn record_block(&self, transactions: u64) {
+		self.blocks_built.fetch_add(1, Ordering::Relaxed);
+		self.transactions_included.fetch_add(transactions, Ordering::Relaxed);
+	}
+
+	pub(crate) fn record_rejected(&self, count: u64) {
+		self.transactions_rejected.fetch_add(count, Ordering::Relaxed);
+	}
+
+	pub(crate) fn record_dead_lettered(&self) {
+		self.transactions_dead_lettered.fetch_add(1, Ordering::Relaxed);
+	}
+
+	/// Reads each counter once. The counters are independent atomics, so an operation
+	/// running concurrently may be reflected in some counters of the snapshot but not yet in
+	/// others, e.g. a block counted in `blocks_built` but not in `transactions_included`.
+	pub(crate) fn snapshot(&self) -> MemseqMetrics {
+		MemseqMetrics {
+			transactions_published: self.transactions_published.load(Ordering::Relaxed),
+			blocks_built: self.blocks_built.load(Ordering::Relaxed),
+			transactions_included: self.transactions_included.load(Ordering::Relaxed),
+			transactions_rejected: self.transactions_rejected.load(Ordering::Relaxed),
+			transactions_dead_lettered: self.transactions_dead_lettered.load(Ordering::Relaxed),
+		}
+	}
+
+	pub(crate) fn reset(&self) {
+		self.transactions_published.store(0, Ordering::Relaxed);
+		self.blocks_built.store(0, Ordering::Relaxed);
+		self.transactions_included.store(0, Ordering::Relaxed);
+		self.transactions_rejected.store(0, Ordering::Relaxed);
+		self.transactions_dead_lettered.store(0, Ordering::Relaxed);
+	}
+}
+
+/// Receives a report of each block built, see
+/// [`Memseq::with_metrics_sink`](crate::Memseq::with_metrics_sink).
+pub trait BlockBuildMetrics: Send + Sync {
+	/// Called once per block built, right before it is returned, with its number of
+	/// transactions, the time spent building it, and whether building stopped because the
+	/// building time expired rather than because the block filled up.
+	fn record_build(&self, tx_count: u32, duration: Duration, hit_time_limit: bool);
+}
+
+/// Discards the reports of the blocks built.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopBlockBuildMetrics;
+
+impl BlockBuildMetrics for NoopBlockBuildMetrics {
+	fn record_build(&self, _tx_count: u32, _duration: Duration, _hit_time_limit: bool) {}
+}
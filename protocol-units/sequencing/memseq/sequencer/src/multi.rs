@@ -0,0 +1,139 @@
+use crate::{Block, Id, Memseq, RocksdbMempool, Sequencer, Transaction};
+use mempool_util::{MempoolBlockOperations, MempoolTransactionOperations};
+use std::collections::HashMap;
+use std::{path::PathBuf, sync::Arc};
+use thiserror::Error;
+use tokio::sync::RwLock;
+
+/// Error returned for a chain that the [`MemseqMulti`] does not manage.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Memseq does not manage chain {0:?}")]
+pub struct UnknownChain(pub String);
+
+/// Block production settings of one chain of a [`MemseqMulti`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainConfig {
+	pub block_size: u32,
+	pub building_time_ms: u64,
+}
+
+impl Default for ChainConfig {
+	fn default() -> Self {
+		// same as Memseq::try_move_rocks
+		ChainConfig { block_size: 10, building_time_ms: 1000 }
+	}
+}
+
+/// Sequencers of several independent chains in one process, keyed by chain id. Each chain
+/// has its own mempool, block production settings and parent block.
+pub struct MemseqMulti<T: MempoolBlockOperations + MempoolTransactionOperations> {
+	chains: HashMap<String, Memseq<T>>,
+}
+
+impl<T: MempoolBlockOperations + MempoolTransactionOperations> MemseqMulti<T> {
+	pub fn new(chains: HashMap<String, Memseq<T>>) -> Self {
+		Self { chains }
+	}
+
+	pub fn chain(&self, chain_id: &str) -> Result<&Memseq<T>, UnknownChain> {
+		self.chains.get(chain_id).ok_or_else(|| UnknownChain(chain_id.to_string()))
+	}
+
+	pub fn chain_ids(&self) -> impl Iterator<Item = &str> {
+		self.chains.keys().map(String::as_str)
+	}
+
+	pub async fn publish(
+		&self,
+		chain_id: &str,
+		transaction: Transaction,
+	) -> Result<(), anyhow::Error> {
+		self.chain(chain_id)?.publish(transaction).await
+	}
+
+	pub async fn wait_for_next_block(
+		&self,
+		chain_id: &str,
+	) -> Result<Option<Block>, anyhow::Error> {
+		self.chain(chain_id)?.wait_for_next_block().await
+	}
+}
+
+impl MemseqMulti<RocksdbMempool> {
+	/// Opens the chains in a single RocksDB database, so that they share one instance and its
+	/// file handles. Each chain stores its data in its own column families, its persisted chain
+	/// included.
+	pub async fn try_move_rocks(
+		path: PathBuf,
+		chains: &[(&str, ChainConfig)],
+	) -> Result<Self, anyhow::Error> {
+		let chain_ids: Vec<_> = chains.iter().map(|(chain_id, _)| *chain_id).collect();
+		let mut mempools = RocksdbMempool::try_new_chains(
+			path.to_str().ok_or(anyhow::anyhow!("PathBuf to str failed"))?,
+			&chain_ids,
+		)?;
+		let mut memseqs = HashMap::new();
+		for (chain_id, config) in chains {
+			let mempool = mempools
+				.remove(*chain_id)
+				.ok_or_else(|| anyhow::anyhow!("Duplicate chain {chain_id:?}"))?;
+			// restored like the chain of a single-chain sequencer
+			let memseq = Memseq::new(
+				Arc::new(RwLock::new(mempool)),
+				config.block_size,
+				Arc::new(RwLock::new(Id::default())),
+				config.building_time_ms,
+			)
+			.with_persisted_chain()
+			.await?;
+			memseqs.insert((*chain_id).to_string(), memseq);
+		}
+		Ok(Self::new(memseqs))
+	}
+}
+
+#[cfg(test)]
+pub mod test {
+
+	use super::*;
+	use tempfile::tempdir;
+
+	#[tokio::test]
+	async fn test_chains_are_isolated() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let multi = MemseqMulti::try_move_rocks(
+			dir.path().to_path_buf(),
+			&[
+				("a", ChainConfig { block_size: 2, building_time_ms: 10 }),
+				("b", ChainConfig { block_size: 5, building_time_ms: 20 }),
+			],
+		)
+		.await?;
+
+		let a: Vec<_> = (0..4u8).map(|i| Transaction::new(vec![i], 0)).collect();
+		let b: Vec<_> = (4..7u8).map(|i| Transaction::new(vec![i], 0)).collect();
+		for transaction in &a {
+			multi.publish("a", transaction.clone()).await?;
+		}
+		for transaction in &b {
+			multi.publish("b", transaction.clone()).await?;
+		}
+
+		let block = multi.wait_for_next_block("b").await?.expect("block on chain b");
+		assert_eq!(block.transactions.len(), 3);
+		assert!(block.transactions.iter().all(|transaction| b.contains(transaction)));
+		assert_eq!(multi.wait_for_next_block("b").await?, None);
+
+		for _ in 0..2 {
+			let block = multi.wait_for_next_block("a").await?.expect("block on chain a");
+			assert_eq!(block.transactions.len(), 2);
+			assert!(block.transactions.iter().all(|transaction| a.contains(transaction)));
+		}
+		assert_eq!(multi.wait_for_next_block("a").await?, None);
+
+		let err = multi.publish("c", Transaction::new(vec![7], 0)).await.unwrap_err();
+		assert_eq!(err.downcast_ref::<UnknownChain>(), Some(&UnknownChain("c".to_string())));
+
+		Ok(())
+	}
+}
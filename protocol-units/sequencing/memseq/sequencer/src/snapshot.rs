@@ -0,0 +1,157 @@
+use crate::{Block, Id, Memseq, Transaction};
+use mempool_util::{MempoolBlockOperations, MempoolTransaction, MempoolTransactionOperations};
+use std::collections::VecDeque;
+
+/// Copy of the pending transactions of a [`Memseq`] taken at one point in time, to build
+/// candidate blocks from without touching the live mempool, see [`Memseq::snapshot`].
+///
+/// Cloning a snapshot lets alternative candidates be built from the same state.
+#[derive(Debug, Clone)]
+pub struct MempoolSnapshot {
+	// in pop order
+	pending: VecDeque<MempoolTransaction>,
+	// taken by the candidate blocks built so far
+	consumed: Vec<Id>,
+	// ids of the candidate blocks built so far
+	candidates: Vec<Id>,
+	// parent and height of the next candidate block
+	parent: Id,
+	height: u64,
+}
+
+impl MempoolSnapshot {
+	/// Number of transactions left to build blocks from.
+	pub fn pending_len(&self) -> usize {
+		self.pending.len()
+	}
+
+	/// Ids of the transactions included in the candidate blocks built so far.
+	pub fn consumed(&self) -> &[Id] {
+		&self.consumed
+	}
+}
+
+impl<T: MempoolBlockOperations + MempoolTransactionOperations> Memseq<T> {
+	/// Copies the pending transactions, in pop order, the current parent block and height.
+	pub async fn snapshot(&self) -> Result<MempoolSnapshot, anyhow::Error> {
+		let mempool = self.mempool.read().await;
+		let pending = mempool.find_mempool_transactions(|_| true, usize::MAX).await?;
+		Ok(MempoolSnapshot {
+			pending: pending.into(),
+			consumed: Vec::new(),
+			candidates: Vec::new(),
+			parent: self.parent_block.read().await.clone(),
+			height: self.height().await + 1,
+		})
+	}
+
+	/// Builds a candidate block of up to `block_size` transactions popped from the snapshot,
+	/// or `None` if the snapshot is exhausted. Consecutive candidates chain off each other.
+	///
+	/// Candidates carry the height, proposer and timestamp of the blocks the sequencer builds.
+	/// The live mempool and the sequencer state are left untouched until a candidate is
+	/// committed with [`Memseq::commit_snapshot`]. Only `block_size` and the extra provider
+	/// apply; the other block assembly settings of the sequencer do not.
+	pub fn build_block_from_snapshot(
+		&self,
+		snap: &mut MempoolSnapshot,
+	) -> Result<Option<Block>, anyhow::Error> {
+		let count = snap.pending.len().min(self.block_size as usize);
+		if count == 0 {
+			return Ok(None);
+		}
+		let transactions: Vec<Transaction> = snap
+			.pending
+			.drain(..count)
+			.map(|mempool_transaction| mempool_transaction.transaction)
+			.collect();
+		snap.consumed.extend(transactions.iter().map(|transaction| transaction.id()));
+
+		let block = self.assemble_block(transactions, snap.parent.clone(), snap.height)?;
+		snap.candidates.push(block.id());
+		snap.parent = block.id();
+		snap.height += 1;
+		Ok(Some(block))
+	}
+
+	/// Commits the chosen candidate of the snapshot as the next block of the sequencer: its
+	/// transactions, and only its, are removed from the live mempool, and it becomes the last
+	/// block built as if built by [`Memseq::wait_for_next_block`]. Fails unless the candidate
+	/// was built from the snapshot and extends the current chain, e.g. when another block was
+	/// built since the snapshot was taken. Chained candidates are committed one by one.
+	pub async fn commit_snapshot(
+		&self,
+		snap: &MempoolSnapshot,
+		candidate: &Block,
+	) -> Result<(), anyhow::Error> {
+		anyhow::ensure!(
+			snap.candidates.contains(&candidate.id()),
+			"Block {} is not a candidate of the snapshot",
+			candidate.id()
+		);
+		let mempool = self.mempool.write().await;
+		let mut last_block = self.last_block.write().await;
+		let height = last_block.as_ref().map_or(0, |(height, _)| *height) + 1;
+		let parent = self.parent_block.read().await.clone();
+		anyhow::ensure!(
+			candidate.height == height && candidate.parent == parent.to_vec(),
+			"Candidate block {} does not extend the current chain",
+			candidate.id()
+		);
+		for transaction in &candidate.transactions {
+			mempool.remove_mempool_transaction(transaction.id()).await?;
+		}
+		self.advance_chain(&mempool, &mut last_block, candidate).await?;
+		self.update_pending_count(&mempool).await;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+pub mod test {
+
+	use super::*;
+	use crate::{Sequencer, Transaction};
+	use tempfile::tempdir;
+
+	#[tokio::test]
+	async fn test_candidate_blocks_from_snapshot() -> Result<(), anyhow::Error> {
+		let dir = tempdir()?;
+		let memseq = Memseq::try_move_rocks(dir.path().to_path_buf()).await?.with_block_size(2);
+		let transactions: Vec<_> = (0..5u8).map(|i| Transaction::new(vec![i], 0)).collect();
+		memseq.publish_batch(transactions.clone()).await?;
+
+		let mut snap = memseq.snapshot().await?;
+		let mut alternative = snap.clone();
+		let first = memseq.build_block_from_snapshot(&mut snap)?.expect("first candidate");
+		let second = memseq.build_block_from_snapshot(&mut snap)?.expect("second candidate");
+		assert_eq!(first.transactions, transactions[..2]);
+		assert_eq!(second.transactions, transactions[2..4]);
+		assert_eq!(second.parent, first.id().to_vec());
+		assert_eq!((first.height, second.height), (1, 2));
+
+		// the live mempool is untouched and the same state yields an alternative candidate
+		assert_eq!(memseq.find_pending(|_| true, usize::MAX).await?, transactions);
+		let other = memseq.build_block_from_snapshot(&mut alternative)?.expect("alternative");
+		assert_eq!(other.transactions, first.transactions);
+		assert_eq!(other.parent, first.parent);
+		assert_eq!(memseq.height().await, 0);
+
+		// a chained candidate cannot be committed before its parent
+		assert!(memseq.commit_snapshot(&snap, &second).await.is_err());
+
+		// committing a candidate consumes only its transactions and advances the chain
+		memseq.commit_snapshot(&snap, &first).await?;
+		assert_eq!(memseq.find_pending(|_| true, usize::MAX).await?, transactions[2..]);
+		assert_eq!(memseq.height().await, 1);
+		assert_eq!(memseq.current_parent().await, first.id());
+
+		// the alternative no longer extends the chain, the chained candidate now does
+		assert!(memseq.commit_snapshot(&alternative, &other).await.is_err());
+		memseq.commit_snapshot(&snap, &second).await?;
+		assert_eq!(memseq.find_pending(|_| true, usize::MAX).await?, transactions[4..]);
+		assert_eq!(memseq.height().await, 2);
+
+		Ok(())
+	}
+}
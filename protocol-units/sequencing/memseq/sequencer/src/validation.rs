@@ -0,0 +1,21 @@
+use crate::Transaction;
+use thiserror::Error;
+
+/// Error of a [`TransactionValidator`] rejecting a transaction.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Invalid transaction: {reason}")]
+pub struct ValidationError {
+	pub reason: String,
+}
+
+impl ValidationError {
+	pub fn new(reason: impl Into<String>) -> Self {
+		Self { reason: reason.into() }
+	}
+}
+
+/// Checks transactions before they are published to the mempool, see
+/// [`Memseq::with_validator`](crate::Memseq::with_validator).
+pub trait TransactionValidator: Send + Sync {
+	fn validate(&self, tx: &Transaction) -> Result<(), ValidationError>;
+}
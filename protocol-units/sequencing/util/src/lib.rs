@@ -3,6 +3,10 @@ use movement_types::{AtomicTransactionBundle, Block, Transaction};
 pub trait Sequencer {
 	async fn publish(&self, atb: Transaction) -> Result<(), anyhow::Error>;
 
+	/// Publishes the transactions in order. On failure, the transactions before the failing
+	/// one remain published and the error tells how many they are.
+	async fn publish_batch(&self, transactions: Vec<Transaction>) -> Result<(), anyhow::Error>;
+
 	async fn wait_for_next_block(&self) -> Result<Option<Block>, anyhow::Error>;
 }
 
@@ -0,0 +1,43 @@
+use movement_types::BlockCommitment;
+use thiserror::Error;
+
+/// Error returned when a commitment is posted at a height that already holds a different
+/// commitment, which means that two different blocks were assigned the same height upstream.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error(
+	"MCR settlement height collision at height {}: {existing:?} already committed, refusing to submit {submitted:?}",
+	.existing.height
+)]
+pub struct HeightCollision {
+	pub existing: BlockCommitment,
+	pub submitted: BlockCommitment,
+}
+
+/// How `post_block_commitment` and `post_block_commitment_batch` handle a height that already
+/// holds a different commitment. A batch is handled as a whole.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HeightCollisionPolicy {
+	/// Fail with [`HeightCollision`] without submitting.
+	#[default]
+	Reject,
+	/// Submit anyway and let the contract decide.
+	Submit,
+}
+
+impl HeightCollisionPolicy {
+	/// Checks a commitment about to be submitted against the commitment already at its height,
+	/// failing with [`HeightCollision`] on a collision. Resubmitting the same commitment is not
+	/// a collision.
+	pub fn check(
+		&self,
+		existing: Option<BlockCommitment>,
+		submitted: &BlockCommitment,
+	) -> Result<(), anyhow::Error> {
+		match (self, existing) {
+			(HeightCollisionPolicy::Reject, Some(existing)) if existing != *submitted => {
+				Err(HeightCollision { existing, submitted: submitted.clone() }.into())
+			}
+			_ => Ok(()),
+		}
+	}
+}
@@ -0,0 +1,71 @@
+//! Block heights as represented by the MCR contract.
+//!
+//! The contract uses height 0 as the "uncommitted" sentinel: reading the accepted commitment
+//! at a height where nothing was accepted returns a zeroed commitment, height included. Every
+//! other value is a legitimate block height. A block at height 0 can therefore not be
+//! committed: submitting it is rejected up front, instead of producing a commitment that would
+//! read back as "not found".
+
+use thiserror::Error;
+
+/// Error returned when committing a block at the reserved height 0.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("MCR settlement height 0 is reserved for uncommitted heights and cannot be committed")]
+pub struct ReservedCommitmentHeight;
+
+/// A height as stored by the MCR contract, where 0 means uncommitted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CommitmentHeight(u64);
+
+impl CommitmentHeight {
+	/// The sentinel returned for heights without an accepted commitment.
+	pub const UNCOMMITTED: CommitmentHeight = CommitmentHeight(0);
+
+	/// The height at which to commit the block at `height`, which must not be the sentinel.
+	pub fn for_block(height: u64) -> Result<Self, ReservedCommitmentHeight> {
+		match height {
+			0 => Err(ReservedCommitmentHeight),
+			height => Ok(CommitmentHeight(height)),
+		}
+	}
+
+	/// Wraps a height read from the contract, which may be the sentinel.
+	pub fn from_raw(height: u64) -> Self {
+		CommitmentHeight(height)
+	}
+
+	/// The height as stored by the contract.
+	pub fn raw(&self) -> u64 {
+		self.0
+	}
+
+	pub fn is_committed(&self) -> bool {
+		*self != Self::UNCOMMITTED
+	}
+
+	/// The committed block height, or `None` for the sentinel.
+	pub fn committed(&self) -> Option<u64> {
+		self.is_committed().then_some(self.0)
+	}
+}
+
+#[cfg(test)]
+pub mod test {
+
+	use super::*;
+
+	#[test]
+	fn test_commitment_height_round_trips() {
+		assert_eq!(CommitmentHeight::for_block(0), Err(ReservedCommitmentHeight));
+		let uncommitted = CommitmentHeight::from_raw(0);
+		assert_eq!(uncommitted, CommitmentHeight::UNCOMMITTED);
+		assert!(!uncommitted.is_committed());
+		assert_eq!(uncommitted.committed(), None);
+
+		let height = CommitmentHeight::for_block(1).unwrap();
+		assert!(height.is_committed());
+		let read_back = CommitmentHeight::from_raw(height.raw());
+		assert_eq!(read_back, height);
+		assert_eq!(read_back.committed(), Some(1));
+	}
+}
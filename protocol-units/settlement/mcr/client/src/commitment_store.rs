@@ -0,0 +1,201 @@
+use anyhow::Error;
+use movement_types::BlockCommitment;
+use rocksdb::{ColumnFamilyDescriptor, Direction, IteratorMode, Options, DB};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// What is locally known about the settlement of a given height.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitmentRecord {
+	pub height: u64,
+	/// The commitment this node posted for the height, if any.
+	pub posted: Option<BlockCommitment>,
+	/// The commitment the contract accepted for the height, if observed.
+	pub accepted: Option<BlockCommitment>,
+}
+
+/// Durable local record of the commitments posted by the settlement client and of
+/// the commitments accepted on chain.
+#[async_trait::async_trait]
+pub trait CommitmentStore: Send + Sync {
+	/// Records a commitment this node is posting to the settlement contract.
+	async fn record_posted(&self, commitment: BlockCommitment) -> Result<(), anyhow::Error>;
+
+	/// Records a commitment the settlement contract has accepted.
+	async fn record_accepted(&self, commitment: BlockCommitment) -> Result<(), anyhow::Error>;
+
+	/// Gets the record for the given height.
+	async fn get(&self, height: u64) -> Result<Option<CommitmentRecord>, anyhow::Error>;
+
+	/// Gets the records for heights in `start..end`, ordered by height.
+	async fn range(&self, start: u64, end: u64) -> Result<Vec<CommitmentRecord>, anyhow::Error>;
+}
+
+#[derive(Debug, Clone)]
+pub struct RocksdbCommitmentStore {
+	db: Arc<RwLock<DB>>,
+}
+
+impl RocksdbCommitmentStore {
+	pub fn try_new(path: &str) -> Result<Self, Error> {
+		let mut options = Options::default();
+		options.create_if_missing(true);
+		options.create_missing_column_families(true);
+
+		let posted_commitments_cf =
+			ColumnFamilyDescriptor::new("posted_commitments", Options::default());
+		let accepted_commitments_cf =
+			ColumnFamilyDescriptor::new("accepted_commitments", Options::default());
+
+		let db = DB::open_cf_descriptors(
+			&options,
+			path,
+			vec![posted_commitments_cf, accepted_commitments_cf],
+		)
+		.map_err(|e| Error::new(e))?;
+
+		Ok(RocksdbCommitmentStore { db: Arc::new(RwLock::new(db)) })
+	}
+
+	/// Big-endian so that the lexicographic key order matches the height order.
+	fn construct_height_key(height: u64) -> [u8; 8] {
+		height.to_be_bytes()
+	}
+
+	async fn put_commitment(&self, cf: &str, commitment: BlockCommitment) -> Result<(), Error> {
+		let serialized_commitment = serde_json::to_vec(&commitment)?;
+		let db = self.db.write().await;
+		let cf_handle = db.cf_handle(cf).ok_or_else(|| Error::msg("CF handle not found"))?;
+		db.put_cf(
+			&cf_handle,
+			Self::construct_height_key(commitment.height),
+			&serialized_commitment,
+		)?;
+		Ok(())
+	}
+
+	/// Collects the commitments stored in `cf` for heights in `start..end`.
+	async fn commitments_in_range(
+		&self,
+		cf: &str,
+		start: u64,
+		end: u64,
+	) -> Result<Vec<BlockCommitment>, Error> {
+		let db = self.db.read().await;
+		let cf_handle = db.cf_handle(cf).ok_or_else(|| Error::msg("CF handle not found"))?;
+		let start_key = Self::construct_height_key(start);
+		let iter = db.iterator_cf(&cf_handle, IteratorMode::From(&start_key, Direction::Forward));
+
+		let mut commitments = Vec::new();
+		for res in iter {
+			let (_, value) = res?;
+			let commitment: BlockCommitment = serde_json::from_slice(&value)?;
+			if commitment.height >= end {
+				break;
+			}
+			commitments.push(commitment);
+		}
+		Ok(commitments)
+	}
+}
+
+#[async_trait::async_trait]
+impl CommitmentStore for RocksdbCommitmentStore {
+	async fn record_posted(&self, commitment: BlockCommitment) -> Result<(), Error> {
+		self.put_commitment("posted_commitments", commitment).await
+	}
+
+	async fn record_accepted(&self, commitment: BlockCommitment) -> Result<(), Error> {
+		self.put_commitment("accepted_commitments", commitment).await
+	}
+
+	async fn get(&self, height: u64) -> Result<Option<CommitmentRecord>, Error> {
+		let records = self.range(height, height.saturating_add(1)).await?;
+		Ok(records.into_iter().next())
+	}
+
+	async fn range(&self, start: u64, end: u64) -> Result<Vec<CommitmentRecord>, Error> {
+		let mut records = BTreeMap::new();
+		for commitment in self.commitments_in_range("posted_commitments", start, end).await? {
+			let height = commitment.height;
+			records
+				.entry(height)
+				.or_insert(CommitmentRecord { height, posted: None, accepted: None })
+				.posted = Some(commitment);
+		}
+		for commitment in self.commitments_in_range("accepted_commitments", start, end).await? {
+			let height = commitment.height;
+			records
+				.entry(height)
+				.or_insert(CommitmentRecord { height, posted: None, accepted: None })
+				.accepted = Some(commitment);
+		}
+		Ok(records.into_values().collect())
+	}
+}
+
+#[cfg(test)]
+pub mod test {
+
+	use super::*;
+	use movement_types::{Commitment, Id};
+	use tempfile::tempdir;
+
+	fn commitment(height: u64, byte: u8) -> BlockCommitment {
+		BlockCommitment { height, block_id: Id([byte; 32]), commitment: Commitment([byte; 32]) }
+	}
+
+	#[tokio::test]
+	async fn test_record_posted_and_accepted() -> Result<(), Error> {
+		let temp_dir = tempdir().unwrap();
+		let path = temp_dir.path().to_str().unwrap();
+		let store = RocksdbCommitmentStore::try_new(path)?;
+
+		assert_eq!(store.get(1).await?, None);
+
+		store.record_posted(commitment(1, 1)).await?;
+		assert_eq!(
+			store.get(1).await?,
+			Some(CommitmentRecord { height: 1, posted: Some(commitment(1, 1)), accepted: None })
+		);
+
+		store.record_accepted(commitment(1, 2)).await?;
+		assert_eq!(
+			store.get(1).await?,
+			Some(CommitmentRecord {
+				height: 1,
+				posted: Some(commitment(1, 1)),
+				accepted: Some(commitment(1, 2)),
+			})
+		);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_range_is_ordered_and_bounded() -> Result<(), Error> {
+		let temp_dir = tempdir().unwrap();
+		let path = temp_dir.path().to_str().unwrap();
+		let store = RocksdbCommitmentStore::try_new(path)?;
+
+		// heights straddling a byte boundary to check the key ordering
+		for height in [300, 2, 1, 256] {
+			store.record_posted(commitment(height, 1)).await?;
+		}
+		store.record_accepted(commitment(3, 1)).await?;
+
+		let heights: Vec<_> =
+			store.range(1, 300).await?.into_iter().map(|record| record.height).collect();
+		assert_eq!(heights, vec![1, 2, 3, 256]);
+
+		let records = store.range(3, 4).await?;
+		assert_eq!(
+			records,
+			vec![CommitmentRecord { height: 3, posted: None, accepted: Some(commitment(3, 1)) }]
+		);
+
+		Ok(())
+	}
+}
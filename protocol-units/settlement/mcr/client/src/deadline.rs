@@ -0,0 +1,69 @@
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+
+/// Multiplier of the gas price paid for a commitment whose deadline is imminent.
+pub const MAX_GAS_ESCALATION: f64 = 3.0;
+
+/// Error returned when a commitment cannot be settled before its deadline, either because the
+/// deadline had already passed or because the submission did not land in time.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("MCR settlement of the commitment at height {height} missed its deadline {deadline:?}")]
+pub struct DeadlineMissed {
+	pub height: u64,
+	pub deadline: SystemTime,
+}
+
+/// Soft deadline of the settlement of a block produced at `produced_at`.
+pub fn settlement_deadline(produced_at: SystemTime, max_settlement_delay: Duration) -> SystemTime {
+	produced_at + max_settlement_delay
+}
+
+/// Gas price paid for a commitment with `remaining` time left before its deadline. It is the
+/// base gas price while the whole `max_settlement_delay` remains, and rises linearly up to
+/// [`MAX_GAS_ESCALATION`] times the base gas price as the deadline approaches.
+pub fn escalated_gas_price(
+	base_gas_price: u128,
+	remaining: Duration,
+	max_settlement_delay: Duration,
+) -> u128 {
+	let urgency = if max_settlement_delay.is_zero() {
+		1.0
+	} else {
+		1.0 - (remaining.as_secs_f64() / max_settlement_delay.as_secs_f64()).min(1.0)
+	};
+	let multiplier = 1.0 + (MAX_GAS_ESCALATION - 1.0) * urgency;
+	(base_gas_price as f64 * multiplier) as u128
+}
+
+#[cfg(test)]
+pub mod test {
+
+	use super::*;
+
+	#[test]
+	fn test_gas_price_escalates_towards_deadline() {
+		let window = Duration::from_secs(60);
+		let base = 1_000;
+
+		assert_eq!(escalated_gas_price(base, window, window), base);
+		// more time than the window is not cheaper than the base gas price
+		assert_eq!(escalated_gas_price(base, window * 2, window), base);
+		assert_eq!(escalated_gas_price(base, window / 2, window), 2_000);
+		assert_eq!(escalated_gas_price(base, Duration::ZERO, window), 3_000);
+
+		let prices: Vec<_> = (0..=6)
+			.rev()
+			.map(|secs| escalated_gas_price(base, Duration::from_secs(secs * 10), window))
+			.collect();
+		assert!(prices.windows(2).all(|pair| pair[0] <= pair[1]));
+	}
+
+	#[test]
+	fn test_settlement_deadline() {
+		let produced_at = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+		assert_eq!(
+			settlement_deadline(produced_at, Duration::from_secs(30)),
+			SystemTime::UNIX_EPOCH + Duration::from_secs(130)
+		);
+	}
+}
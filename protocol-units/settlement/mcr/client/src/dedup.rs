@@ -0,0 +1,102 @@
+use crate::{AcceptedCommitment, AcceptedStream};
+use movement_types::{BlockCommitment, Id};
+use std::collections::{HashSet, VecDeque};
+use tokio_stream::StreamExt;
+
+/// Number of distinct commitments remembered by [`dedup_commitment_stream`].
+///
+/// A re-subscription replays at most the events since the last delivered height, so the
+/// window only needs to cover the overlap at a reconnection boundary, not the whole history.
+pub const DEDUP_WINDOW: usize = 256;
+
+/// Bounded set of the most recently delivered commitments, keyed by `(height, block_id)`.
+/// Once full, the oldest entry is forgotten to make room for a new one.
+#[derive(Debug, Clone)]
+pub struct RecentCommitments {
+	window: usize,
+	order: VecDeque<(u64, Id)>,
+	seen: HashSet<(u64, Id)>,
+}
+
+impl RecentCommitments {
+	pub fn new(window: usize) -> Self {
+		Self { window, order: VecDeque::with_capacity(window), seen: HashSet::new() }
+	}
+
+	/// Remembers the commitment. Returns `false` if it was already delivered within the window.
+	pub fn insert(&mut self, commitment: &BlockCommitment) -> bool {
+		let key = (commitment.height, commitment.block_id.clone());
+		if self.seen.contains(&key) {
+			return false;
+		}
+		if self.order.len() >= self.window {
+			if let Some(oldest) = self.order.pop_front() {
+				self.seen.remove(&oldest);
+			}
+		}
+		self.order.push_back(key.clone());
+		self.seen.insert(key);
+		true
+	}
+}
+
+/// Drops commitments already delivered within the last `window` distinct commitments, so
+/// that events replayed when re-subscribing after a dropped connection are not delivered
+/// twice downstream. Errors are passed through.
+pub fn dedup_commitment_stream<I>(stream: AcceptedStream<I>, window: usize) -> AcceptedStream<I>
+where
+	I: AcceptedCommitment + Send + 'static,
+{
+	let mut recent = RecentCommitments::new(window);
+	Box::pin(stream.filter(move |commitment| match commitment {
+		Ok(commitment) => recent.insert(commitment.block_commitment()),
+		Err(_) => true,
+	}))
+}
+
+#[cfg(test)]
+pub mod test {
+
+	use super::*;
+	use crate::CommitmentStream;
+	use movement_types::Commitment;
+
+	fn commitment(height: u64) -> BlockCommitment {
+		BlockCommitment {
+			height,
+			block_id: Id([height as u8; 32]),
+			commitment: Commitment([height as u8; 32]),
+		}
+	}
+
+	fn subscription(heights: std::ops::RangeInclusive<u64>) -> CommitmentStream {
+		Box::pin(tokio_stream::iter(heights.map(|height| Ok(commitment(height)))))
+	}
+
+	#[tokio::test]
+	async fn test_reconnect_overlap_is_deduplicated() -> Result<(), anyhow::Error> {
+		// the connection drops after height 5 and the re-subscription replays from height 3
+		let stream: CommitmentStream = Box::pin(subscription(1..=5).chain(subscription(3..=8)));
+		let delivered: Vec<_> = dedup_commitment_stream(stream, DEDUP_WINDOW)
+			.map(|commitment| commitment.map(|commitment| commitment.height))
+			.collect::<Result<_, _>>()
+			.await?;
+
+		assert_eq!(delivered, (1..=8).collect::<Vec<_>>());
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_recent_commitments_forgets_oldest() {
+		let mut recent = RecentCommitments::new(2);
+		assert!(recent.insert(&commitment(1)));
+		assert!(recent.insert(&commitment(2)));
+		assert!(!recent.insert(&commitment(1)));
+
+		assert!(recent.insert(&commitment(3)));
+		// height 1 fell out of the window
+		assert!(recent.insert(&commitment(1)));
+		assert!(!recent.insert(&commitment(3)));
+	}
+}
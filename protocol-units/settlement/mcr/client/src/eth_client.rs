@@ -1,12 +1,22 @@
+use crate::collision::HeightCollisionPolicy;
+use crate::commitment_height::{CommitmentHeight, ReservedCommitmentHeight};
+use crate::commitment_store::CommitmentStore;
+use crate::deadline::{escalated_gas_price, DeadlineMissed};
+use crate::dedup::{dedup_commitment_stream, DEDUP_WINDOW};
+use crate::reconnect::{with_reconnect_grace, StreamTransportError};
+use crate::reorg::{with_reorg_detection, CommitmentEventStream, DEFAULT_MAX_REORG_DEPTH};
 use crate::send_eth_transaction::InsufficentFunds;
 use crate::send_eth_transaction::SendTransactionErrorRule;
 use crate::send_eth_transaction::UnderPriced;
 use crate::send_eth_transaction::VerifyRule;
-use crate::{CommitmentStream, McrSettlementClientOperations};
+use crate::timeout::{with_timeout, OperationTimeouts};
+use crate::util::{retry_with_backoff, BackoffPolicy};
+use crate::{AcceptedCommitment, AcceptedStream, CommitmentStream, McrSettlementClientOperations};
 use alloy::pubsub::PubSubFrontend;
 use alloy_network::Ethereum;
 use alloy_network::EthereumWallet;
 use alloy_primitives::Address;
+use alloy_primitives::TxHash;
 use alloy_primitives::U256;
 use alloy::providers::fillers::ChainIdFiller;
 use alloy::providers::fillers::FillProvider;
@@ -24,9 +34,10 @@ use mcr_settlement_config::Config;
 use movement_types::BlockCommitment;
 use movement_types::{Commitment, Id};
 use serde_json::Value as JsonValue;
-use std::array::TryFromSliceError;
 use std::fs;
 use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use thiserror::Error;
 use tokio_stream::StreamExt;
 
@@ -82,6 +93,13 @@ pub struct Client<P> {
 	send_transaction_error_rules: Vec<Box<dyn VerifyRule>>,
 	gas_limit: u64,
 	send_transaction_retries: u32,
+	commitment_store: Option<Arc<dyn CommitmentStore>>,
+	timeouts: OperationTimeouts,
+	reconnect_grace: Duration,
+	max_reorg_depth: u64,
+	height_collision_policy: HeightCollisionPolicy,
+	backoff: BackoffPolicy,
+	max_settlement_delay: Duration,
 }
 
 impl
@@ -101,8 +119,8 @@ impl
 	>
 {
 	pub async fn build_with_config(config: Config) -> Result<Self, anyhow::Error> {
-		let signer_private_key = config.settle.signer_private_key.clone();
-		let signer = signer_private_key.parse::<PrivateKeySigner>()?;
+		config.validate()?;
+		let signer = config.settle.signer()?;
 		let signer_address = signer.address();
 		let contract_address = config.settle.mcr_contract_address.parse()?;
 		let rpc_url = config.eth_rpc_connection_url();
@@ -124,6 +142,13 @@ impl
 			config.transactions.transaction_send_retries,
 		)
 		.await?;
+		client.timeouts = OperationTimeouts::from_config(&config.transactions);
+		client.reconnect_grace =
+			Duration::from_millis(config.eth_connection.eth_ws_reconnect_grace_ms);
+		client.max_reorg_depth = config.eth_connection.eth_max_reorg_depth;
+		client.backoff = config.backoff;
+		client.max_settlement_delay =
+			Duration::from_millis(config.transactions.max_settlement_delay);
 		Ok(client)
 	}
 }
@@ -158,91 +183,350 @@ impl<P> Client<P> {
 			send_transaction_error_rules,
 			gas_limit,
 			send_transaction_retries,
+			commitment_store: None,
+			timeouts: OperationTimeouts::default(),
+			reconnect_grace: Duration::from_secs(1),
+			max_reorg_depth: DEFAULT_MAX_REORG_DEPTH,
+			height_collision_policy: HeightCollisionPolicy::default(),
+			backoff: BackoffPolicy::default(),
+			max_settlement_delay: Duration::from_secs(60),
 		})
 	}
+
+	/// Sets the timeouts applied to submissions, reads and the event stream setup.
+	pub fn with_timeouts(mut self, timeouts: OperationTimeouts) -> Self {
+		self.timeouts = timeouts;
+		self
+	}
+
+	/// Sets how a commitment posted at a height already holding a different commitment is
+	/// handled. Defaults to [`HeightCollisionPolicy::Reject`].
+	pub fn with_height_collision_policy(mut self, policy: HeightCollisionPolicy) -> Self {
+		self.height_collision_policy = policy;
+		self
+	}
+
+	/// Sets the deepest reorg absorbed by
+	/// [`stream_commitment_events`](Client::stream_commitment_events). Defaults to
+	/// [`DEFAULT_MAX_REORG_DEPTH`].
+	pub fn with_max_reorg_depth(mut self, max_reorg_depth: u64) -> Self {
+		self.max_reorg_depth = max_reorg_depth;
+		self
+	}
+
+	/// Sets the backoff of the re-subscriptions to the event stream and of the transaction
+	/// resends. The number of resends stays set by the send retries of the client.
+	pub fn with_backoff_policy(mut self, backoff: BackoffPolicy) -> Self {
+		self.backoff = backoff;
+		self
+	}
+
+	/// Sets the time allowed to settle a block after it is produced, over which
+	/// [`post_before_deadline`](Client::post_before_deadline) escalates the gas price.
+	pub fn with_max_settlement_delay(mut self, max_settlement_delay: Duration) -> Self {
+		self.max_settlement_delay = max_settlement_delay;
+		self
+	}
+
+	/// Keeps a local record of every posted commitment and of the commitments
+	/// accepted on chain as they are streamed.
+	pub fn with_commitment_store(mut self, commitment_store: Arc<dyn CommitmentStore>) -> Self {
+		self.commitment_store = Some(commitment_store);
+		self
+	}
+
+	async fn record_posted(&self, block_commitment: &BlockCommitment) {
+		if let Some(commitment_store) = &self.commitment_store {
+			// a local bookkeeping failure must not prevent settlement
+			if let Err(err) = commitment_store.record_posted(block_commitment.clone()).await {
+				tracing::warn!(
+					"Failed to record posted commitment at height {}: {err}",
+					block_commitment.height
+				);
+			}
+		}
+	}
 }
 
-#[async_trait::async_trait]
-impl<P> McrSettlementClientOperations for Client<P>
+impl<P> Client<P>
 where
 	P: Provider + Clone,
 {
-	async fn post_block_commitment(
+	/// Estimates the L1 cost of submitting `block_commitments` as one batch, at the current
+	/// gas price of the provider. The gas units include the margin added when the batch is
+	/// actually sent, so the estimate matches the fee checked against the gas limit.
+	pub async fn estimate_cost_wei(
+		&self,
+		block_commitments: &[BlockCommitment],
+	) -> Result<CostEstimate, anyhow::Error> {
+		let eth_block_commitment = eth_block_commitments(block_commitments)?;
+		let contract = MCR::new(self.contract_address, &self.rpc_provider);
+		let call_builder = contract.submitBatchBlockCommitment(eth_block_commitment);
+
+		with_timeout("read", self.timeouts.read, async {
+			let gas_units = crate::send_eth_transaction::with_gas_estimate_margin(
+				call_builder.estimate_gas().await?,
+			);
+			let gas_price = self.rpc_provider.get_gas_price().await?;
+			Ok(CostEstimate::new(gas_units, gas_price))
+		})
+		.await
+	}
+
+	/// Gets the pending nonce of the signer, i.e. the nonce of its next transaction counting
+	/// those still in the mempool, so that callers sending their own transactions from the
+	/// same key can interleave them with the client's submissions.
+	pub async fn signer_pending_nonce(&self) -> Result<u64, anyhow::Error> {
+		with_timeout("read", self.timeouts.read, async {
+			Ok(self.rpc_provider.get_transaction_count(self.signer_address).pending().await?)
+		})
+		.await
+	}
+
+	/// Gas price currently offered by [`post_before_deadline`](Client::post_before_deadline)
+	/// for a commitment due at `deadline`, the current gas price of the provider escalated by
+	/// [`escalated_gas_price`] as the deadline approaches. `None` if the deadline has passed.
+	pub async fn deadline_gas_price(
+		&self,
+		deadline: SystemTime,
+	) -> Result<Option<u128>, anyhow::Error> {
+		let Ok(remaining) = deadline.duration_since(SystemTime::now()) else {
+			return Ok(None);
+		};
+		let base_gas_price = with_timeout("read", self.timeouts.read, async {
+			Ok(self.rpc_provider.get_gas_price().await?)
+		})
+		.await?;
+		Ok(Some(escalated_gas_price(base_gas_price, remaining, self.max_settlement_delay)))
+	}
+
+	/// Posts a block commitment that must be settled by `deadline`, see
+	/// [`settlement_deadline`](crate::deadline::settlement_deadline). The closer the deadline,
+	/// the higher the gas price offered, see [`deadline_gas_price`](Client::deadline_gas_price).
+	/// The gas price is escalated again on every resend, as the deadline gets closer.
+	///
+	/// Fails with [`DeadlineMissed`] without submitting if the deadline has already passed, and
+	/// once it passes if the submission has not landed by then.
+	pub async fn post_before_deadline(
 		&self,
 		block_commitment: BlockCommitment,
+		deadline: SystemTime,
 	) -> Result<(), anyhow::Error> {
+		let missed = DeadlineMissed { height: block_commitment.height, deadline };
+		let Ok(remaining) = deadline.duration_since(SystemTime::now()) else {
+			return Err(missed.into());
+		};
+		let max_settlement_delay = self.max_settlement_delay;
+		let gas_pricing = move |base_gas_price| {
+			let remaining = deadline.duration_since(SystemTime::now()).unwrap_or_default();
+			escalated_gas_price(base_gas_price, remaining, max_settlement_delay)
+		};
+
+		let eth_block_commitment = self.prepare_submission(&block_commitment).await?;
 		let contract = MCR::new(self.contract_address, &self.rpc_provider);
+		let call_builder = contract.submitBlockCommitment(eth_block_commitment);
 
-		let eth_block_commitment = MCR::BlockCommitment {
-			// Currently, to simplify the API, we'll say 0 is uncommitted all other numbers are legitimate heights
-			height: U256::from(block_commitment.height),
+		tokio::time::timeout(
+			remaining,
+			crate::send_eth_transaction::send_transaction(
+				call_builder,
+				&self.send_transaction_error_rules,
+				self.send_transaction_retries,
+				self.gas_limit as u128,
+				&self.backoff,
+				Some(&gas_pricing),
+			),
+		)
+		.await
+		.map_err(|_| missed)??;
+		self.record_posted(&block_commitment).await;
+		Ok(())
+	}
+
+	/// Checks a commitment about to be submitted against the height collision policy,
+	/// returning its contract representation.
+	async fn prepare_submission(
+		&self,
+		block_commitment: &BlockCommitment,
+	) -> Result<MCR::BlockCommitment, anyhow::Error> {
+		let height = CommitmentHeight::for_block(block_commitment.height)?;
+		self.check_height_collision(block_commitment).await?;
+
+		Ok(MCR::BlockCommitment {
+			height: U256::from(height.raw()),
 			commitment: alloy_primitives::FixedBytes(block_commitment.commitment.0),
 			blockId: alloy_primitives::FixedBytes(block_commitment.block_id.0),
-		};
+		})
+	}
+
+	/// Checks a commitment about to be submitted against the commitment already accepted at
+	/// its height, if the height collision policy asks for it.
+	async fn check_height_collision(
+		&self,
+		block_commitment: &BlockCommitment,
+	) -> Result<(), anyhow::Error> {
+		if self.height_collision_policy == HeightCollisionPolicy::Reject {
+			let existing = self.get_commitment_at_height(block_commitment.height).await?;
+			self.height_collision_policy.check(existing, block_commitment)?;
+		}
+		Ok(())
+	}
+
+	/// Streams accepted commitments like
+	/// [`stream_block_commitments`](McrSettlementClientOperations::stream_block_commitments),
+	/// along with where each was settled on L1.
+	pub async fn stream_settled_commitments(
+		&self,
+	) -> Result<AcceptedStream<SettledCommitment>, anyhow::Error> {
+		let (stream, start_block) = subscribe_block_accepted(
+			self.ws_provider.clone(),
+			self.contract_address,
+			self.timeouts.stream_setup,
+		)
+		.await?;
+
+		// The subscription only delivers new events, those logged since the last delivered
+		// one, or since the first subscription if none was, are backfilled when re-subscribing.
+		let ws_provider = self.ws_provider.clone();
+		let contract_address = self.contract_address;
+		let timeouts = self.timeouts;
+		let backoff = self.backoff.clone();
+		let stream = with_reconnect_grace(stream, self.reconnect_grace, move |last| {
+			let ws_provider = ws_provider.clone();
+			let backoff = backoff.clone();
+			async move {
+				// subscribed before backfilling so that nothing logged in between is missed,
+				// the overlap is dropped by the deduplication below
+				let (live, _) = retry_with_backoff(
+					|| {
+						subscribe_block_accepted(
+							ws_provider.clone(),
+							contract_address,
+							timeouts.stream_setup,
+						)
+					},
+					&backoff,
+				)
+				.await?;
+				let since = last.and_then(|last: SettledCommitment| last.meta);
+				let backfill = retry_with_backoff(
+					|| {
+						query_block_accepted_since(
+							ws_provider.clone(),
+							contract_address,
+							start_block,
+							since,
+							timeouts.read,
+						)
+					},
+					&backoff,
+				)
+				.await?;
+				Ok(Box::pin(futures::stream::iter(backfill).chain(live))
+					as AcceptedStream<SettledCommitment>)
+			}
+		});
+
+		let commitment_store = self.commitment_store.clone();
+		let stream = stream.then(move |settled: Result<SettledCommitment, anyhow::Error>| {
+			let commitment_store = commitment_store.clone();
+			async move {
+				if let (Ok(settled), Some(commitment_store)) = (&settled, commitment_store) {
+					let commitment = &settled.commitment;
+					if let Err(err) = commitment_store.record_accepted(commitment.clone()).await {
+						tracing::warn!(
+							"Failed to record accepted commitment at height {}: {err}",
+							commitment.height
+						);
+					}
+				}
+				settled
+			}
+		});
+		Ok(dedup_commitment_stream(Box::pin(stream), DEDUP_WINDOW))
+	}
+
+	/// Streams settled commitments like
+	/// [`stream_settled_commitments`](Client::stream_settled_commitments), with a
+	/// [`Reorged`](crate::reorg::CommitmentEvent::Reorged) event before a commitment replacing
+	/// already delivered ones. A reorg deeper than the maximum reorg depth ends the stream with
+	/// a [`DeepReorg`](crate::reorg::DeepReorg) error.
+	pub async fn stream_commitment_events(
+		&self,
+	) -> Result<CommitmentEventStream<SettledCommitment>, anyhow::Error> {
+		let stream = self.stream_settled_commitments().await?;
+		Ok(with_reorg_detection(stream, self.max_reorg_depth))
+	}
+}
+
+#[async_trait::async_trait]
+impl<P> McrSettlementClientOperations for Client<P>
+where
+	P: Provider + Clone,
+{
+	async fn post_block_commitment(
+		&self,
+		block_commitment: BlockCommitment,
+	) -> Result<(), anyhow::Error> {
+		let eth_block_commitment = self.prepare_submission(&block_commitment).await?;
+
+		let contract = MCR::new(self.contract_address, &self.rpc_provider);
 
 		let call_builder = contract.submitBlockCommitment(eth_block_commitment);
 
-		crate::send_eth_transaction::send_transaction(
-			call_builder,
-			&self.send_transaction_error_rules,
-			self.send_transaction_retries,
-			self.gas_limit as u128,
+		with_timeout(
+			"submit",
+			self.timeouts.submit,
+			crate::send_eth_transaction::send_transaction(
+				call_builder,
+				&self.send_transaction_error_rules,
+				self.send_transaction_retries,
+				self.gas_limit as u128,
+				&self.backoff,
+				None,
+			),
 		)
-		.await
+		.await?;
+		self.record_posted(&block_commitment).await;
+		Ok(())
 	}
 
 	async fn post_block_commitment_batch(
 		&self,
 		block_commitments: Vec<BlockCommitment>,
 	) -> Result<(), anyhow::Error> {
-		let contract = MCR::new(self.contract_address, &self.rpc_provider);
+		let eth_block_commitment = eth_block_commitments(&block_commitments)?;
+		// the whole batch is refused on a collision, before anything is submitted
+		for block_commitment in &block_commitments {
+			self.check_height_collision(block_commitment).await?;
+		}
 
-		let eth_block_commitment: Vec<_> = block_commitments
-			.into_iter()
-			.map(|block_commitment| {
-				Ok(MCR::BlockCommitment {
-					// Currently, to simplify the API, we'll say 0 is uncommitted all other numbers are legitimate heights
-					height: U256::from(block_commitment.height),
-					commitment: alloy_primitives::FixedBytes(block_commitment.commitment.0),
-					blockId: alloy_primitives::FixedBytes(block_commitment.block_id.0),
-				})
-			})
-			.collect::<Result<Vec<_>, TryFromSliceError>>()?;
+		let contract = MCR::new(self.contract_address, &self.rpc_provider);
 
 		let call_builder = contract.submitBatchBlockCommitment(eth_block_commitment);
 
-		crate::send_eth_transaction::send_transaction(
-			call_builder,
-			&self.send_transaction_error_rules,
-			self.send_transaction_retries,
-			self.gas_limit as u128,
+		with_timeout(
+			"submit",
+			self.timeouts.submit,
+			crate::send_eth_transaction::send_transaction(
+				call_builder,
+				&self.send_transaction_error_rules,
+				self.send_transaction_retries,
+				self.gas_limit as u128,
+				&self.backoff,
+				None,
+			),
 		)
-		.await
+		.await?;
+		for block_commitment in &block_commitments {
+			self.record_posted(block_commitment).await;
+		}
+		Ok(())
 	}
 
 	async fn stream_block_commitments(&self) -> Result<CommitmentStream, anyhow::Error> {
-		// Register to contract BlockCommitmentSubmitted event
-
-		let contract = MCR::new(self.contract_address, &self.ws_provider);
-		let event_filter = contract.BlockAccepted_filter().watch().await?;
-
-		let stream = event_filter.into_stream().map(|event| {
-			event
-				.and_then(|(commitment, _)| {
-					let height = commitment.height.try_into().map_err(
-						|err: alloy::primitives::ruint::FromUintError<u64>| {
-							alloy_sol_types::Error::Other(err.to_string().into())
-						},
-					)?;
-					Ok(BlockCommitment {
-						height,
-						block_id: Id(commitment.blockHash.0),
-						commitment: Commitment(commitment.stateCommitment.0),
-					})
-				})
-				.map_err(|err| McrEthConnectorError::EventNotificationError(err).into())
-		});
-		Ok(Box::pin(stream) as CommitmentStream)
+		let stream = self.stream_settled_commitments().await?;
+		Ok(Box::pin(stream.map(|settled| settled.map(|settled| settled.commitment))))
 	}
 
 	async fn get_commitment_at_height(
@@ -250,19 +534,17 @@ where
 		height: u64,
 	) -> Result<Option<BlockCommitment>, anyhow::Error> {
 		let contract = MCR::new(self.contract_address, &self.ws_provider);
-		let MCR::getAcceptedCommitmentAtBlockHeightReturn { _0: commitment } = contract
-			.getAcceptedCommitmentAtBlockHeight(U256::from(height))
-			.call()
+		let MCR::getAcceptedCommitmentAtBlockHeightReturn { _0: commitment } =
+			with_timeout("read", self.timeouts.read, async {
+				Ok(contract.getAcceptedCommitmentAtBlockHeight(U256::from(height)).call().await?)
+			})
 			.await?;
 		
 		let return_height: u64 = commitment.height.try_into().context(
 			"Failed to convert the commitment height from U256 to u64",
 		)?;
-		// Commitment with height 0 mean not found
-		Ok((return_height != 0).then_some(BlockCommitment {
-			height: commitment.height.try_into().context(
-				"Failed to convert the commitment height from U256 to u64",
-			)?,
+		Ok(CommitmentHeight::from_raw(return_height).committed().map(|height| BlockCommitment {
+			height,
 			block_id: Id(commitment.blockId.into()),
 			commitment: Commitment(commitment.commitment.into()),
 		}))
@@ -271,18 +553,217 @@ where
 	async fn get_max_tolerable_block_height(&self) -> Result<u64, anyhow::Error> {
 		let contract = MCR::new(self.contract_address, &self.ws_provider);
 		let MCR::getMaxTolerableBlockHeightReturn { _0: block_height } =
-			contract.getMaxTolerableBlockHeight().call().await?;
+			with_timeout("read", self.timeouts.read, async {
+				Ok(contract.getMaxTolerableBlockHeight().call().await?)
+			})
+			.await?;
 		Ok(block_height.try_into().context(
 			"Failed to convert the max tolerable block height from U256 to u64",
 		)?)
 	}
 }
 
+/// Expected L1 cost of a submission.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CostEstimate {
+	pub gas_units: u128,
+	/// Gas price in wei.
+	pub gas_price: u128,
+	pub total_wei: U256,
+}
+
+impl CostEstimate {
+	pub fn new(gas_units: u128, gas_price: u128) -> Self {
+		// computed in 256 bits, the product of two u128 cannot overflow
+		let total_wei = U256::from(gas_units) * U256::from(gas_price);
+		CostEstimate { gas_units, gas_price, total_wei }
+	}
+}
+
+/// Converts commitments to their contract representation, rejecting the reserved height.
+fn eth_block_commitments(
+	block_commitments: &[BlockCommitment],
+) -> Result<Vec<MCR::BlockCommitment>, ReservedCommitmentHeight> {
+	block_commitments
+		.iter()
+		.map(|block_commitment| {
+			Ok(MCR::BlockCommitment {
+				height: U256::from(CommitmentHeight::for_block(block_commitment.height)?.raw()),
+				commitment: alloy_primitives::FixedBytes(block_commitment.commitment.0),
+				blockId: alloy_primitives::FixedBytes(block_commitment.block_id.0),
+			})
+		})
+		.collect()
+}
+
+/// Where on L1 a commitment was accepted: the log of its `BlockAccepted` event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SettlementMeta {
+	pub l1_tx_hash: TxHash,
+	pub l1_block_number: u64,
+	pub log_index: u64,
+}
+
+/// An accepted commitment along with where it was settled, if the event log told.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SettledCommitment {
+	pub commitment: BlockCommitment,
+	pub meta: Option<SettlementMeta>,
+}
+
+impl AcceptedCommitment for SettledCommitment {
+	fn block_commitment(&self) -> &BlockCommitment {
+		&self.commitment
+	}
+}
+
+/// Subscribes to the `BlockAccepted` events logged from the next L1 block on, returning the
+/// stream along with that block number.
+///
+/// New logs are polled for at the poll interval of the provider. A failed poll is yielded as a
+/// [`StreamTransportError`] and the next poll resumes from the same block, so that
+/// [`with_reconnect_grace`] can tell a dropped connection from a bad event.
+async fn subscribe_block_accepted(
+	ws_provider: RootProvider<PubSubFrontend>,
+	contract_address: Address,
+	stream_setup_timeout: Duration,
+) -> Result<(AcceptedStream<SettledCommitment>, u64), anyhow::Error> {
+	let from_block = with_timeout("stream setup", stream_setup_timeout, async {
+		Ok(ws_provider.get_block_number().await? + 1)
+	})
+	.await?;
+	let filter = MCR::new(contract_address, &ws_provider).BlockAccepted_filter().filter;
+	let poll_interval = ws_provider.client().poll_interval();
+
+	let stream = futures::stream::unfold(from_block, move |from_block| {
+		let ws_provider = ws_provider.clone();
+		let filter = filter.clone();
+		async move {
+			tokio::time::sleep(poll_interval).await;
+			let poll = async {
+				let to_block = ws_provider.get_block_number().await?;
+				if to_block < from_block {
+					return Ok((Vec::new(), from_block));
+				}
+				let logs =
+					ws_provider.get_logs(&filter.from_block(from_block).to_block(to_block)).await?;
+				Ok::<_, alloy_transport::TransportError>((logs, to_block + 1))
+			};
+			let (items, from_block) = match poll.await {
+				Ok((logs, next_block)) => (block_accepted_items(Ok(logs)), next_block),
+				Err(err) => (block_accepted_items(Err(err)), from_block),
+			};
+			Some((futures::stream::iter(items), from_block))
+		}
+	});
+	Ok((Box::pin(stream.flatten()), from_block))
+}
+
+/// Maps a poll of `BlockAccepted` logs to stream items: a failed poll to a
+/// [`StreamTransportError`], a log that fails to decode to an
+/// [`EventNotificationError`](McrEthConnectorError::EventNotificationError).
+fn block_accepted_items(
+	logs: Result<Vec<alloy::rpc::types::Log>, alloy_transport::TransportError>,
+) -> Vec<Result<SettledCommitment, anyhow::Error>> {
+	match logs {
+		Ok(logs) => logs
+			.into_iter()
+			.map(|log| {
+				log.log_decode::<MCR::BlockAccepted>()
+					.and_then(|decoded| settled_commitment(decoded.inner.data, log))
+					.map_err(|err| McrEthConnectorError::EventNotificationError(err).into())
+			})
+			.collect(),
+		Err(err) => vec![Err(StreamTransportError(err.into()).into())],
+	}
+}
+
+/// Queries the `BlockAccepted` events logged after `since`, or from `start_block` on if
+/// nothing was delivered yet, in log order.
+async fn query_block_accepted_since(
+	ws_provider: RootProvider<PubSubFrontend>,
+	contract_address: Address,
+	start_block: u64,
+	since: Option<SettlementMeta>,
+	read_timeout: Duration,
+) -> Result<Vec<Result<SettledCommitment, anyhow::Error>>, anyhow::Error> {
+	let contract = MCR::new(contract_address, &ws_provider);
+	let from_block = since.map_or(start_block, |since| since.l1_block_number);
+	let events = with_timeout("read", read_timeout, async {
+		Ok(contract.BlockAccepted_filter().from_block(from_block).query().await?)
+	})
+	.await?;
+	Ok(events
+		.into_iter()
+		.filter(|(_, log)| match since {
+			Some(since) => {
+				(log.block_number, log.log_index)
+					> (Some(since.l1_block_number), Some(since.log_index))
+			}
+			None => true,
+		})
+		.map(|(commitment, log)| {
+			settled_commitment(commitment, log)
+				.map_err(|err| McrEthConnectorError::EventNotificationError(err).into())
+		})
+		.collect())
+}
+
+/// Reads the accepted commitment of a `BlockAccepted` event, along with where it was logged.
+fn settled_commitment(
+	commitment: MCR::BlockAccepted,
+	log: alloy::rpc::types::Log,
+) -> Result<SettledCommitment, alloy_sol_types::Error> {
+	let height = commitment.height.try_into().map_err(
+		|err: alloy::primitives::ruint::FromUintError<u64>| {
+			alloy_sol_types::Error::Other(err.to_string().into())
+		},
+	)?;
+	let height = CommitmentHeight::from_raw(height).committed().ok_or_else(|| {
+		alloy_sol_types::Error::Other("BlockAccepted event at the reserved height 0".into())
+	})?;
+	// only missing for logs of pending blocks
+	let meta = match (log.transaction_hash, log.block_number, log.log_index) {
+		(Some(l1_tx_hash), Some(l1_block_number), Some(log_index)) => {
+			Some(SettlementMeta { l1_tx_hash, l1_block_number, log_index })
+		}
+		_ => None,
+	};
+	Ok(SettledCommitment {
+		commitment: BlockCommitment {
+			height,
+			block_id: Id(commitment.blockHash.0),
+			commitment: Commitment(commitment.stateCommitment.0),
+		},
+		meta,
+	})
+}
+
 pub struct AnvilAddressEntry {
-	pub address: String,
+	/// Parsed regardless of the casing in the dump, so that it compares equal to the
+	/// address of the signer.
+	pub address: Address,
 	pub private_key: String,
 }
 
+impl AnvilAddressEntry {
+	/// Parses the private key into a signer.
+	pub fn signer(&self) -> Result<PrivateKeySigner, anyhow::Error> {
+		self.private_key
+			.parse::<PrivateKeySigner>()
+			.with_context(|| format!("Invalid anvil private key for address {}", self.address))
+	}
+}
+
+/// Parses an address of the anvil dump, which may or may not be checksummed.
+fn parse_anvil_address(address: &str) -> Result<Address, anyhow::Error> {
+	// the casing only carries the EIP-55 checksum, which anvil dumps do not reliably apply
+	address
+		.to_lowercase()
+		.parse::<Address>()
+		.with_context(|| format!("Invalid anvil address {address:?} in available_accounts"))
+}
+
 /// Read the Anvil config file keys and return all address/private keys.
 pub fn read_anvil_json_file_addresses<P: AsRef<Path>>(
 	anvil_conf_path: P,
@@ -310,9 +791,131 @@ pub fn read_anvil_json_file_addresses<P: AsRef<Path>>(
 			s.to_owned()
 		});
 
-	let res = available_accounts_iter
+	available_accounts_iter
 		.zip(private_keys_iter)
-		.map(|(address, private_key)| AnvilAddressEntry { address, private_key })
-		.collect::<Vec<_>>();
-	Ok(res)
-}
\ No newline at end of file
+		.map(|(address, private_key)| {
+			Ok(AnvilAddressEntry { address: parse_anvil_address(&address)?, private_key })
+		})
+		.collect()
+}
+
+#[cfg(test)]
+pub mod test {
+	use super::*;
+	use std::io::Write;
+
+	#[test]
+	fn test_anvil_address_entry_signer() -> Result<(), anyhow::Error> {
+		// First well-known anvil account.
+		let dump = r#"{
+			"available_accounts": ["0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266"],
+			"private_keys": ["0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80"]
+		}"#;
+		let mut anvil_conf = tempfile::NamedTempFile::new()?;
+		anvil_conf.write_all(dump.as_bytes())?;
+
+		let entries = read_anvil_json_file_addresses(anvil_conf.path())?;
+		assert_eq!(entries.len(), 1);
+
+		let signer = entries[0].signer()?;
+		assert_eq!(signer.address(), entries[0].address);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_anvil_addresses_ignore_casing() -> Result<(), anyhow::Error> {
+		// the same account checksummed, lowercase and uppercase
+		let dump = r#"{
+			"available_accounts": [
+				"0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266",
+				"0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266",
+				"0xF39FD6E51AAD88F6F4CE6AB8827279CFFFB92266"
+			],
+			"private_keys": ["0x01", "0x02", "0x03"]
+		}"#;
+		let mut anvil_conf = tempfile::NamedTempFile::new()?;
+		anvil_conf.write_all(dump.as_bytes())?;
+
+		let entries = read_anvil_json_file_addresses(anvil_conf.path())?;
+		let expected: Address = "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266".parse()?;
+		assert_eq!(entries.len(), 3);
+		assert!(entries.iter().all(|entry| entry.address == expected));
+
+		let dump = r#"{"available_accounts": ["0xnot_an_address"], "private_keys": ["0x01"]}"#;
+		let mut anvil_conf = tempfile::NamedTempFile::new()?;
+		anvil_conf.write_all(dump.as_bytes())?;
+		let err = read_anvil_json_file_addresses(anvil_conf.path()).err().expect("invalid address");
+		assert!(err.to_string().contains("0xnot_an_address"), "{err}");
+
+		Ok(())
+	}
+
+	fn block_accepted_log(height: u64) -> alloy::rpc::types::Log {
+		use alloy_sol_types::SolEvent;
+		let event = MCR::BlockAccepted {
+			blockHash: [height as u8; 32].into(),
+			stateCommitment: [height as u8; 32].into(),
+			height: U256::from(height),
+		};
+		alloy::rpc::types::Log {
+			inner: alloy_primitives::Log { address: Address::ZERO, data: event.encode_log_data() },
+			block_number: Some(height),
+			..Default::default()
+		}
+	}
+
+	#[tokio::test]
+	async fn test_failed_polls_are_retried_as_transport_errors() -> Result<(), anyhow::Error> {
+		let polls = vec![
+			Ok(vec![block_accepted_log(1)]),
+			Err(alloy_transport::TransportErrorKind::backend_gone()),
+			Ok(vec![block_accepted_log(2)]),
+			Err(alloy_transport::TransportErrorKind::backend_gone()),
+		];
+		let items: Vec<_> = polls.into_iter().flat_map(block_accepted_items).collect();
+		assert!(items[1].as_ref().is_err_and(|err| err.is::<StreamTransportError>()));
+
+		let resubscribed_from = Arc::new(std::sync::Mutex::new(Vec::new()));
+		let recorded = Arc::clone(&resubscribed_from);
+		let stream = with_reconnect_grace(
+			Box::pin(tokio_stream::iter(items)),
+			Duration::from_secs(60),
+			move |last: Option<SettledCommitment>| {
+				recorded.lock().unwrap().push(last.map(|last| last.commitment.height));
+				async { Ok(Box::pin(tokio_stream::empty()) as AcceptedStream<SettledCommitment>) }
+			},
+		);
+		let heights = stream
+			.map(|settled| settled.map(|settled| settled.commitment.height))
+			.collect::<Result<Vec<_>, _>>()
+			.await?;
+
+		assert_eq!(heights, vec![1, 2]);
+		// the first failed poll was retried, the second one within the grace period and then
+		// the end of the replacement re-subscribed
+		assert_eq!(*resubscribed_from.lock().unwrap(), vec![Some(2), Some(2)]);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_undecodable_logs_are_not_transport_errors() {
+		let mut log = block_accepted_log(1);
+		log.inner.data = alloy_primitives::LogData::new_unchecked(Vec::new(), Default::default());
+		let items = block_accepted_items(Ok(vec![log]));
+		let err = items[0].as_ref().err().expect("undecodable log");
+		assert!(!err.is::<StreamTransportError>());
+		assert!(err.is::<McrEthConnectorError>());
+	}
+
+	#[test]
+	fn test_cost_estimate_total() {
+		let estimate = CostEstimate::new(100_000, 2_000_000_000);
+		assert_eq!(estimate.total_wei, U256::from(200_000_000_000_000u128));
+
+		// the total exceeds u128 without overflowing
+		let estimate = CostEstimate::new(u128::MAX, 2);
+		assert_eq!(estimate.total_wei, U256::from(u128::MAX) * U256::from(2));
+	}
+}
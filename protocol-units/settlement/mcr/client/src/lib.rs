@@ -1,9 +1,28 @@
-use movement_types::BlockCommitment;
+use movement_types::{aggregate_commitment, BlockCommitment, Id};
+use std::ops::Range;
 use tokio_stream::Stream;
 
 #[cfg(test)]
 pub mod tests;
 
+pub mod collision;
+
+pub mod commitment_height;
+
+pub mod commitment_store;
+
+pub mod deadline;
+
+pub mod dedup;
+
+pub mod reconnect;
+
+pub mod reorg;
+
+pub mod timeout;
+
+pub mod util;
+
 pub mod mock;
 
 #[cfg(feature = "mock")]
@@ -16,8 +35,25 @@ pub use eth_client::Client as McrEthSettlementClient;
 
 mod send_eth_transaction;
 
-type CommitmentStream =
-	std::pin::Pin<Box<dyn Stream<Item = Result<BlockCommitment, anyhow::Error>> + Send>>;
+/// Maximum number of concurrent height lookups of
+/// [`McrSettlementClientOperations::list_committed_heights`] and
+/// [`McrSettlementClientOperations::is_block_committed`].
+pub const COMMITTED_HEIGHTS_CONCURRENCY: usize = 8;
+
+type AcceptedStream<I> = std::pin::Pin<Box<dyn Stream<Item = Result<I, anyhow::Error>> + Send>>;
+
+type CommitmentStream = AcceptedStream<BlockCommitment>;
+
+/// An item of a stream of accepted commitments, which may carry more than the commitment.
+pub trait AcceptedCommitment {
+	fn block_commitment(&self) -> &BlockCommitment;
+}
+
+impl AcceptedCommitment for BlockCommitment {
+	fn block_commitment(&self) -> &BlockCommitment {
+		self
+	}
+}
 
 #[async_trait::async_trait]
 pub trait McrSettlementClientOperations {
@@ -28,10 +64,53 @@ pub trait McrSettlementClientOperations {
 	) -> Result<(), anyhow::Error>;
 
 	/// Posts a batch of block commitments to the settlement client.
+	///
+	/// Defaults to posting the commitments one by one, in order, stopping at the first error.
+	/// Implementations able to submit a batch at once should override it.
 	async fn post_block_commitment_batch(
 		&self,
 		block_commitment: Vec<BlockCommitment>,
-	) -> Result<(), anyhow::Error>;
+	) -> Result<(), anyhow::Error>
+	where
+		Self: Sync,
+	{
+		for block_commitment in block_commitment {
+			self.post_block_commitment(block_commitment).await?;
+		}
+		Ok(())
+	}
+
+	/// Settles a batch of consecutive blocks under a single commitment: the
+	/// [`aggregate_commitment`] of the batch, posted at the height and with the block id of
+	/// the last block.
+	///
+	/// Only that height is settled. The heights before it remain uncommitted, so that
+	/// [`McrSettlementClientOperations::get_commitment_at_height`] and
+	/// [`McrSettlementClientOperations::is_block_committed`] do not find the other blocks of
+	/// the batch, and a single commitment is streamed for the whole batch. To verify one of
+	/// those blocks, a consumer needs the individual commitments of the batch, published
+	/// separately, and checks that their aggregate is the commitment settled at the last
+	/// height.
+	async fn post_rollup_commitment(&self, blocks: &[BlockCommitment]) -> Result<(), anyhow::Error>
+	where
+		Self: Sync,
+	{
+		let last = blocks.last().ok_or(anyhow::anyhow!("Empty rollup"))?;
+		for pair in blocks.windows(2) {
+			anyhow::ensure!(
+				pair[0].height.checked_add(1) == Some(pair[1].height),
+				"Rollup height {} does not follow height {}",
+				pair[1].height,
+				pair[0].height
+			);
+		}
+		self.post_block_commitment(BlockCommitment {
+			height: last.height,
+			block_id: last.block_id.clone(),
+			commitment: aggregate_commitment(blocks),
+		})
+		.await
+	}
 
 	/// Streams block commitments from the settlement client.
 	async fn stream_block_commitments(&self) -> Result<CommitmentStream, anyhow::Error>;
@@ -44,4 +123,109 @@ pub trait McrSettlementClientOperations {
 
 	/// Gets the max tolerable block height.
 	async fn get_max_tolerable_block_height(&self) -> Result<u64, anyhow::Error>;
+
+	/// Lists the heights in `start..end` that have an accepted commitment, in ascending order.
+	async fn list_committed_heights(&self, start: u64, end: u64) -> Result<Vec<u64>, anyhow::Error>
+	where
+		Self: Sync,
+	{
+		use futures::{StreamExt, TryStreamExt};
+
+		futures::stream::iter(start..end)
+			.map(|height| async move {
+				Ok::<_, anyhow::Error>(self.get_commitment_at_height(height).await?.map(|_| height))
+			})
+			.buffered(COMMITTED_HEIGHTS_CONCURRENCY)
+			.try_filter_map(|height| async move { Ok(height) })
+			.try_collect()
+			.await
+	}
+
+	/// Whether a commitment to `block_id` was accepted at any height of `search_range`.
+	///
+	/// Looks up every height of the range until a match is found, so the cost is one read per
+	/// height: keep the range as narrow as what is known of the height of the block.
+	async fn is_block_committed(
+		&self,
+		block_id: Id,
+		search_range: Range<u64>,
+	) -> Result<bool, anyhow::Error>
+	where
+		Self: Sync,
+	{
+		use futures::{StreamExt, TryStreamExt};
+
+		let mut commitments = futures::stream::iter(search_range)
+			.map(|height| self.get_commitment_at_height(height))
+			.buffer_unordered(COMMITTED_HEIGHTS_CONCURRENCY);
+		while let Some(commitment) = commitments.try_next().await? {
+			if commitment.is_some_and(|commitment| commitment.block_id == block_id) {
+				return Ok(true);
+			}
+		}
+		Ok(false)
+	}
+}
+
+#[cfg(test)]
+pub mod test {
+
+	use super::*;
+	use movement_types::Commitment;
+	use std::sync::Mutex;
+
+	/// Only provides the single submission, relying on the default batch submission.
+	#[derive(Default)]
+	struct SingleSubmissionClient {
+		posted: Mutex<Vec<u64>>,
+	}
+
+	#[async_trait::async_trait]
+	impl McrSettlementClientOperations for SingleSubmissionClient {
+		async fn post_block_commitment(
+			&self,
+			block_commitment: BlockCommitment,
+		) -> Result<(), anyhow::Error> {
+			if block_commitment.height == 0 {
+				anyhow::bail!("height 0 rejected");
+			}
+			self.posted.lock().unwrap().push(block_commitment.height);
+			Ok(())
+		}
+
+		async fn stream_block_commitments(&self) -> Result<CommitmentStream, anyhow::Error> {
+			Ok(Box::pin(tokio_stream::empty()))
+		}
+
+		async fn get_commitment_at_height(
+			&self,
+			_height: u64,
+		) -> Result<Option<BlockCommitment>, anyhow::Error> {
+			Ok(None)
+		}
+
+		async fn get_max_tolerable_block_height(&self) -> Result<u64, anyhow::Error> {
+			Ok(0)
+		}
+	}
+
+	fn commitment(height: u64) -> BlockCommitment {
+		BlockCommitment { height, block_id: Default::default(), commitment: Commitment::test() }
+	}
+
+	#[tokio::test]
+	async fn test_default_batch_posts_each_commitment() -> Result<(), anyhow::Error> {
+		let client = SingleSubmissionClient::default();
+		client.post_block_commitment_batch(vec![commitment(1), commitment(2)]).await?;
+		assert_eq!(*client.posted.lock().unwrap(), vec![1, 2]);
+
+		// stops at the first failure
+		let result = client
+			.post_block_commitment_batch(vec![commitment(3), commitment(0), commitment(4)])
+			.await;
+		assert!(result.is_err());
+		assert_eq!(*client.posted.lock().unwrap(), vec![1, 2, 3]);
+
+		Ok(())
+	}
 }
@@ -1,7 +1,12 @@
+use crate::collision::HeightCollisionPolicy;
+use crate::commitment_height::CommitmentHeight;
+use crate::timeout::{with_timeout, OperationTimeouts};
 use crate::{CommitmentStream, McrSettlementClientOperations};
 use movement_types::BlockCommitment;
 use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::sync::{mpsc, RwLock};
 use tokio_stream::wrappers::ReceiverStream;
 use mcr_settlement_config::Config;
@@ -14,6 +19,10 @@ pub struct McrSettlementClient {
 	pub current_height: Arc<RwLock<u64>>,
 	pub block_lead_tolerance: u64,
 	paused_at_height: Arc<RwLock<Option<u64>>>,
+	timeouts: OperationTimeouts,
+	response_delay: Arc<RwLock<Duration>>,
+	height_collision_policy: HeightCollisionPolicy,
+	sends: Arc<AtomicUsize>,
 }
 
 impl McrSettlementClient {
@@ -26,11 +35,46 @@ impl McrSettlementClient {
 			current_height: Arc::new(RwLock::new(0)),
 			block_lead_tolerance: 16,
 			paused_at_height: Arc::new(RwLock::new(None)),
+			timeouts: OperationTimeouts::default(),
+			response_delay: Arc::new(RwLock::new(Duration::ZERO)),
+			height_collision_policy: HeightCollisionPolicy::Submit,
+			sends: Arc::new(AtomicUsize::new(0)),
 		}
 	}
 
 	pub async fn build_with_config(config: Config) -> Result<Self, anyhow::Error> {
-		Ok(Self::new())
+		Ok(Self::new().with_timeouts(OperationTimeouts::from_config(&config.transactions)))
+	}
+
+	pub fn with_timeouts(mut self, timeouts: OperationTimeouts) -> Self {
+		self.timeouts = timeouts;
+		self
+	}
+
+	/// Sets how a commitment posted at a height already holding a different commitment is
+	/// handled. Defaults to [`HeightCollisionPolicy::Submit`], so that overridden commitments
+	/// simulate the chain settling on a different commitment than the one posted.
+	/// [`HeightCollisionPolicy::Reject`] behaves like the default of the Ethereum client.
+	pub fn with_height_collision_policy(mut self, policy: HeightCollisionPolicy) -> Self {
+		self.height_collision_policy = policy;
+		self
+	}
+
+	/// Delays every subsequent operation by the given duration, to simulate a slow node.
+	pub async fn set_response_delay(&self, delay: Duration) {
+		*self.response_delay.write().await = delay;
+	}
+
+	/// Number of settlement transactions sent so far, one per posted commitment or batch.
+	pub fn sends(&self) -> usize {
+		self.sends.load(Ordering::SeqCst)
+	}
+
+	async fn respond(&self) {
+		let delay = *self.response_delay.read().await;
+		if !delay.is_zero() {
+			tokio::time::sleep(delay).await;
+		}
 	}
 
 	/// Overrides the commitment to settle on at given height.
@@ -68,13 +112,12 @@ impl McrSettlementClient {
 	}
 }
 
-#[async_trait::async_trait]
-impl McrSettlementClientOperations for McrSettlementClient {
-	async fn post_block_commitment(
+impl McrSettlementClient {
+	async fn settle_block_commitment(
 		&self,
 		block_commitment: BlockCommitment,
 	) -> Result<(), anyhow::Error> {
-		let height = block_commitment.height;
+		let height = CommitmentHeight::for_block(block_commitment.height)?.raw();
 
 		let settled = {
 			let mut commitments = self.commitments.write().await;
@@ -99,37 +142,81 @@ impl McrSettlementClientOperations for McrSettlementClient {
 
 		Ok(())
 	}
+}
+
+#[async_trait::async_trait]
+impl McrSettlementClientOperations for McrSettlementClient {
+	async fn post_block_commitment(
+		&self,
+		block_commitment: BlockCommitment,
+	) -> Result<(), anyhow::Error> {
+		with_timeout("submit", self.timeouts.submit, async {
+			self.respond().await;
+			let existing = self.commitments.read().await.get(&block_commitment.height).cloned();
+			self.height_collision_policy.check(existing, &block_commitment)?;
+			self.sends.fetch_add(1, Ordering::SeqCst);
+			self.settle_block_commitment(block_commitment).await
+		})
+		.await
+	}
 
 	async fn post_block_commitment_batch(
 		&self,
 		block_commitment: Vec<BlockCommitment>,
 	) -> Result<(), anyhow::Error> {
-		for commitment in block_commitment {
-			self.post_block_commitment(commitment).await?;
-		}
-		Ok(())
+		with_timeout("submit", self.timeouts.submit, async {
+			self.respond().await;
+			// the whole batch is refused on a collision, like a reverted transaction
+			{
+				let commitments = self.commitments.read().await;
+				for commitment in &block_commitment {
+					let existing = commitments.get(&commitment.height).cloned();
+					self.height_collision_policy.check(existing, commitment)?;
+				}
+			}
+			self.sends.fetch_add(1, Ordering::SeqCst);
+			for commitment in block_commitment {
+				self.settle_block_commitment(commitment).await?;
+			}
+			Ok(())
+		})
+		.await
 	}
 
 	async fn stream_block_commitments(&self) -> Result<CommitmentStream, anyhow::Error> {
-		let receiver = self
-			.stream_receiver
-			.lock()
-			.unwrap()
-			.take()
-			.expect("stream_block_commitments already called");
-		Ok(Box::pin(ReceiverStream::new(receiver)))
+		with_timeout("stream setup", self.timeouts.stream_setup, async {
+			self.respond().await;
+			let receiver = self
+				.stream_receiver
+				.lock()
+				.unwrap()
+				.take()
+				.expect("stream_block_commitments already called");
+			Ok(Box::pin(ReceiverStream::new(receiver)) as CommitmentStream)
+		})
+		.await
 	}
 
 	async fn get_commitment_at_height(
 		&self,
 		height: u64,
 	) -> Result<Option<BlockCommitment>, anyhow::Error> {
-		let guard = self.commitments.read().await;
-		Ok(guard.get(&height).cloned())
+		with_timeout("read", self.timeouts.read, async {
+			self.respond().await;
+			let guard = self.commitments.read().await;
+			Ok(CommitmentHeight::from_raw(height)
+				.committed()
+				.and_then(|height| guard.get(&height).cloned()))
+		})
+		.await
 	}
 
 	async fn get_max_tolerable_block_height(&self) -> Result<u64, anyhow::Error> {
-		Ok(*self.current_height.read().await + self.block_lead_tolerance)
+		with_timeout("read", self.timeouts.read, async {
+			self.respond().await;
+			Ok(*self.current_height.read().await + self.block_lead_tolerance)
+		})
+		.await
 	}
 }
 
@@ -137,7 +224,10 @@ impl McrSettlementClientOperations for McrSettlementClient {
 pub mod test {
 
 	use super::*;
-	use movement_types::Commitment;
+	use crate::collision::HeightCollision;
+	use crate::commitment_height::ReservedCommitmentHeight;
+	use crate::timeout::OperationTimedOut;
+	use movement_types::{Commitment, Id};
 
 	use futures::future;
 	use tokio::select;
@@ -184,6 +274,46 @@ pub mod test {
 		Ok(())
 	}
 
+	#[tokio::test]
+	async fn test_post_rollup_commitment() -> Result<(), anyhow::Error> {
+		let client = McrSettlementClient::new();
+		let blocks: Vec<_> = (1..=3)
+			.map(|height| BlockCommitment {
+				height,
+				block_id: Id([height as u8; 32]),
+				commitment: Commitment([height as u8 + 10; 32]),
+			})
+			.collect();
+		client.post_rollup_commitment(&blocks).await?;
+		// sent as a single transaction
+		assert_eq!(client.sends(), 1);
+
+		// a single commitment, for the last block, holds the aggregate of the batch
+		assert_eq!(client.list_committed_heights(0, 10).await?, vec![3]);
+		assert_eq!(
+			client.get_commitment_at_height(3).await?,
+			Some(BlockCommitment {
+				height: 3,
+				block_id: Id([3; 32]),
+				commitment: movement_types::aggregate_commitment(&blocks),
+			})
+		);
+		assert!(!client.is_block_committed(Id([2; 32]), 0..10).await?);
+
+		assert!(client.post_rollup_commitment(&[]).await.is_err());
+		let gap = [blocks[0].clone(), BlockCommitment { height: 5, ..blocks[2].clone() }];
+		assert!(client.post_rollup_commitment(&gap).await.is_err());
+		let wrapping = [
+			BlockCommitment { height: u64::MAX, ..blocks[0].clone() },
+			BlockCommitment { height: 0, ..blocks[1].clone() },
+		];
+		assert!(client.post_rollup_commitment(&wrapping).await.is_err());
+		assert_eq!(client.list_committed_heights(0, 10).await?, vec![3]);
+		assert_eq!(client.sends(), 1);
+
+		Ok(())
+	}
+
 	#[tokio::test]
 	async fn test_stream_block_commitments() -> Result<(), anyhow::Error> {
 		let client = McrSettlementClient::new();
@@ -268,4 +398,155 @@ pub mod test {
 		assert_eq!(stream.next().await.expect("stream has ended")?, commitment2);
 		Ok(())
 	}
+	#[tokio::test]
+	async fn test_list_committed_heights() -> Result<(), anyhow::Error> {
+		let client = McrSettlementClient::new();
+		for height in [1, 3, 5] {
+			client
+				.override_block_commitment(BlockCommitment {
+					height,
+					block_id: Default::default(),
+					commitment: Commitment([height as u8; 32]),
+				})
+				.await;
+		}
+		assert_eq!(client.list_committed_heights(0, 20).await?, vec![1, 3, 5]);
+		assert_eq!(client.list_committed_heights(2, 5).await?, vec![3]);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_is_block_committed() -> Result<(), anyhow::Error> {
+		let client = McrSettlementClient::new();
+		for height in [1, 3, 5] {
+			client
+				.override_block_commitment(BlockCommitment {
+					height,
+					block_id: Id([height as u8; 32]),
+					commitment: Commitment([height as u8; 32]),
+				})
+				.await;
+		}
+		assert!(client.is_block_committed(Id([3; 32]), 0..20).await?);
+		assert!(!client.is_block_committed(Id([7; 32]), 0..20).await?);
+		// committed, but outside of the searched range
+		assert!(!client.is_block_committed(Id([5; 32]), 0..5).await?);
+		Ok(())
+	}
+
+	// on paused time, the response delays and timeouts elapse without waiting, in order
+	#[tokio::test(start_paused = true)]
+	async fn test_operation_timeouts_are_independent() -> Result<(), anyhow::Error> {
+		let slow = Duration::from_millis(200);
+		let short = Duration::from_millis(50);
+		let long = Duration::from_secs(5);
+		let commitment = BlockCommitment {
+			height: 1,
+			block_id: Default::default(),
+			commitment: Commitment([1; 32]),
+		};
+
+		// a short read timeout does not abort slow submissions or the stream setup
+		let client = McrSettlementClient::new().with_timeouts(OperationTimeouts {
+			submit: long,
+			read: short,
+			stream_setup: long,
+		});
+		client.set_response_delay(slow).await;
+		client.post_block_commitment(commitment.clone()).await?;
+		let _stream = client.stream_block_commitments().await?;
+		let err = client.get_commitment_at_height(1).await.unwrap_err();
+		assert_eq!(
+			err.downcast_ref::<OperationTimedOut>(),
+			Some(&OperationTimedOut { operation: "read", timeout: short })
+		);
+
+		// a short submit timeout does not abort reads
+		let client = McrSettlementClient::new().with_timeouts(OperationTimeouts {
+			submit: short,
+			read: long,
+			stream_setup: long,
+		});
+		client.set_response_delay(slow).await;
+		let err = client.post_block_commitment(commitment.clone()).await.unwrap_err();
+		assert_eq!(
+			err.downcast_ref::<OperationTimedOut>(),
+			Some(&OperationTimedOut { operation: "submit", timeout: short })
+		);
+		assert_eq!(client.get_commitment_at_height(1).await?, None);
+
+		// a short stream setup timeout does not abort reads
+		let client = McrSettlementClient::new().with_timeouts(OperationTimeouts {
+			submit: long,
+			read: long,
+			stream_setup: short,
+		});
+		client.set_response_delay(slow).await;
+		let err = client.stream_block_commitments().await.err().expect("stream setup timed out");
+		assert_eq!(
+			err.downcast_ref::<OperationTimedOut>(),
+			Some(&OperationTimedOut { operation: "stream setup", timeout: short })
+		);
+		assert_eq!(client.get_max_tolerable_block_height().await?, 16);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_height_collision_is_rejected() -> Result<(), anyhow::Error> {
+		let client =
+			McrSettlementClient::new().with_height_collision_policy(HeightCollisionPolicy::Reject);
+		let existing = BlockCommitment {
+			height: 1,
+			block_id: Default::default(),
+			commitment: Commitment::test(),
+		};
+		client.override_block_commitment(existing.clone()).await;
+
+		// resubmitting the same commitment is not a collision
+		client.post_block_commitment(existing.clone()).await?;
+
+		let submitted =
+			BlockCommitment { height: 1, block_id: Id([1; 32]), commitment: Commitment([1; 32]) };
+		let err = client.post_block_commitment(submitted.clone()).await.unwrap_err();
+		assert_eq!(
+			err.downcast_ref::<HeightCollision>(),
+			Some(&HeightCollision { existing: existing.clone(), submitted: submitted.clone() })
+		);
+		assert_eq!(client.get_commitment_at_height(1).await?, Some(existing.clone()));
+
+		// a batch with a colliding commitment is refused as a whole
+		let next =
+			BlockCommitment { height: 2, block_id: Id([2; 32]), commitment: Commitment([2; 32]) };
+		let err = client
+			.post_block_commitment_batch(vec![next, submitted.clone()])
+			.await
+			.unwrap_err();
+		assert_eq!(
+			err.downcast_ref::<HeightCollision>(),
+			Some(&HeightCollision { existing, submitted })
+		);
+		assert_eq!(client.get_commitment_at_height(2).await?, None);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_height_zero_is_not_committed() -> Result<(), anyhow::Error> {
+		let client = McrSettlementClient::new();
+		let commitment = |height| BlockCommitment {
+			height,
+			block_id: Default::default(),
+			commitment: Commitment::test(),
+		};
+
+		let err = client.post_block_commitment(commitment(0)).await.unwrap_err();
+		assert_eq!(err.downcast_ref::<ReservedCommitmentHeight>(), Some(&ReservedCommitmentHeight));
+		assert_eq!(client.get_commitment_at_height(0).await?, None);
+
+		client.post_block_commitment(commitment(1)).await?;
+		assert_eq!(client.get_commitment_at_height(1).await?, Some(commitment(1)));
+
+		Ok(())
+	}
 }
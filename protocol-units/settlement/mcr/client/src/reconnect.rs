@@ -0,0 +1,196 @@
+use crate::{AcceptedCommitment, AcceptedStream};
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tokio_stream::StreamExt;
+
+/// Failure of the connection carrying a commitment stream, which re-subscribing may cure.
+/// [`with_reconnect_grace`] only retries errors of this type, other errors, e.g. an event
+/// that fails to decode, are passed through.
+#[derive(Debug, thiserror::Error)]
+#[error("Commitment stream transport failed: {0}")]
+pub struct StreamTransportError(pub anyhow::Error);
+
+struct GraceState<F, I> {
+	stream: AcceptedStream<I>,
+	resubscribe: F,
+	last: Option<I>,
+	last_error: Option<Instant>,
+	// whether the stream was replaced after the previous one ended, with nothing delivered since
+	resubscribed_on_end: bool,
+	done: bool,
+}
+
+/// Tolerates transient transport failures of a commitment stream, see
+/// [`StreamTransportError`].
+///
+/// A single transport error is dropped and the same subscription is polled again. A second
+/// one within `grace` of the first is treated as a dead subscription, and so is the end of
+/// the stream: `resubscribe` is called with the last delivered commitment to replace the
+/// stream, e.g. backfilling what was accepted since. The wrapped stream ends only if a
+/// replacement ends in turn without delivering anything. Other errors are yielded without
+/// affecting the subscription, and so is a failed re-subscription, which ends the stream.
+pub fn with_reconnect_grace<I, F, Fut>(
+	stream: AcceptedStream<I>,
+	grace: Duration,
+	resubscribe: F,
+) -> AcceptedStream<I>
+where
+	I: AcceptedCommitment + Clone + Send + 'static,
+	F: FnMut(Option<I>) -> Fut + Send + 'static,
+	Fut: Future<Output = Result<AcceptedStream<I>, anyhow::Error>> + Send,
+{
+	let state = GraceState {
+		stream,
+		resubscribe,
+		last: None,
+		last_error: None,
+		resubscribed_on_end: false,
+		done: false,
+	};
+	Box::pin(futures::stream::unfold(state, move |mut state| async move {
+		if state.done {
+			return None;
+		}
+		loop {
+			let dead = match state.stream.next().await {
+				Some(Ok(commitment)) => {
+					state.last = Some(commitment.clone());
+					state.resubscribed_on_end = false;
+					return Some((Ok(commitment), state));
+				}
+				Some(Err(err)) if err.is::<StreamTransportError>() => {
+					let now = Instant::now();
+					match state.last_error {
+						Some(at) if now.duration_since(at) <= grace => {
+							tracing::warn!(
+								"Commitment stream failed again ({err}), re-subscribing"
+							);
+							true
+						}
+						_ => {
+							tracing::debug!("Transient commitment stream error ({err}), retrying");
+							state.last_error = Some(now);
+							false
+						}
+					}
+				}
+				Some(Err(err)) => return Some((Err(err), state)),
+				None if state.resubscribed_on_end => return None,
+				None => {
+					tracing::warn!("Commitment stream ended, re-subscribing");
+					state.resubscribed_on_end = true;
+					true
+				}
+			};
+			if dead {
+				match (state.resubscribe)(state.last.clone()).await {
+					Ok(stream) => {
+						state.stream = stream;
+						state.last_error = None;
+					}
+					Err(err) => {
+						state.done = true;
+						return Some((Err(err), state));
+					}
+				}
+			}
+		}
+	}))
+}
+
+#[cfg(test)]
+pub mod test {
+
+	use super::*;
+	use crate::CommitmentStream;
+	use movement_types::{BlockCommitment, Commitment, Id};
+	use std::sync::atomic::{AtomicUsize, Ordering};
+	use std::sync::Arc;
+
+	fn commitment(height: u64) -> Result<BlockCommitment, anyhow::Error> {
+		Ok(BlockCommitment {
+			height,
+			block_id: Id([height as u8; 32]),
+			commitment: Commitment([height as u8; 32]),
+		})
+	}
+
+	fn heights(
+		items: Vec<Result<BlockCommitment, anyhow::Error>>,
+	) -> Result<Vec<u64>, anyhow::Error> {
+		items.into_iter().map(|item| item.map(|commitment| commitment.height)).collect()
+	}
+
+	fn transport_error(message: &'static str) -> Result<BlockCommitment, anyhow::Error> {
+		Err(StreamTransportError(anyhow::anyhow!(message)).into())
+	}
+
+	#[tokio::test]
+	async fn test_transient_error_recovers_without_resubscription() -> Result<(), anyhow::Error> {
+		let stream: CommitmentStream = Box::pin(tokio_stream::iter(vec![
+			commitment(1),
+			transport_error("momentary blip"),
+			commitment(2),
+		]));
+		let resubscribed_from = Arc::new(std::sync::Mutex::new(Vec::new()));
+		let recorded = Arc::clone(&resubscribed_from);
+		let stream = with_reconnect_grace(stream, Duration::from_secs(1), move |last| {
+			recorded.lock().unwrap().push(last.map(|last: BlockCommitment| last.height));
+			async { Ok(Box::pin(tokio_stream::empty()) as CommitmentStream) }
+		});
+
+		assert_eq!(heights(stream.collect().await)?, vec![1, 2]);
+		// only the end of the stream caused a re-subscription
+		assert_eq!(*resubscribed_from.lock().unwrap(), vec![Some(2)]);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_repeated_errors_resubscribe_from_last_height() -> Result<(), anyhow::Error> {
+		let stream: CommitmentStream = Box::pin(tokio_stream::iter(vec![
+			commitment(1),
+			transport_error("connection lost"),
+			transport_error("connection lost"),
+		]));
+		let resubscribed_from = Arc::new(std::sync::Mutex::new(Vec::new()));
+		let recorded = Arc::clone(&resubscribed_from);
+		let stream = with_reconnect_grace(stream, Duration::from_secs(1), move |last| {
+			let mut recorded = recorded.lock().unwrap();
+			let items = if recorded.is_empty() { vec![commitment(2)] } else { vec![] };
+			recorded.push(last.map(|last: BlockCommitment| last.height));
+			async move { Ok(Box::pin(tokio_stream::iter(items)) as CommitmentStream) }
+		});
+
+		assert_eq!(heights(stream.collect().await)?, vec![1, 2]);
+		// then once more when the replacement ended
+		assert_eq!(*resubscribed_from.lock().unwrap(), vec![Some(1), Some(2)]);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_other_errors_are_passed_through() -> Result<(), anyhow::Error> {
+		let stream: CommitmentStream = Box::pin(tokio_stream::iter(vec![
+			commitment(1),
+			Err(anyhow::anyhow!("BlockAccepted event at the reserved height 0")),
+			Err(anyhow::anyhow!("BlockAccepted event at the reserved height 0")),
+			commitment(2),
+		]));
+		let resubscriptions = Arc::new(AtomicUsize::new(0));
+		let counter = Arc::clone(&resubscriptions);
+		let stream = with_reconnect_grace(stream, Duration::from_secs(1), move |_| {
+			counter.fetch_add(1, Ordering::SeqCst);
+			async { Ok(Box::pin(tokio_stream::empty()) as CommitmentStream) }
+		});
+
+		let items: Vec<_> = stream.collect().await;
+		assert_eq!(items.len(), 4);
+		assert!(items[1].is_err() && items[2].is_err());
+		assert!(matches!(&items[3], Ok(commitment) if commitment.height == 2));
+		// once for the end of the stream, not for the errors
+		assert_eq!(resubscriptions.load(Ordering::SeqCst), 1);
+
+		Ok(())
+	}
+}
@@ -0,0 +1,201 @@
+use crate::{AcceptedCommitment, AcceptedStream};
+use movement_types::BlockCommitment;
+use std::collections::BTreeMap;
+use tokio_stream::{Stream, StreamExt};
+
+/// Default of the deepest reorg absorbed by [`with_reorg_detection`].
+pub const DEFAULT_MAX_REORG_DEPTH: u64 = 64;
+
+/// An item of the stream returned by [`with_reorg_detection`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommitmentEvent<I> {
+	Accepted(I),
+	/// A different commitment was accepted at `height`, at or below the last delivered height,
+	/// discarding the `depth` heights from `height` up. The replacing commitment follows as
+	/// [`CommitmentEvent::Accepted`].
+	Reorged {
+		height: u64,
+		depth: u64,
+	},
+}
+
+/// Ends the stream of [`with_reorg_detection`] on a reorg deeper than its maximum.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error(
+	"Reorg at height {height} discards {depth} heights, more than the maximum of {max_reorg_depth}"
+)]
+pub struct DeepReorg {
+	pub height: u64,
+	pub depth: u64,
+	pub max_reorg_depth: u64,
+}
+
+pub type CommitmentEventStream<I> =
+	std::pin::Pin<Box<dyn Stream<Item = Result<CommitmentEvent<I>, anyhow::Error>> + Send>>;
+
+struct ReorgState<I> {
+	stream: AcceptedStream<I>,
+	max_reorg_depth: u64,
+	// commitments delivered within the reorg window, by height
+	delivered: BTreeMap<u64, BlockCommitment>,
+	// replacing commitment to deliver after its reorg event
+	replacing: Option<I>,
+	done: bool,
+}
+
+impl<I> ReorgState<I> {
+	/// Returns the depth of the reorg caused by `commitment`, if it replaces a delivered one.
+	fn reorg_depth(&self, commitment: &BlockCommitment) -> Option<u64> {
+		let (&last_height, _) = self.delivered.last_key_value()?;
+		if commitment.height > last_height {
+			return None;
+		}
+		match self.delivered.get(&commitment.height) {
+			// replayed, not replaced
+			Some(delivered) if delivered == commitment => None,
+			_ => Some(last_height - commitment.height + 1),
+		}
+	}
+
+	fn deliver(&mut self, commitment: &BlockCommitment) {
+		// heights from a reorged height up belong to the discarded branch
+		self.delivered.split_off(&commitment.height);
+		self.delivered.insert(commitment.height, commitment.clone());
+		// only keep what a reorg within the maximum depth can still replace
+		let keep_from = commitment.height.saturating_sub(self.max_reorg_depth);
+		self.delivered = self.delivered.split_off(&keep_from);
+	}
+}
+
+/// Detects reorgs on a stream of accepted commitments in height order.
+///
+/// A commitment differing from the one delivered at its height, or at a height below the
+/// last delivered one, is a reorg. A reorg discarding up to `max_reorg_depth` heights yields
+/// [`CommitmentEvent::Reorged`] before the replacing commitment and the stream goes on. A
+/// deeper one yields a [`DeepReorg`] error and ends the stream. A commitment replayed
+/// unchanged is passed through; replays older than the reorg window should be dropped
+/// beforehand with [`dedup_commitment_stream`](crate::dedup::dedup_commitment_stream).
+/// Other errors are passed through.
+pub fn with_reorg_detection<I>(
+	stream: AcceptedStream<I>,
+	max_reorg_depth: u64,
+) -> CommitmentEventStream<I>
+where
+	I: AcceptedCommitment + Send + 'static,
+{
+	let state = ReorgState {
+		stream,
+		max_reorg_depth,
+		delivered: BTreeMap::new(),
+		replacing: None,
+		done: false,
+	};
+	Box::pin(futures::stream::unfold(state, |mut state| async move {
+		if state.done {
+			return None;
+		}
+		if let Some(commitment) = state.replacing.take() {
+			return Some((Ok(CommitmentEvent::Accepted(commitment)), state));
+		}
+		let commitment = match state.stream.next().await? {
+			Ok(commitment) => commitment,
+			Err(err) => return Some((Err(err), state)),
+		};
+		let block_commitment = commitment.block_commitment();
+		let Some(depth) = state.reorg_depth(block_commitment) else {
+			state.deliver(block_commitment);
+			return Some((Ok(CommitmentEvent::Accepted(commitment)), state));
+		};
+
+		let height = block_commitment.height;
+		if depth > state.max_reorg_depth {
+			state.done = true;
+			let max_reorg_depth = state.max_reorg_depth;
+			return Some((Err(DeepReorg { height, depth, max_reorg_depth }.into()), state));
+		}
+		tracing::warn!("Commitment stream reorged at height {height}, depth {depth}");
+		state.deliver(block_commitment);
+		state.replacing = Some(commitment);
+		Some((Ok(CommitmentEvent::Reorged { height, depth }), state))
+	}))
+}
+
+#[cfg(test)]
+pub mod test {
+
+	use super::*;
+	use crate::CommitmentStream;
+	use movement_types::{Commitment, Id};
+
+	fn commitment(height: u64, branch: u8) -> Result<BlockCommitment, anyhow::Error> {
+		Ok(BlockCommitment {
+			height,
+			block_id: Id([branch; 32]),
+			commitment: Commitment([height as u8; 32]),
+		})
+	}
+
+	fn event_heights(events: &[CommitmentEvent<BlockCommitment>]) -> Vec<(&str, u64)> {
+		events
+			.iter()
+			.map(|event| match event {
+				CommitmentEvent::Accepted(commitment) => ("accepted", commitment.height),
+				CommitmentEvent::Reorged { height, .. } => ("reorged", *height),
+			})
+			.collect()
+	}
+
+	#[tokio::test]
+	async fn test_shallow_reorg_continues() -> Result<(), anyhow::Error> {
+		// heights 3 and 4 are replaced by another branch
+		let stream: CommitmentStream = Box::pin(tokio_stream::iter(vec![
+			commitment(1, 0),
+			commitment(2, 0),
+			commitment(3, 0),
+			commitment(4, 0),
+			commitment(3, 1),
+			commitment(4, 1),
+			commitment(5, 1),
+		]));
+		let events: Vec<_> = with_reorg_detection(stream, 2).collect::<Result<_, _>>().await?;
+
+		assert_eq!(
+			event_heights(&events),
+			vec![
+				("accepted", 1),
+				("accepted", 2),
+				("accepted", 3),
+				("accepted", 4),
+				("reorged", 3),
+				("accepted", 3),
+				("accepted", 4),
+				("accepted", 5),
+			]
+		);
+		assert_eq!(events[4], CommitmentEvent::Reorged { height: 3, depth: 2 });
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_deep_reorg_ends_stream() -> Result<(), anyhow::Error> {
+		let stream: CommitmentStream = Box::pin(tokio_stream::iter(vec![
+			commitment(1, 0),
+			commitment(2, 0),
+			commitment(3, 0),
+			commitment(4, 0),
+			commitment(2, 1),
+			commitment(3, 1),
+		]));
+		let events: Vec<_> = with_reorg_detection(stream, 2).collect().await;
+
+		assert_eq!(events.len(), 5);
+		let err = events.into_iter().last().expect("deep reorg error").unwrap_err();
+		assert_eq!(
+			err.downcast_ref::<DeepReorg>(),
+			Some(&DeepReorg { height: 2, depth: 3, max_reorg_depth: 2 })
+		);
+
+		Ok(())
+	}
+}
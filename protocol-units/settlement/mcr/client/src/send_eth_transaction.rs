@@ -1,10 +1,12 @@
 use crate::eth_client::McrEthConnectorError;
+use crate::util::{retry_with_backoff, BackoffPolicy, Permanent};
 use alloy_contract::CallBuilder;
 use alloy_contract::CallDecoder;
 use alloy_network::Ethereum;
 use alloy::providers::Provider;
 use alloy_transport::{Transport, TransportError};
 use std::marker::PhantomData;
+use std::sync::Mutex;
 
 // Define a rule to verify the error generated when a transaction is send to determine if:
 // * the Transaction must me resend with more gas: return Ok(true)
@@ -58,6 +60,14 @@ impl VerifyRule for SendTransactionErrorRule<InsufficentFunds> {
 	}
 }
 
+/// Adds the margin applied to gas estimates, because initial gas estimates are too low.
+pub fn with_gas_estimate_margin(estimate_gas: u128) -> u128 {
+	estimate_gas + (estimate_gas * 20) / 100
+}
+
+/// Gas price offered by a send attempt, computed from the current gas price of the provider.
+pub type GasPricing<'a> = &'a (dyn Fn(u128) -> u128 + Send + Sync);
+
 pub async fn send_transaction<
 	P: Provider<T, Ethereum> + Clone,
 	T: Transport + Clone,
@@ -67,73 +77,100 @@ pub async fn send_transaction<
 	send_transaction_error_rules: &[Box<dyn VerifyRule>],
 	number_retry: u32,
 	gas_limit: u128,
+	backoff: &BackoffPolicy,
+	gas_pricing: Option<GasPricing<'_>>,
 ) -> Result<(), anyhow::Error> {
+	// `number_retry` counts the attempts, the transaction is not sent at all if it is 0
+	if number_retry == 0 {
+		return Err(McrEthConnectorError::RpcTransactionExecution(
+			"Send commitment Transaction fail because of exceed max retry".to_string(),
+		)
+		.into());
+	}
+
 	//validate gas price.
-	let mut estimate_gas = base_call_builder.estimate_gas().await?;
-	// Add 20% because initial gas estimate are too low.
-	estimate_gas += (estimate_gas * 20) / 100;
+	let estimate_gas = with_gas_estimate_margin(base_call_builder.estimate_gas().await?);
+	let estimate_gas = Mutex::new(estimate_gas);
+	let (estimate_gas, base_call_builder) = (&estimate_gas, &base_call_builder);
+	//increase gas of 10%
+	let bump_gas = move || {
+		let mut estimate_gas = estimate_gas.lock().unwrap();
+		*estimate_gas += (*estimate_gas * 10) / 100;
+	};
 
 	// Sending Transaction automatically can lead to errors that depend on the state for Eth.
 	// It's convenient to manage some of them automatically to avoid to fail commitment Transaction.
 	// I define a first one but other should be added depending on the test with mainnet.
-	for _ in 0..number_retry {
-		let call_builder = base_call_builder.clone().gas(estimate_gas);
-
-		//detect if the gas price doesn't execeed the limit.
-		let gas_price = call_builder.provider.get_gas_price().await?;
-		let transaction_fee_wei = estimate_gas * gas_price;
-		if transaction_fee_wei > gas_limit {
-			return Err(McrEthConnectorError::GasLimitExceed(transaction_fee_wei, gas_limit).into());
-		}
+	let policy = BackoffPolicy { max_attempts: number_retry, ..backoff.clone() };
+	retry_with_backoff(
+		move || async move {
+			let estimate_gas = *estimate_gas.lock().unwrap();
+			let mut call_builder = base_call_builder.clone().gas(estimate_gas);
 
-		//send the Transaction and detect send error.
-		let pending_transaction = match call_builder.send().await {
-			Ok(pending_transaction) => pending_transaction,
-			Err(err) => {
-				//apply defined rules.
-				for rule in send_transaction_error_rules {
-					// Verify all rules. If one rule return true or an error stop verification.
-					// If true retry with more gas else return the error.
-					if rule.verify(&err)? {
-						//increase gas of 10% and retry
-						estimate_gas += (estimate_gas * 10) / 100;
-						tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-						continue;
-					}
+			//detect if the gas price doesn't execeed the limit.
+			let gas_price = call_builder.provider.get_gas_price().await?;
+			// priced again on every attempt, otherwise filled in by the provider
+			let gas_price = match gas_pricing {
+				Some(gas_pricing) => {
+					let gas_price = gas_pricing(gas_price);
+					call_builder = call_builder.gas_price(gas_price);
+					gas_price
 				}
-
-				return Err(McrEthConnectorError::from(err).into());
+				None => gas_price,
+			};
+			let transaction_fee_wei = estimate_gas * gas_price;
+			if transaction_fee_wei > gas_limit {
+				let err = McrEthConnectorError::GasLimitExceed(transaction_fee_wei, gas_limit);
+				return Err(Permanent(err.into()).into());
 			}
-		};
 
-		match pending_transaction.get_receipt().await {
-			// Transaction execution fail
-			Ok(transaction_receipt) if !transaction_receipt.status() => {
-				tracing::debug!(
-					"transaction_receipt.gas_used: {} / estimate_gas: {estimate_gas}",
-					transaction_receipt.gas_used
-				);
-				if transaction_receipt.gas_used == estimate_gas {
-					tracing::warn!("Send commitment Transaction  fail because of insufficient gas, receipt:{transaction_receipt:?} ");
-					estimate_gas += (estimate_gas * 10) / 100;
-					continue;
-				} else {
-					return Err(McrEthConnectorError::RpcTransactionExecution(format!(
-						"Send commitment Transaction fail, abort Transaction, receipt:{transaction_receipt:?}"
-					))
-					.into());
+			//send the Transaction and detect send error.
+			let pending_transaction = match call_builder.send().await {
+				Ok(pending_transaction) => pending_transaction,
+				Err(err) => {
+					//apply defined rules.
+					for rule in send_transaction_error_rules {
+						// Verify all rules. If one rule return true or an error stop verification.
+						// If true retry with more gas else return the error.
+						if rule.verify(&err).map_err(|err| Permanent(err.into()))? {
+							bump_gas();
+							return Err(McrEthConnectorError::from(err).into());
+						}
+					}
+
+					return Err(Permanent(McrEthConnectorError::from(err).into()).into());
 				}
-			}
-			Ok(_) => return Ok(()),
-			Err(err) => {
-				return Err(McrEthConnectorError::RpcTransactionExecution(err.to_string()).into())
-			}
-		};
-	}
+			};
 
-	//Max retry exceed
-	Err(McrEthConnectorError::RpcTransactionExecution(
-		"Send commitment Transaction fail because of exceed max retry".to_string(),
+			match pending_transaction.get_receipt().await {
+				// Transaction execution fail
+				Ok(transaction_receipt) if !transaction_receipt.status() => {
+					tracing::debug!(
+						"transaction_receipt.gas_used: {} / estimate_gas: {estimate_gas}",
+						transaction_receipt.gas_used
+					);
+					if transaction_receipt.gas_used == estimate_gas {
+						tracing::warn!("Send commitment Transaction  fail because of insufficient gas, receipt:{transaction_receipt:?} ");
+						bump_gas();
+						Err(McrEthConnectorError::RpcTransactionExecution(format!(
+							"Send commitment Transaction fail because of insufficient gas, receipt:{transaction_receipt:?}"
+						))
+						.into())
+					} else {
+						let err = McrEthConnectorError::RpcTransactionExecution(format!(
+							"Send commitment Transaction fail, abort Transaction, receipt:{transaction_receipt:?}"
+						));
+						Err(Permanent(err.into()).into())
+					}
+				}
+				Ok(_) => Ok(()),
+				Err(err) => {
+					let err = McrEthConnectorError::RpcTransactionExecution(err.to_string());
+					Err(Permanent(err.into()).into())
+				}
+			}
+		},
+		&policy,
 	)
-	.into())
+	.await
 }
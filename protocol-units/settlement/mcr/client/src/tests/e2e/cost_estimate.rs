@@ -0,0 +1,36 @@
+use crate::eth_client::{Client, CostEstimate};
+use crate::tests::e2e::settlement_config;
+use movement_types::{BlockCommitment, Commitment, Id};
+
+fn block_commitments(count: u64) -> Vec<BlockCommitment> {
+	(1..=count)
+		.map(|height| BlockCommitment {
+			height,
+			block_id: Id([height as u8; 32]),
+			commitment: Commitment([height as u8; 32]),
+		})
+		.collect()
+}
+
+#[tokio::test]
+pub async fn test_estimate_cost_scales_with_batch_size() -> Result<(), anyhow::Error> {
+	let config = settlement_config().await?;
+
+	let client = Client::build_with_config(config).await?;
+	let single = client.estimate_cost_wei(&block_commitments(1)).await?;
+	let batch = client.estimate_cost_wei(&block_commitments(8)).await?;
+
+	for estimate in [single, batch] {
+		assert_eq!(estimate, CostEstimate::new(estimate.gas_units, estimate.gas_price));
+	}
+	assert!(batch.gas_units > single.gas_units);
+
+	// the gas price may move between the two estimates, compare at a fixed one
+	let gas_price = single.gas_price;
+	assert!(
+		CostEstimate::new(batch.gas_units, gas_price).total_wei
+			> CostEstimate::new(single.gas_units, gas_price).total_wei
+	);
+
+	Ok(())
+}
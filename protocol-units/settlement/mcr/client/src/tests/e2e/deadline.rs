@@ -0,0 +1,47 @@
+use crate::deadline::{settlement_deadline, DeadlineMissed};
+use crate::eth_client::Client;
+use crate::tests::e2e::settlement_config;
+use crate::McrSettlementClientOperations;
+use movement_types::{BlockCommitment, Commitment, Id};
+use std::time::{Duration, Instant, SystemTime};
+
+#[tokio::test]
+pub async fn test_post_before_deadline() -> Result<(), anyhow::Error> {
+	let config = settlement_config().await?;
+
+	let max_settlement_delay = Duration::from_secs(60);
+	let client = Client::build_with_config(config)
+		.await?
+		.with_max_settlement_delay(max_settlement_delay);
+
+	// a block produced long ago is closer to its deadline than a block just produced
+	let now = SystemTime::now();
+	let relaxed = settlement_deadline(now, max_settlement_delay);
+	let early = settlement_deadline(now - Duration::from_secs(50), max_settlement_delay);
+	let relaxed_gas_price = client.deadline_gas_price(relaxed).await?.unwrap();
+	let early_gas_price = client.deadline_gas_price(early).await?.unwrap();
+	assert!(early_gas_price > relaxed_gas_price);
+
+	let height = client.get_max_tolerable_block_height().await?;
+	let commitment =
+		BlockCommitment { height, block_id: Id([7; 32]), commitment: Commitment([8; 32]) };
+	client.post_before_deadline(commitment, early).await?;
+
+	// a past deadline fails without submitting
+	let past = settlement_deadline(now - Duration::from_secs(120), max_settlement_delay);
+	let commitment = BlockCommitment {
+		height: height + 1,
+		block_id: Id([9; 32]),
+		commitment: Commitment([10; 32]),
+	};
+	let started = Instant::now();
+	let err = client.post_before_deadline(commitment, past).await.unwrap_err();
+	assert!(started.elapsed() < Duration::from_secs(1));
+	assert_eq!(
+		err.downcast_ref::<DeadlineMissed>(),
+		Some(&DeadlineMissed { height: height + 1, deadline: past })
+	);
+	assert_eq!(client.get_commitment_at_height(height + 1).await?, None);
+
+	Ok(())
+}
@@ -1 +1,19 @@
-pub mod genesis_ceremony;
\ No newline at end of file
+pub mod cost_estimate;
+pub mod deadline;
+pub mod genesis_ceremony;
+pub mod settlement_meta;
+pub mod signer_nonce;
+
+use godfig::{backend::config_file::ConfigFile, Godfig};
+use mcr_settlement_config::Config;
+
+/// Waits for the MCR settlement config of the local test network to be ready and loads it.
+pub async fn settlement_config() -> Result<Config, anyhow::Error> {
+	let dot_movement = dot_movement::DotMovement::try_from_env()?;
+	let config_file = dot_movement.try_get_or_create_config_file().await?;
+
+	// get a matching godfig object
+	let godfig: Godfig<Config, ConfigFile> =
+		Godfig::new(ConfigFile::new(config_file), vec!["mcr_settlement".to_string()]);
+	Ok(godfig.try_wait_for_ready().await?)
+}
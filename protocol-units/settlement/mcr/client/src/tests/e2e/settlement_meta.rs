@@ -0,0 +1,38 @@
+use crate::eth_client::Client;
+use crate::tests::e2e::settlement_config;
+use crate::McrSettlementClientOperations;
+use alloy::providers::Provider;
+use anyhow::Context;
+use movement_types::{BlockCommitment, Commitment, Id};
+use std::time::Duration;
+use tokio_stream::StreamExt;
+
+#[tokio::test]
+pub async fn test_settled_commitment_meta_matches_transaction() -> Result<(), anyhow::Error> {
+	let config = settlement_config().await?;
+
+	let client = Client::build_with_config(config).await?;
+	let mut stream = client.stream_settled_commitments().await?;
+
+	let height = client.get_max_tolerable_block_height().await?;
+	let commitment =
+		BlockCommitment { height, block_id: Id([3; 32]), commitment: Commitment([4; 32]) };
+	client.post_block_commitment(commitment.clone()).await?;
+
+	let settled = tokio::time::timeout(Duration::from_secs(30), stream.next())
+		.await?
+		.context("commitment stream ended")??;
+	assert_eq!(settled.commitment, commitment);
+	let meta = settled.meta.context("accepted event without settlement metadata")?;
+
+	let receipt = client
+		.rpc_provider
+		.get_transaction_receipt(meta.l1_tx_hash)
+		.await?
+		.context("no receipt for the settlement transaction")?;
+	assert_eq!(receipt.transaction_hash, meta.l1_tx_hash);
+	assert_eq!(receipt.block_number, Some(meta.l1_block_number));
+	assert_eq!(receipt.from, client.signer_address);
+
+	Ok(())
+}
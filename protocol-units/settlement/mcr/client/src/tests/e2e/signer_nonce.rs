@@ -0,0 +1,33 @@
+use crate::eth_client::Client;
+use crate::tests::e2e::settlement_config;
+use alloy::providers::Provider;
+use alloy::rpc::types::TransactionRequest;
+use alloy_network::TransactionBuilder;
+use alloy_primitives::U256;
+use anyhow::Context;
+
+#[tokio::test]
+pub async fn test_signer_pending_nonce_advances() -> Result<(), anyhow::Error> {
+	let mut config = settlement_config().await?;
+
+	// sign with a well-known account no other test sends from, so that no transaction of a
+	// concurrent test lands between the two nonce reads
+	let testing = config.testing.as_ref().context("No testing config")?;
+	config.settle.signer_private_key = testing
+		.well_known_account_private_keys
+		.last()
+		.context("No well known account")?
+		.clone();
+
+	let client = Client::build_with_config(config).await?;
+	let nonce = client.signer_pending_nonce().await?;
+
+	let transaction = TransactionRequest::default()
+		.with_to(client.signer_address)
+		.with_value(U256::ZERO);
+	client.rpc_provider.send_transaction(transaction).await?.watch().await?;
+
+	assert_eq!(client.signer_pending_nonce().await?, nonce + 1);
+
+	Ok(())
+}
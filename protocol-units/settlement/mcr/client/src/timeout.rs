@@ -0,0 +1,51 @@
+use mcr_settlement_config::common::transactions::Config as TransactionsConfig;
+use std::future::Future;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Error returned when a settlement client operation exceeds its configured timeout.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("MCR settlement {operation} timed out after {timeout:?}")]
+pub struct OperationTimedOut {
+	pub operation: &'static str,
+	pub timeout: Duration,
+}
+
+/// Independent timeouts for the kinds of operations of a settlement client, so that
+/// aggressive read timeouts do not abort legitimately slow submissions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OperationTimeouts {
+	/// Applies to posting commitments, single or batched.
+	pub submit: Duration,
+	/// Applies to contract reads.
+	pub read: Duration,
+	/// Applies to subscribing to the commitment event stream, not to the stream itself.
+	pub stream_setup: Duration,
+}
+
+impl OperationTimeouts {
+	pub fn from_config(config: &TransactionsConfig) -> Self {
+		Self {
+			submit: Duration::from_millis(config.submit_timeout),
+			read: Duration::from_millis(config.read_timeout),
+			stream_setup: Duration::from_millis(config.stream_setup_timeout),
+		}
+	}
+}
+
+impl Default for OperationTimeouts {
+	fn default() -> Self {
+		Self::from_config(&TransactionsConfig::default())
+	}
+}
+
+/// Runs the operation, failing with [`OperationTimedOut`] if it does not complete in time.
+pub(crate) async fn with_timeout<T>(
+	operation: &'static str,
+	timeout: Duration,
+	future: impl Future<Output = Result<T, anyhow::Error>>,
+) -> Result<T, anyhow::Error> {
+	tokio::time::timeout(timeout, future)
+		.await
+		.map_err(|_| OperationTimedOut { operation, timeout })?
+}
@@ -0,0 +1,129 @@
+pub use mcr_settlement_config::BackoffPolicy;
+use std::future::Future;
+use std::time::Duration;
+
+/// Error of an attempt of [`retry_with_backoff`] that is returned right away instead of
+/// being retried, e.g. a rejection that another attempt cannot fix.
+#[derive(Debug, thiserror::Error)]
+#[error(transparent)]
+pub struct Permanent(pub anyhow::Error);
+
+/// Delay before the retry following the failed attempt `attempt`, counted from 0, with a
+/// random fraction of up to `policy.jitter` taken off [`BackoffPolicy::delay`].
+pub fn backoff_delay(policy: &BackoffPolicy, attempt: u32) -> Duration {
+	let jitter = policy.jitter.clamp(0.0, 1.0) * rand::random::<f64>();
+	policy.delay(attempt).mul_f64(1.0 - jitter)
+}
+
+/// Runs `op` until it succeeds, waiting [`backoff_delay`] between attempts, for at most
+/// `policy.max_attempts` attempts. Returns the error of the last attempt once they are
+/// exhausted, or right away the error wrapped in a [`Permanent`] error, unwrapped.
+pub async fn retry_with_backoff<T, F, Fut>(
+	mut op: F,
+	policy: &BackoffPolicy,
+) -> Result<T, anyhow::Error>
+where
+	F: FnMut() -> Fut,
+	Fut: Future<Output = Result<T, anyhow::Error>>,
+{
+	let mut attempt = 0;
+	loop {
+		let err = match op().await {
+			Ok(value) => return Ok(value),
+			Err(err) => match err.downcast::<Permanent>() {
+				Ok(Permanent(err)) => return Err(err),
+				Err(err) => err,
+			},
+		};
+		attempt += 1;
+		if attempt >= policy.max_attempts {
+			return Err(err);
+		}
+		let delay = backoff_delay(policy, attempt - 1);
+		tracing::debug!(
+			"Attempt {attempt} of {} failed ({err}), retrying in {delay:?}",
+			policy.max_attempts
+		);
+		tokio::time::sleep(delay).await;
+	}
+}
+
+#[cfg(test)]
+pub mod test {
+
+	use super::*;
+	use std::sync::atomic::{AtomicU32, Ordering};
+
+	fn policy(max_attempts: u32) -> BackoffPolicy {
+		BackoffPolicy { base_ms: 1, max_ms: 10, multiplier: 3.0, jitter: 0.0, max_attempts }
+	}
+
+	#[test]
+	fn test_delay_sequence() {
+		let delays: Vec<_> =
+			(0..5).map(|attempt| backoff_delay(&policy(5), attempt).as_millis()).collect();
+		// grows by the multiplier up to the maximum
+		assert_eq!(delays, vec![1, 3, 9, 10, 10]);
+
+		let jittered = BackoffPolicy { base_ms: 1000, jitter: 0.5, ..policy(5) };
+		for attempt in 0..5 {
+			let delay = backoff_delay(&jittered, attempt);
+			assert!(delay <= jittered.delay(attempt));
+			assert!(delay >= jittered.delay(attempt) / 2);
+		}
+	}
+
+	#[tokio::test]
+	async fn test_max_attempts_terminates() -> Result<(), anyhow::Error> {
+		let attempts = AtomicU32::new(0);
+		let result: Result<(), _> = retry_with_backoff(
+			|| async {
+				let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+				Err(anyhow::anyhow!("attempt {attempt} failed"))
+			},
+			&policy(4),
+		)
+		.await;
+		assert_eq!(result.unwrap_err().to_string(), "attempt 3 failed");
+		assert_eq!(attempts.load(Ordering::SeqCst), 4);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_retries_until_success() -> Result<(), anyhow::Error> {
+		let attempts = AtomicU32::new(0);
+		let value = retry_with_backoff(
+			|| async {
+				match attempts.fetch_add(1, Ordering::SeqCst) {
+					0 | 1 => Err(anyhow::anyhow!("transient")),
+					attempt => Ok(attempt),
+				}
+			},
+			&policy(4),
+		)
+		.await?;
+		assert_eq!(value, 2);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_permanent_error_is_not_retried() -> Result<(), anyhow::Error> {
+		let attempts = AtomicU32::new(0);
+		let result: Result<(), _> = retry_with_backoff(
+			|| async {
+				attempts.fetch_add(1, Ordering::SeqCst);
+				Err(Permanent(anyhow::anyhow!("rejected")).into())
+			},
+			&policy(4),
+		)
+		.await;
+		let err = result.unwrap_err();
+		assert!(!err.is::<Permanent>());
+		assert_eq!(err.to_string(), "rejected");
+		assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+		Ok(())
+	}
+}
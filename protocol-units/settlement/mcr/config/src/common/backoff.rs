@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+use godfig::env_short_default;
+use std::time::Duration;
+
+/// Exponential backoff between the attempts of an operation retried by the MCR client.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackoffPolicy {
+	/// Delay before the first retry, in milliseconds.
+	#[serde(default = "default_backoff_base_ms")]
+	pub base_ms: u64,
+	/// Longest delay between two attempts, in milliseconds.
+	#[serde(default = "default_backoff_max_ms")]
+	pub max_ms: u64,
+	/// Factor applied to the delay after each retry.
+	#[serde(default = "default_backoff_multiplier")]
+	pub multiplier: f64,
+	/// Fraction of each delay, in `[0.0, 1.0]`, randomly taken off so that clients failing
+	/// together do not retry in lockstep.
+	#[serde(default = "default_backoff_jitter")]
+	pub jitter: f64,
+	/// Attempts made in total, the first one included.
+	#[serde(default = "default_backoff_max_attempts")]
+	pub max_attempts: u32,
+}
+
+env_short_default!(
+	default_backoff_base_ms,
+	u64,
+	500 as u64
+);
+
+env_short_default!(
+	default_backoff_max_ms,
+	u64,
+	30_000 as u64
+);
+
+env_short_default!(
+	default_backoff_multiplier,
+	f64,
+	2.0
+);
+
+env_short_default!(
+	default_backoff_jitter,
+	f64,
+	0.2
+);
+
+env_short_default!(
+	default_backoff_max_attempts,
+	u32,
+	5 as u32
+);
+
+impl Default for BackoffPolicy {
+	fn default() -> Self {
+		BackoffPolicy {
+			base_ms: default_backoff_base_ms(),
+			max_ms: default_backoff_max_ms(),
+			multiplier: default_backoff_multiplier(),
+			jitter: default_backoff_jitter(),
+			max_attempts: default_backoff_max_attempts(),
+		}
+	}
+}
+
+impl BackoffPolicy {
+
+	/// Delay before the retry following the failed attempt `attempt`, counted from 0,
+	/// without jitter: `base_ms * multiplier^attempt`, capped at `max_ms`.
+	pub fn delay(&self, attempt: u32) -> Duration {
+		let delay_ms = self.base_ms as f64 * self.multiplier.powi(attempt as i32);
+		Duration::from_millis(delay_ms.min(self.max_ms as f64) as u64)
+	}
+
+}
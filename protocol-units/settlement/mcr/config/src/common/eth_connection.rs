@@ -24,6 +24,16 @@ pub struct Config {
 
 	#[serde(default)]
 	pub eth_chain_id: u64,
+
+	/// Window in milliseconds within which a second transport error on the WS event stream
+	/// triggers a full re-subscription. A single one is retried on the same subscription.
+	#[serde(default = "default_eth_ws_reconnect_grace_ms")]
+	pub eth_ws_reconnect_grace_ms: u64,
+
+	/// Deepest reorg, in commitment heights, the commitment event stream absorbs. A deeper
+	/// reorg ends the stream with an error so that an operator can intervene.
+	#[serde(default = "default_eth_max_reorg_depth")]
+	pub eth_max_reorg_depth: u64,
 }
 
 env_default!(
@@ -75,6 +85,20 @@ env_default!(
 	0
 );
 
+env_default!(
+	default_eth_ws_reconnect_grace_ms,
+	"ETH_WS_RECONNECT_GRACE_MS",
+	u64,
+	1000
+);
+
+env_default!(
+	default_eth_max_reorg_depth,
+	"ETH_MAX_REORG_DEPTH",
+	u64,
+	64
+);
+
 impl Default for Config {
 	fn default() -> Self {
 		Config {
@@ -86,6 +110,8 @@ impl Default for Config {
 			eth_ws_connection_hostname: default_eth_ws_connection_hostname(),
 			eth_ws_connection_port: default_eth_ws_connection_port(),
 			eth_chain_id: default_eth_chain_id(),
+			eth_ws_reconnect_grace_ms: default_eth_ws_reconnect_grace_ms(),
+			eth_max_reorg_depth: default_eth_max_reorg_depth(),
 		}
 	}
 }
@@ -3,4 +3,5 @@ pub mod eth_connection;
 pub mod settlement;
 pub mod staking;
 pub mod deploy;
-pub mod transactions;
\ No newline at end of file
+pub mod transactions;
+pub mod backoff;
\ No newline at end of file
@@ -2,6 +2,9 @@ use serde::{Deserialize, Serialize};
 use godfig::env_default;
 use alloy::signers::local::PrivateKeySigner;
 use std::env;
+use std::fs;
+
+use crate::ConfigError;
 
 const DEFAULT_MCR_CONTRACT_ADDRESS: &str = "0x0";
 
@@ -9,6 +12,8 @@ const DEFAULT_MCR_CONTRACT_ADDRESS: &str = "0x0";
 pub struct Config {
 	#[serde(default = "default_should_settle")]
 	pub should_settle : bool,
+	/// The hex-encoded signer private key, with or without `0x` prefix. Instead of the key
+	/// itself, `file:<path>` reads it from a file and `env:<VAR>` from an environment variable.
     #[serde(default = "default_signer_private_key")]
 	pub signer_private_key: String,
 	#[serde(default = "default_mcr_contract_address")]
@@ -41,4 +46,109 @@ impl Default for Config {
 			mcr_contract_address: default_mcr_contract_address(),
 		}
 	}
-}
\ No newline at end of file
+}
+
+impl Config {
+	/// Resolves [`Config::signer_private_key`], following any indirection, to its 64 hex
+	/// characters without `0x` prefix.
+	pub fn signer_private_key_hex(&self) -> Result<String, ConfigError> {
+		let key = if let Some(path) = self.signer_private_key.strip_prefix("file:") {
+			fs::read_to_string(path).map_err(|e| ConfigError::InvalidSignerKey {
+				reason: format!("failed to read key file {:?}: {}", path, e),
+			})?
+		} else if let Some(var) = self.signer_private_key.strip_prefix("env:") {
+			env::var(var).map_err(|e| ConfigError::InvalidSignerKey {
+				reason: format!("failed to read key variable {}: {}", var, e),
+			})?
+		} else {
+			self.signer_private_key.clone()
+		};
+		normalize_private_key(&key)
+	}
+
+	/// Builds the signer from [`Config::signer_private_key`].
+	pub fn signer(&self) -> Result<PrivateKeySigner, ConfigError> {
+		self.signer_private_key_hex()?
+			.parse::<PrivateKeySigner>()
+			.map_err(|e| ConfigError::InvalidSignerKey { reason: e.to_string() })
+	}
+}
+
+/// Strips surrounding whitespace and an optional `0x` prefix from a hex-encoded private key,
+/// and checks that 64 hex characters remain. The reason of an error never includes the key.
+pub fn normalize_private_key(key: &str) -> Result<String, ConfigError> {
+	let key = key.trim();
+	let key = key.strip_prefix("0x").or_else(|| key.strip_prefix("0X")).unwrap_or(key);
+	if key.len() != 64 {
+		return Err(ConfigError::InvalidSignerKey {
+			reason: format!("expected 64 hex characters, found {}", key.len()),
+		});
+	}
+	if let Some(position) = key.find(|c: char| !c.is_ascii_hexdigit()) {
+		return Err(ConfigError::InvalidSignerKey {
+			reason: format!("non-hex character at position {}", position),
+		});
+	}
+	Ok(key.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+pub mod test {
+	use super::*;
+	use std::io::Write;
+
+	const KEY: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+
+	fn config(signer_private_key: &str) -> Config {
+		Config { signer_private_key: signer_private_key.to_string(), ..Config::default() }
+	}
+
+	#[test]
+	fn test_prefixed_and_unprefixed_keys() {
+		assert_eq!(config(&format!("0x{}", KEY)).signer_private_key_hex(), Ok(KEY.to_string()));
+		assert_eq!(config(KEY).signer_private_key_hex(), Ok(KEY.to_string()));
+		assert_eq!(config(&KEY.to_ascii_uppercase()).signer_private_key_hex(), Ok(KEY.to_string()));
+		assert!(config(KEY).signer().is_ok());
+	}
+
+	#[test]
+	fn test_malformed_keys() {
+		assert_eq!(
+			config(&format!("0x{}", &KEY[..62])).signer_private_key_hex(),
+			Err(ConfigError::InvalidSignerKey {
+				reason: "expected 64 hex characters, found 62".to_string()
+			})
+		);
+		assert_eq!(
+			config(&format!("{}zz", &KEY[..62])).signer_private_key_hex(),
+			Err(ConfigError::InvalidSignerKey {
+				reason: "non-hex character at position 62".to_string()
+			})
+		);
+		// 0x prefix only once
+		assert!(config(&format!("0x0x{}", &KEY[..60])).signer_private_key_hex().is_err());
+	}
+
+	#[test]
+	fn test_key_indirection() -> Result<(), anyhow::Error> {
+		let mut file = tempfile::NamedTempFile::new()?;
+		writeln!(file, "0x{}", KEY)?;
+		let path = file.path().to_str().unwrap();
+		assert_eq!(config(&format!("file:{}", path)).signer_private_key_hex(), Ok(KEY.to_string()));
+		assert!(matches!(
+			config("file:/nonexistent/signer.key").signer_private_key_hex(),
+			Err(ConfigError::InvalidSignerKey { .. })
+		));
+
+		env::set_var("MCR_TEST_INDIRECT_SIGNER_PRIVATE_KEY", KEY);
+		assert_eq!(
+			config("env:MCR_TEST_INDIRECT_SIGNER_PRIVATE_KEY").signer_private_key_hex(),
+			Ok(KEY.to_string())
+		);
+		assert!(config("env:MCR_TEST_UNSET_SIGNER_PRIVATE_KEY")
+			.signer_private_key_hex()
+			.is_err());
+
+		Ok(())
+	}
+}
@@ -9,8 +9,25 @@ pub struct Config {
 	/// Timeout for batching blocks, in milliseconds
 	#[serde(default = "default_batch_timeout")]
 	pub batch_timeout: u64,
+	/// Attempts at sending a commitment transaction, the first included. A failed attempt is
+	/// retried with more gas when the error may be overcome that way. With 0, commitments
+	/// fail without being sent.
 	#[serde(default = "default_transaction_send_retries")]
 	pub transaction_send_retries: u32,
+	/// Timeout for submitting commitments, in milliseconds.
+	/// Generous by default: a submission includes gas estimation and resends.
+	#[serde(default = "default_submit_timeout")]
+	pub submit_timeout: u64,
+	/// Timeout for contract reads, in milliseconds.
+	#[serde(default = "default_read_timeout")]
+	pub read_timeout: u64,
+	/// Timeout for setting up the commitment event stream, in milliseconds.
+	#[serde(default = "default_stream_setup_timeout")]
+	pub stream_setup_timeout: u64,
+	/// Time allowed to settle a block after it is produced, in milliseconds.
+	/// Submissions escalate their gas price as the end of this delay approaches.
+	#[serde(default = "default_max_settlement_delay")]
+	pub max_settlement_delay: u64,
 }
 
 env_short_default!(
@@ -31,12 +48,40 @@ env_short_default!(
     10 as u32
 );
 
+env_short_default!(
+    default_submit_timeout,
+    u64,
+    120_000 as u64
+);
+
+env_short_default!(
+    default_read_timeout,
+    u64,
+    10_000 as u64
+);
+
+env_short_default!(
+    default_stream_setup_timeout,
+    u64,
+    30_000 as u64
+);
+
+env_short_default!(
+    default_max_settlement_delay,
+    u64,
+    60_000 as u64
+);
+
 impl Default for Config {
     fn default() -> Self {
         Config {
             gas_limit: default_gas_limit(),
             batch_timeout: default_batch_timeout(),
             transaction_send_retries: default_transaction_send_retries(),
+            submit_timeout: default_submit_timeout(),
+            read_timeout: default_read_timeout(),
+            stream_setup_timeout: default_stream_setup_timeout(),
+            max_settlement_delay: default_max_settlement_delay(),
         }
     }
 }
\ No newline at end of file
@@ -1,8 +1,12 @@
 //! This crate provides configuration parameters for the MCR settlement
 //! component of a Movement node.
+use alloy::primitives::Address;
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 pub mod common;
 
+pub use common::backoff::BackoffPolicy;
+
 use godfig::env_short_default;
 use common::deploy::maybe_deploy;
 use common::testing::default_maybe_testing;
@@ -21,6 +25,11 @@ pub struct Config {
 	#[serde(default)]
 	pub transactions : common::transactions::Config,
 
+	/// Backoff of the operations retried by the client: re-subscriptions to the event stream
+	/// and transaction resends.
+	#[serde(default)]
+	pub backoff : BackoffPolicy,
+
 	/// Whether or not to attempt to run locally.
 	#[serde(default = "maybe_run_local")]
 	pub maybe_run_local : bool,
@@ -35,6 +44,30 @@ pub struct Config {
 
 }
 
+/// Errors reported by [`Config::validate`], naming the offending field.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+	#[error(
+		"settle.signer_private_key is not a valid hex-encoded secp256k1 private key: {reason}"
+	)]
+	InvalidSignerKey { reason: String },
+	#[error("settle.mcr_contract_address {0:?} is not a valid 20-byte hex address: {1}")]
+	InvalidContractAddress(String, String),
+	#[error("ETH RPC connection url {0:?} is invalid: {1}")]
+	InvalidRpcUrl(String, String),
+	#[error("ETH WS connection url {0:?} is invalid: {1}")]
+	InvalidWsUrl(String, String),
+}
+
+/// Reason reported when a connection url has the protocol of the other connection, which
+/// usually means that the rpc and ws settings were swapped.
+fn swapped_protocol_reason(field: &str, protocol: &str, expected: &str) -> String {
+	format!(
+		"eth_connection.{field} is {protocol:?}, expected {expected}: \
+		the rpc and ws connection settings appear to be swapped"
+	)
+}
+
 env_short_default!(
 	maybe_run_local,
 	bool,
@@ -59,6 +92,67 @@ impl Config {
 		self.maybe_run_local
 	}
 
+	/// Checks the fields the settlement client parses at build time, so that a bad value
+	/// is reported by name rather than as a low-level parse error.
+	pub fn validate(&self) -> Result<(), ConfigError> {
+		// the key itself is deliberately left out of the error message
+		self.settle.signer()?;
+
+		self.settle.mcr_contract_address.parse::<Address>().map_err(|e| {
+			ConfigError::InvalidContractAddress(
+				self.settle.mcr_contract_address.clone(),
+				e.to_string(),
+			)
+		})?;
+
+		let eth_connection = &self.eth_connection;
+		let rpc_url = self.eth_rpc_connection_url();
+		match eth_connection.eth_rpc_connection_protocol.as_str() {
+			"http" | "https" => {}
+			protocol @ ("ws" | "wss") => {
+				return Err(ConfigError::InvalidRpcUrl(
+					rpc_url,
+					swapped_protocol_reason(
+						"eth_rpc_connection_protocol",
+						protocol,
+						"http or https",
+					),
+				))
+			}
+			protocol => {
+				return Err(ConfigError::InvalidRpcUrl(
+					rpc_url,
+					format!("unsupported protocol {:?}, expected http or https", protocol),
+				))
+			}
+		}
+		if eth_connection.eth_rpc_connection_hostname.is_empty() {
+			return Err(ConfigError::InvalidRpcUrl(rpc_url, "hostname is empty".to_string()));
+		}
+
+		let ws_url = self.eth_ws_connection_url();
+		match eth_connection.eth_ws_connection_protocol.as_str() {
+			"ws" | "wss" => {}
+			protocol @ ("http" | "https") => {
+				return Err(ConfigError::InvalidWsUrl(
+					ws_url,
+					swapped_protocol_reason("eth_ws_connection_protocol", protocol, "ws or wss"),
+				))
+			}
+			protocol => {
+				return Err(ConfigError::InvalidWsUrl(
+					ws_url,
+					format!("unsupported protocol {:?}, expected ws or wss", protocol),
+				))
+			}
+		}
+		if eth_connection.eth_ws_connection_hostname.is_empty() {
+			return Err(ConfigError::InvalidWsUrl(ws_url, "hostname is empty".to_string()));
+		}
+
+		Ok(())
+	}
+
 }
 
 impl Default for Config {
@@ -67,9 +161,87 @@ impl Default for Config {
 			eth_connection : common::eth_connection::Config::default(),
 			settle : common::settlement::Config::default(),
 			transactions : common::transactions::Config::default(),
+			backoff : BackoffPolicy::default(),
 			maybe_run_local : maybe_run_local(),
 			deploy : maybe_deploy(),
 			testing : default_maybe_testing()
 		}
 	}
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+pub mod test {
+	use super::*;
+
+	fn valid_config() -> Config {
+		let mut config = Config::default();
+		config.settle.signer_private_key =
+			"0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318".to_string();
+		config.settle.mcr_contract_address =
+			"0x5FbDB2315678afecb367f032d93F642f64180aa3".to_string();
+		config.eth_connection.eth_rpc_connection_protocol = "http".to_string();
+		config.eth_connection.eth_rpc_connection_hostname = "localhost".to_string();
+		config.eth_connection.eth_ws_connection_protocol = "ws".to_string();
+		config.eth_connection.eth_ws_connection_hostname = "localhost".to_string();
+		config
+	}
+
+	#[test]
+	fn test_validate_valid_config() {
+		assert_eq!(valid_config().validate(), Ok(()));
+	}
+
+	#[test]
+	fn test_validate_invalid_signer_key() {
+		let mut config = valid_config();
+		config.settle.signer_private_key = "not a key".to_string();
+		assert!(matches!(config.validate(), Err(ConfigError::InvalidSignerKey { .. })));
+	}
+
+	#[test]
+	fn test_validate_invalid_contract_address() {
+		let mut config = valid_config();
+		config.settle.mcr_contract_address = "0x0".to_string();
+		assert!(matches!(config.validate(), Err(ConfigError::InvalidContractAddress(_, _))));
+	}
+
+	#[test]
+	fn test_validate_invalid_rpc_url() {
+		let mut config = valid_config();
+		config.eth_connection.eth_rpc_connection_protocol = "ftp".to_string();
+		assert!(matches!(config.validate(), Err(ConfigError::InvalidRpcUrl(_, _))));
+
+		let mut config = valid_config();
+		config.eth_connection.eth_rpc_connection_hostname = "".to_string();
+		assert!(matches!(config.validate(), Err(ConfigError::InvalidRpcUrl(_, _))));
+	}
+
+	#[test]
+	fn test_validate_invalid_ws_url() {
+		let mut config = valid_config();
+		config.eth_connection.eth_ws_connection_protocol = "https".to_string();
+		assert!(matches!(config.validate(), Err(ConfigError::InvalidWsUrl(_, _))));
+
+		let mut config = valid_config();
+		config.eth_connection.eth_ws_connection_hostname = "".to_string();
+		assert!(matches!(config.validate(), Err(ConfigError::InvalidWsUrl(_, _))));
+	}
+
+	#[test]
+	fn test_validate_swapped_urls() {
+		let mut config = valid_config();
+		config.eth_connection.eth_rpc_connection_protocol = "ws".to_string();
+		config.eth_connection.eth_ws_connection_protocol = "http".to_string();
+		let err = config.validate().unwrap_err();
+		assert!(matches!(err, ConfigError::InvalidRpcUrl(_, _)));
+		let message = err.to_string();
+		assert!(message.contains("eth_connection.eth_rpc_connection_protocol"), "{message}");
+		assert!(message.contains("swapped"), "{message}");
+
+		let mut config = valid_config();
+		config.eth_connection.eth_ws_connection_protocol = "https".to_string();
+		let message = config.validate().unwrap_err().to_string();
+		assert!(message.contains("eth_connection.eth_ws_connection_protocol"), "{message}");
+		assert!(message.contains("swapped"), "{message}");
+	}
+}
@@ -0,0 +1,161 @@
+use crate::CommitmentEventStream;
+use movement_types::BlockCommitmentEvent;
+use thiserror::Error;
+use tokio_stream::{Stream, StreamExt};
+
+/// Error of a stream checked by [`enforce_contiguous_heights`].
+#[derive(Error, Debug)]
+pub enum ContiguityError {
+	/// Events were expected from `expected` on, but the next one is at `received`: the
+	/// heights in `expected..received` were skipped.
+	#[error("Commitment heights {expected}..{received} were skipped")]
+	Gap { expected: u64, received: u64 },
+	/// Error of the underlying stream.
+	#[error(transparent)]
+	Stream(#[from] anyhow::Error),
+}
+
+/// How [`enforce_contiguous_heights`] reports a skipped height.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ContiguityPolicy {
+	/// Ends the stream with a [`ContiguityError::Gap`].
+	#[default]
+	Error,
+	/// Emits a [`ContiguityError::Gap`] marker before the event following the gap, then
+	/// carries on from that event.
+	Gap,
+}
+
+/// Checks that the heights of the events, accepted or rejected, follow each other without
+/// gaps, starting from the height of the first event. A skipped height is reported according
+/// to `policy`, so that a consumer tracking settlement notices a missed commitment as soon as
+/// the next one arrives.
+///
+/// An event at or below the last height seen, e.g. one delivered again, is passed through and
+/// does not change the height expected next. Errors of the underlying stream are passed
+/// through as [`ContiguityError::Stream`].
+pub fn enforce_contiguous_heights(
+	stream: CommitmentEventStream,
+	policy: ContiguityPolicy,
+) -> impl Stream<Item = Result<BlockCommitmentEvent, ContiguityError>> {
+	let state = Contiguity { stream, last: None, pending: None, ended: false };
+	futures::stream::unfold(state, move |mut state| async move {
+		if let Some(event) = state.pending.take() {
+			return Some((Ok(event), state));
+		}
+		if state.ended {
+			return None;
+		}
+		let event = match state.stream.next().await? {
+			Ok(event) => event,
+			Err(err) => return Some((Err(err.into()), state)),
+		};
+		let height = event.height();
+		// no height is expected after the highest one, every later event is at or below it
+		match state.last.map(|last| last.checked_add(1)) {
+			Some(Some(expected)) if height > expected => {
+				state.last = Some(height);
+				match policy {
+					ContiguityPolicy::Error => state.ended = true,
+					ContiguityPolicy::Gap => state.pending = Some(event),
+				}
+				Some((Err(ContiguityError::Gap { expected, received: height }), state))
+			}
+			Some(Some(expected)) if height < expected => Some((Ok(event), state)),
+			Some(None) => Some((Ok(event), state)),
+			_ => {
+				state.last = Some(height);
+				Some((Ok(event), state))
+			}
+		}
+	})
+}
+
+struct Contiguity {
+	stream: CommitmentEventStream,
+	// height of the last event in sequence, the next one is expected right after it
+	last: Option<u64>,
+	// the event following a gap, emitted after the gap marker
+	pending: Option<BlockCommitmentEvent>,
+	ended: bool,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::replay::replay_events;
+	use movement_types::{BlockCommitment, BlockCommitmentRejectionReason, Commitment};
+
+	fn events(heights: &[u64]) -> Vec<BlockCommitmentEvent> {
+		heights
+			.iter()
+			.map(|&height| {
+				BlockCommitmentEvent::Accepted(BlockCommitment {
+					height,
+					block_id: Default::default(),
+					commitment: Commitment([height as u8; 32]),
+				})
+			})
+			.collect()
+	}
+
+	#[tokio::test]
+	async fn test_gap_ends_stream() -> Result<(), anyhow::Error> {
+		let stream = replay_events(events(&[1, 2, 4, 5]), None);
+		let results: Vec<_> =
+			enforce_contiguous_heights(stream, ContiguityPolicy::Error).collect().await;
+
+		assert_eq!(results.len(), 3);
+		assert_eq!(results[0].as_ref().map(BlockCommitmentEvent::height).ok(), Some(1));
+		assert_eq!(results[1].as_ref().map(BlockCommitmentEvent::height).ok(), Some(2));
+		assert!(matches!(results[2], Err(ContiguityError::Gap { expected: 3, received: 4 })));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_gap_marker_continues_stream() -> Result<(), anyhow::Error> {
+		let mut all = events(&[1, 2]);
+		// rejected events count towards contiguity, and repeated ones are passed through
+		all.push(BlockCommitmentEvent::Rejected {
+			height: 3,
+			reason: BlockCommitmentRejectionReason::InvalidCommitment,
+		});
+		all.extend(events(&[2, 6, 7]));
+		let stream = replay_events(all, None);
+		let results: Vec<_> =
+			enforce_contiguous_heights(stream, ContiguityPolicy::Gap).collect().await;
+
+		let heights: Vec<_> = results
+			.iter()
+			.map(|result| match result {
+				Ok(event) => Ok(event.height()),
+				Err(ContiguityError::Gap { expected, received }) => Err((*expected, *received)),
+				Err(err) => panic!("unexpected error: {err}"),
+			})
+			.collect();
+		assert_eq!(heights, vec![Ok(1), Ok(2), Ok(3), Ok(2), Err((4, 6)), Ok(6), Ok(7)]);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_highest_height_ends_expected_range() -> Result<(), anyhow::Error> {
+		let stream = replay_events(events(&[u64::MAX - 2, u64::MAX, u64::MAX, 1]), None);
+		let results: Vec<_> =
+			enforce_contiguous_heights(stream, ContiguityPolicy::Gap).collect().await;
+
+		assert_eq!(results.len(), 5);
+		assert!(matches!(
+			results[1],
+			Err(ContiguityError::Gap { expected, received: u64::MAX }) if expected == u64::MAX - 1
+		));
+		let heights: Vec<_> = results
+			.iter()
+			.filter_map(|result| result.as_ref().map(BlockCommitmentEvent::height).ok())
+			.collect();
+		assert_eq!(heights, vec![u64::MAX - 2, u64::MAX, u64::MAX, 1]);
+
+		Ok(())
+	}
+}
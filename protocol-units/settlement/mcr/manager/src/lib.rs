@@ -1,7 +1,9 @@
 use movement_types::{BlockCommitment, BlockCommitmentEvent};
 use tokio_stream::Stream;
 
+pub mod contiguity;
 mod manager;
+pub mod replay;
 
 pub use manager::Manager as McrSettlementManager;
 
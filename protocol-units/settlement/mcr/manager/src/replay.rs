@@ -0,0 +1,125 @@
+use crate::CommitmentEventStream;
+use movement_types::BlockCommitmentEvent;
+use tokio_stream::StreamExt;
+
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Creates an event stream replaying the given events in order, waiting `delay` before
+/// each event if set. Lets consumers be tested without a live settlement client.
+pub fn replay_events(
+	events: Vec<BlockCommitmentEvent>,
+	delay: Option<Duration>,
+) -> CommitmentEventStream {
+	Box::pin(tokio_stream::iter(events).then(move |event| async move {
+		if let Some(delay) = delay {
+			tokio::time::sleep(delay).await;
+		}
+		Ok(event)
+	}))
+}
+
+/// Creates an event stream replaying a log saved by [`EventRecorder::save`].
+pub fn replay_events_from_file<P: AsRef<Path>>(
+	path: P,
+	delay: Option<Duration>,
+) -> Result<CommitmentEventStream, anyhow::Error> {
+	let events = serde_json::from_slice(&fs::read(path)?)?;
+	Ok(replay_events(events, delay))
+}
+
+/// Captures the events of a stream into a log that can be replayed with [`replay_events`].
+#[derive(Debug, Clone, Default)]
+pub struct EventRecorder {
+	events: Arc<Mutex<Vec<BlockCommitmentEvent>>>,
+}
+
+impl EventRecorder {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Wraps the stream so that every event passing through is recorded. Errors are passed
+	/// through without being recorded.
+	pub fn record(&self, stream: CommitmentEventStream) -> CommitmentEventStream {
+		let events = Arc::clone(&self.events);
+		Box::pin(stream.map(move |event| {
+			if let Ok(event) = &event {
+				events.lock().unwrap().push(event.clone());
+			}
+			event
+		}))
+	}
+
+	/// The events recorded so far.
+	pub fn events(&self) -> Vec<BlockCommitmentEvent> {
+		self.events.lock().unwrap().clone()
+	}
+
+	/// Saves the events recorded so far as a JSON log.
+	pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), anyhow::Error> {
+		fs::write(path, serde_json::to_vec(&self.events())?)?;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{McrSettlementManager, McrSettlementManagerOperations};
+	use mcr_settlement_client::mock::McrSettlementClient;
+	use mcr_settlement_config::Config;
+	use movement_types::{BlockCommitment, BlockCommitmentRejectionReason, Commitment};
+
+	#[tokio::test]
+	async fn test_replay_recorded_events() -> Result<(), anyhow::Error> {
+		let config = Config::default();
+		let mut client = McrSettlementClient::new();
+		client.block_lead_tolerance = 1;
+		client
+			.override_block_commitment(BlockCommitment {
+				height: 2,
+				block_id: Default::default(),
+				commitment: Commitment([0; 32]),
+			})
+			.await;
+		let (manager, event_stream) = McrSettlementManager::new(client, &config);
+
+		let recorder = EventRecorder::new();
+		let mut event_stream = recorder.record(event_stream);
+		for height in 1..=3 {
+			manager
+				.post_block_commitment(BlockCommitment {
+					height,
+					block_id: Default::default(),
+					commitment: Commitment([height as u8; 32]),
+				})
+				.await?;
+		}
+		for _ in 0..2 {
+			event_stream.next().await.expect("stream has ended")?;
+		}
+		let recorded = recorder.events();
+		assert!(matches!(recorded[0], BlockCommitmentEvent::Accepted(_)));
+		assert_eq!(
+			recorded[1],
+			BlockCommitmentEvent::Rejected {
+				height: 2,
+				reason: BlockCommitmentRejectionReason::InvalidCommitment,
+			}
+		);
+
+		let dir = tempfile::tempdir()?;
+		let path = dir.path().join("events.json");
+		recorder.save(&path)?;
+
+		let replayed: Vec<_> =
+			replay_events_from_file(&path, Some(Duration::from_millis(10)))?.collect().await;
+		let replayed = replayed.into_iter().collect::<Result<Vec<_>, _>>()?;
+		assert_eq!(replayed, recorded);
+
+		Ok(())
+	}
+}
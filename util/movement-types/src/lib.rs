@@ -1,9 +1,16 @@
 use aptos_types::state_proof::StateProof;
 
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use sha2::Digest;
+use subtle::ConstantTimeEq;
 
 use core::fmt;
+use ed25519_dalek::{Signer, SigningKey, Verifier, VerifyingKey};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
 
 #[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Id(pub [u8; 32]);
@@ -20,6 +27,45 @@ impl Id {
 	pub fn genesis_block() -> Self {
 		Self([0; 32])
 	}
+
+	/// Derives a stable id from a name, e.g. to tell sequencers apart: the SHA-256 of the name.
+	pub fn from_name(name: &str) -> Self {
+		Id(sha2::Sha256::digest(name.as_bytes()).into())
+	}
+
+	/// Derives a stable id from a public key, e.g. the signing key of a sequencer.
+	pub fn from_public_key(public_key: &[u8]) -> Self {
+		Self::derive(b"public_key", public_key)
+	}
+
+	/// Hashes `data` under a domain, so that a key and a name with the same bytes yield
+	/// different ids.
+	fn derive(domain: &[u8], data: &[u8]) -> Self {
+		let mut hasher = sha2::Sha256::new();
+		hasher.update(domain);
+		hasher.update([0]);
+		hasher.update(data);
+		Id(hasher.finalize().into())
+	}
+
+	/// Parses an id from the 64 hex digits written by its [`Display`](fmt::Display) impl, in
+	/// either case.
+	pub fn from_hex(hex: &str) -> Result<Self, hex::FromHexError> {
+		let mut bytes = [0; 32];
+		hex::decode_to_slice(hex, &mut bytes)?;
+		Ok(Self(bytes))
+	}
+
+	/// Lowercase hex, the same as its [`Display`](fmt::Display) impl.
+	pub fn to_hex(&self) -> String {
+		hex::encode(self.0)
+	}
+
+	/// Compares in constant time, where the outcome gates acceptance and the time taken must
+	/// not reveal how many leading bytes match. `==` remains for use as a map key.
+	pub fn ct_eq(&self, other: &Id) -> bool {
+		self.0[..].ct_eq(&other.0[..]).into()
+	}
 }
 
 impl AsRef<[u8]> for Id {
@@ -30,30 +76,222 @@ impl AsRef<[u8]> for Id {
 
 impl fmt::Display for Id {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		write!(f, "{:?}", &self.0)
+		for byte in &self.0 {
+			write!(f, "{:02x}", byte)?;
+		}
+		Ok(())
 	}
 }
 
+/// Ed25519 signature of the id of a [`Transaction`], along with the key it verifies against.
+#[serde_with::serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TransactionSignature {
+	pub public_key: [u8; 32],
+	#[serde_as(as = "serde_with::Bytes")]
+	pub signature: [u8; 64],
+}
+
+/// Domain tag of transaction ids, versioned so that a change of their encoding cannot collide
+/// with ids of the previous one.
+const TRANSACTION_ID_TAG: &[u8] = b"movement/transaction-id/v1";
+
 #[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Transaction {
-	pub data: Vec<u8>,
+	data: Vec<u8>,
 	pub sequence_number: u64,
+	/// The account that sent the transaction, which together with the sequence number
+	/// identifies a replaceable slot in the mempool.
+	#[serde(default)]
+	pub sender: Id,
+	/// Kind of the transaction, e.g. system or user, that block assembly may apply quotas to.
+	/// Committed in the id.
+	#[serde(default)]
+	pub category: u8,
+	/// Set by [`Transaction::sign`]. Not committed in the id, which is what is signed.
+	#[serde(default)]
+	pub signature: Option<TransactionSignature>,
+	#[serde(skip)]
+	id: CachedId,
 }
 
 impl Transaction {
 	pub fn new(data: Vec<u8>, sequence_number: u64) -> Self {
-		Self { data, sequence_number }
+		Self {
+			data,
+			sequence_number,
+			sender: Id::default(),
+			category: 0,
+			signature: None,
+			id: CachedId::default(),
+		}
+	}
+
+	/// Creates a transaction signed by `signer`, see [`Transaction::sign`].
+	pub fn new_signed(data: Vec<u8>, sequence_number: u64, signer: &SigningKey) -> Self {
+		let mut transaction = Self::new(data, sequence_number);
+		transaction.sign(signer);
+		transaction
+	}
+
+	/// Signs the id of the transaction. Changing the data or the fields committed in the id
+	/// afterwards invalidates the signature.
+	pub fn sign(&mut self, signer: &SigningKey) {
+		self.signature = Some(TransactionSignature {
+			public_key: signer.verifying_key().to_bytes(),
+			signature: signer.sign(&self.id().0).to_bytes(),
+		});
+	}
+
+	/// Whether the transaction carries a valid signature of its id. `false` if it is unsigned.
+	pub fn verify_signature(&self) -> bool {
+		let Some(signature) = &self.signature else {
+			return false;
+		};
+		let Ok(public_key) = VerifyingKey::from_bytes(&signature.public_key) else {
+			return false;
+		};
+		let signature = ed25519_dalek::Signature::from_bytes(&signature.signature);
+		public_key.verify(&self.id().0, &signature).is_ok()
+	}
+
+	/// Creates a transaction whose data is the BCS encoding of `payload`, to be read back with
+	/// [`Transaction::decode_payload`].
+	pub fn from_payload<T: Serialize>(
+		payload: &T,
+		sequence_number: u64,
+	) -> Result<Self, anyhow::Error> {
+		Ok(Self::new(bcs::to_bytes(payload)?, sequence_number))
+	}
+
+	/// Decodes the payload of a transaction created with [`Transaction::from_payload`]. Fails
+	/// if the data is not the whole BCS encoding of a `T`.
+	pub fn decode_payload<T: DeserializeOwned>(&self) -> Result<T, anyhow::Error> {
+		Ok(bcs::from_bytes(&self.data)?)
 	}
 
+	pub fn data(&self) -> &[u8] {
+		&self.data
+	}
+
+	/// Size of the transaction counted by block byte budgets: its data and sequence number.
+	/// See [`Transaction::serialized_len`] for the size of its full encoding.
+	pub fn size_bytes(&self) -> usize {
+		self.data.len() + std::mem::size_of::<u64>()
+	}
+
+	/// Length of the BCS encoding of the transaction, computed without serializing it.
+	pub fn serialized_len(&self) -> Result<usize, anyhow::Error> {
+		Ok(bcs::serialized_size(self)?)
+	}
+
+	/// Replaces the data of the transaction, which changes its id.
+	pub fn set_data(&mut self, data: Vec<u8>) {
+		self.data = data;
+		self.id = CachedId::default();
+	}
+
+	/// Creates a transaction stamped with the next sequence number of `counter`.
+	pub fn next_from(counter: &SequenceCounter, data: Vec<u8>) -> Self {
+		Self::new(data, counter.next())
+	}
+
+	pub fn with_sender(mut self, sender: Id) -> Self {
+		self.sender = sender;
+		self.id = CachedId::default();
+		self
+	}
+
+	pub fn with_category(mut self, category: u8) -> Self {
+		self.category = category;
+		self.id = CachedId::default();
+		self
+	}
+
+	/// Hash of the data and the fields committed in it. Computed on first use and cached
+	/// until the data or a field is replaced with a setter, e.g. [`Transaction::set_data`].
+	/// The cache is bypassed if the public fields it covers were assigned directly since.
 	pub fn id(&self) -> Id {
+		let (fields, id) = self.id.0.get_or_init(|| (self.id_fields(), self.compute_id()));
+		if *fields == self.id_fields() {
+			id.clone()
+		} else {
+			self.compute_id()
+		}
+	}
+
+	/// The public fields committed in the id.
+	fn id_fields(&self) -> (u64, Id, u8) {
+		(self.sequence_number, self.sender.clone(), self.category)
+	}
+
+	/// The data is length-prefixed and every other field has a fixed width, so that no two
+	/// transactions hash the same bytes.
+	fn compute_id(&self) -> Id {
 		let mut hasher = sha2::Sha256::new();
+		hasher.update(TRANSACTION_ID_TAG);
+		hasher.update((self.data.len() as u64).to_le_bytes());
 		hasher.update(&self.data);
-		hasher.update(&self.sequence_number.to_le_bytes());
+		hasher.update(self.sequence_number.to_le_bytes());
+		hasher.update(&self.sender);
+		hasher.update([self.category]);
 		Id(hasher.finalize().into())
 	}
 
 	pub fn test() -> Self {
-		Self { data: vec![0], sequence_number: 0 }
+		Self::new(vec![0], 0)
+	}
+}
+
+/// Id of a [`Transaction`] along with the public fields it was computed with.
+/// Ignored by comparisons and hashing, so that it does not change the identity of the
+/// transaction.
+#[derive(Clone, Default)]
+struct CachedId(OnceLock<((u64, Id, u8), Id)>);
+
+impl fmt::Debug for CachedId {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{:?}", self.0.get().map(|(_, id)| id))
+	}
+}
+
+impl PartialEq for CachedId {
+	fn eq(&self, _other: &Self) -> bool {
+		true
+	}
+}
+
+impl Eq for CachedId {}
+
+impl PartialOrd for CachedId {
+	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl Ord for CachedId {
+	fn cmp(&self, _other: &Self) -> std::cmp::Ordering {
+		std::cmp::Ordering::Equal
+	}
+}
+
+impl std::hash::Hash for CachedId {
+	fn hash<H: std::hash::Hasher>(&self, _state: &mut H) {}
+}
+
+/// Thread-safe source of monotonically increasing sequence numbers.
+#[derive(Debug, Default)]
+pub struct SequenceCounter(AtomicU64);
+
+impl SequenceCounter {
+	/// Creates a counter whose first value is `start`.
+	pub fn new(start: u64) -> Self {
+		Self(AtomicU64::new(start))
+	}
+
+	/// Returns the next sequence number. Each value is handed out exactly once.
+	pub fn next(&self) -> u64 {
+		self.0.fetch_add(1, Ordering::Relaxed)
 	}
 }
 
@@ -67,6 +305,68 @@ pub struct TransactionEntry {
 pub struct AtomicTransactionBundle {
 	pub sequencer_id: Id,
 	pub transactions: Vec<TransactionEntry>,
+	/// Height of the block the bundle was built against, if known. Lets downstream detect
+	/// bundles built against a stale tip.
+	#[serde(default)]
+	pub parent_height: Option<u64>,
+}
+
+impl AtomicTransactionBundle {
+	pub fn id(&self) -> Id {
+		let mut hasher = sha2::Sha256::new();
+		hasher.update(&self.sequencer_id);
+		for entry in &self.transactions {
+			hasher.update(&entry.consumer_id);
+			hasher.update(&entry.data.id());
+		}
+		Id(hasher.finalize().into())
+	}
+
+	/// The transactions of the bundle, in order, without their consumer ids.
+	pub fn into_transactions(self) -> Vec<Transaction> {
+		self.transactions.into_iter().map(|entry| entry.data).collect()
+	}
+
+	/// Checks the internal consistency of a received bundle: it has transactions and a
+	/// sequencer id, its entries either all have a consumer id or all leave it to the default,
+	/// and the transactions of each sender appear in strictly increasing sequence number order.
+	pub fn validate(&self) -> Result<(), BundleValidationError> {
+		if self.transactions.is_empty() {
+			return Err(BundleValidationError::Empty);
+		}
+		if self.sequencer_id == Id::default() {
+			return Err(BundleValidationError::MissingSequencerId);
+		}
+		let has_consumer_ids = self.transactions[0].consumer_id != Id::default();
+		let mut last_sequence_numbers = HashMap::new();
+		for (index, entry) in self.transactions.iter().enumerate() {
+			if (entry.consumer_id != Id::default()) != has_consumer_ids {
+				return Err(BundleValidationError::InconsistentConsumerId { index });
+			}
+			let transaction = &entry.data;
+			if let Some(last) =
+				last_sequence_numbers.insert(&transaction.sender, transaction.sequence_number)
+			{
+				if transaction.sequence_number <= last {
+					return Err(BundleValidationError::SequenceOutOfOrder { index });
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Inconsistency found by [`AtomicTransactionBundle::validate`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum BundleValidationError {
+	#[error("Bundle has no transactions")]
+	Empty,
+	#[error("Bundle has no sequencer id")]
+	MissingSequencerId,
+	#[error("Bundle entry {index} disagrees with the first entry on having a consumer id")]
+	InconsistentConsumerId { index: usize },
+	#[error("Bundle entry {index} does not follow the previous transaction of its sender")]
+	SequenceOutOfOrder { index: usize },
 }
 
 impl TryFrom<AtomicTransactionBundle> for Transaction {
@@ -86,48 +386,420 @@ impl From<Transaction> for AtomicTransactionBundle {
 		Self {
 			sequencer_id: Id::default(),
 			transactions: vec![TransactionEntry { consumer_id: Id::default(), data: transaction }],
+			parent_height: None,
 		}
 	}
 }
 
+/// Inconsistency found by [`Block::validate_parent`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum BlockValidationError {
+	#[error("Block does not link to its claimed parent {parent}")]
+	ParentMismatch { parent: Id },
+	#[error("Block at height {height} does not follow its parent at height {parent_height}")]
+	HeightGap { parent_height: u64, height: u64 },
+}
+
+/// Consensus metadata of a block, committed in the block id.
 #[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
-pub enum BlockMetadata {
-	#[default]
-	BlockMetadata,
+pub struct BlockMetadata {
+	/// The sequencer that proposed the block, which it can be held accountable for.
+	#[serde(default)]
+	pub proposer: Id,
+	#[serde(default)]
+	pub epoch: u64,
+	#[serde(default)]
+	pub round: u64,
+}
+
+impl BlockMetadata {
+	/// Tag of the metadata encoding in the block id, so that a later encoding cannot collide
+	/// with this one.
+	const ID_TAG: u8 = 1;
+
+	/// The bytes the metadata contributes to the block id, of a fixed width.
+	fn id_bytes(&self) -> Vec<u8> {
+		let mut bytes = vec![Self::ID_TAG];
+		bytes.extend_from_slice(&self.proposer.0);
+		bytes.extend_from_slice(&self.epoch.to_le_bytes());
+		bytes.extend_from_slice(&self.round.to_le_bytes());
+		bytes
+	}
 }
 
 #[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Block {
 	pub metadata: BlockMetadata,
 	pub parent: Vec<u8>,
+	/// Position of the block in its chain, 0 if unknown.
+	#[serde(default)]
+	pub height: u64,
+	/// Time the block was built, in milliseconds since the Unix epoch, 0 if unknown.
+	#[serde(default)]
+	pub timestamp_ms: u64,
 	pub transactions: Vec<Transaction>,
+	/// Opaque application-defined metadata, committed to in the block id.
+	#[serde(default)]
+	pub extra: Vec<u8>,
 }
 
 impl Block {
 	pub fn new(metadata: BlockMetadata, parent: Vec<u8>, transactions: Vec<Transaction>) -> Self {
-		Self { metadata, parent, transactions }
+		Self::new_with_extra(metadata, parent, transactions, Vec::new())
+	}
+
+	/// Creates a block carrying application-defined `extra` metadata.
+	///
+	/// `extra` is part of the block id: two blocks with the same transactions but different
+	/// `extra` have different ids. An empty `extra` yields the same id as [`Block::new`].
+	pub fn new_with_extra(
+		metadata: BlockMetadata,
+		parent: Vec<u8>,
+		transactions: Vec<Transaction>,
+		extra: Vec<u8>,
+	) -> Self {
+		Self { metadata, parent, height: 0, timestamp_ms: 0, transactions, extra }
+	}
+
+	/// Creates a block at `height` in its chain, built at `timestamp_ms`, both committed to in
+	/// the block id. [`Block::new`] leaves them at 0.
+	pub fn new_at(
+		height: u64,
+		timestamp_ms: u64,
+		parent: Vec<u8>,
+		transactions: Vec<Transaction>,
+	) -> Self {
+		Self { height, timestamp_ms, ..Self::new(BlockMetadata::default(), parent, transactions) }
+	}
+
+	/// Creates a block holding exactly the transactions of the bundle, in the bundle order, so
+	/// that the bundle is sequenced as a whole or not at all. The sequencer of the bundle is
+	/// the proposer of the block.
+	pub fn from_bundle(parent: Vec<u8>, bundle: AtomicTransactionBundle) -> Self {
+		let metadata =
+			BlockMetadata { proposer: bundle.sequencer_id.clone(), ..Default::default() };
+		Self::new(metadata, parent, bundle.into_transactions())
+	}
+
+	/// Checks that the block extends `parent`: it links to the id of `parent` and, unless its
+	/// own height is unknown, i.e. 0, it is at the height following that of `parent`.
+	pub fn validate_parent(&self, parent: &Block) -> Result<(), BlockValidationError> {
+		let parent_id = parent.id();
+		if self.parent != parent_id.to_vec() {
+			return Err(BlockValidationError::ParentMismatch { parent: parent_id });
+		}
+		// no height follows the highest one
+		if self.height != 0 && parent.height.checked_add(1) != Some(self.height) {
+			return Err(BlockValidationError::HeightGap {
+				parent_height: parent.height,
+				height: self.height,
+			});
+		}
+		Ok(())
 	}
 
+	/// Hashes the header fields, so that a block and its header have the same id.
 	pub fn id(&self) -> Id {
-		let mut hasher = sha2::Sha256::new();
-		hasher.update(&self.parent);
-		for transaction in &self.transactions {
-			hasher.update(&transaction.id());
+		block_id(
+			&self.metadata,
+			&self.parent,
+			self.height,
+			self.timestamp_ms,
+			&self.transactions_root(),
+			self.transactions.len() as u64,
+			&self.extra,
+		)
+	}
+
+	/// Commits to the ids of the transactions, in order. This is the [`Block::merkle_root`].
+	pub fn transactions_root(&self) -> Id {
+		self.merkle_root()
+	}
+
+	/// Root of a binary Merkle tree over the ids of the transactions, in order, against which
+	/// [`Block::inclusion_proof`] proves the inclusion of a single transaction. The root of a
+	/// block without transactions is the all-zero id.
+	pub fn merkle_root(&self) -> Id {
+		transactions_root(&self.transactions)
+	}
+
+	/// Proves that the transaction at `index` is included in the block, see
+	/// [`MerkleProof::verify`]. `None` if there is no transaction at `index`.
+	pub fn inclusion_proof(&self, index: usize) -> Option<MerkleProof> {
+		if index >= self.transactions.len() {
+			return None;
+		}
+		let mut level: Vec<Id> = self
+			.transactions
+			.iter()
+			.map(|transaction| merkle_leaf(&transaction.id()))
+			.collect();
+		let mut position = index;
+		let mut siblings = Vec::new();
+		while level.len() > 1 {
+			if let Some(sibling) = level.get(position ^ 1) {
+				siblings.push(sibling.clone());
+			}
+			level = merkle_parents(&level);
+			position /= 2;
+		}
+		Some(MerkleProof {
+			index: index as u64,
+			leaf_count: self.transactions.len() as u64,
+			siblings,
+		})
+	}
+
+	/// The block without its transactions, for peers to exchange before fetching the body.
+	pub fn header(&self) -> BlockHeader {
+		BlockHeader {
+			metadata: self.metadata.clone(),
+			parent: self.parent.clone(),
+			height: self.height,
+			timestamp_ms: self.timestamp_ms,
+			transactions_root: self.transactions_root(),
+			transaction_count: self.transactions.len() as u64,
+			extra: self.extra.clone(),
 		}
-		Id(hasher.finalize().into())
 	}
 
 	pub fn test() -> Self {
 		Self {
-			metadata: BlockMetadata::BlockMetadata,
+			metadata: BlockMetadata::default(),
 			parent: vec![0],
+			height: 0,
+			timestamp_ms: 0,
 			transactions: vec![Transaction::test()],
+			extra: Vec::new(),
 		}
 	}
 
 	pub fn add_transaction(&mut self, transaction: Transaction) {
 		self.transactions.push(transaction);
 	}
+
+	/// Splits the block into consecutive sub-blocks whose JSON encoding, as submitted to the
+	/// DA, is at most `max_bytes`. Transaction order is kept and a transaction is never split,
+	/// so a transaction too large on its own ends up alone in an oversized sub-block.
+	///
+	/// The sub-blocks form a chain that passes [`Block::validate_parent`]: the first keeps the
+	/// parent and height of the original block and each subsequent one has the id of the
+	/// previous sub-block as its parent and the height following it, unless the height is
+	/// unknown, i.e. 0. Every sub-block keeps the metadata, timestamp and `extra` of the
+	/// original block.
+	pub fn split_by_bytes(mut self, max_bytes: u64) -> Vec<Block> {
+		let transactions = std::mem::take(&mut self.transactions);
+		let mut blocks = Vec::new();
+		let mut current = self;
+		let mut current_size = json_size(&current);
+
+		for transaction in transactions {
+			let transaction_size = json_size(&transaction);
+			// transactions after the first are preceded by a separator
+			if !current.transactions.is_empty() && current_size + 1 + transaction_size > max_bytes {
+				let parent = current.id().to_vec();
+				// past the highest height, the height of the sub-block is left unknown
+				let height = match current.height {
+					0 => 0,
+					height => height.checked_add(1).unwrap_or(0),
+				};
+				let next = Block { parent, height, transactions: Vec::new(), ..current.clone() };
+				current_size = json_size(&next);
+				blocks.push(std::mem::replace(&mut current, next));
+			}
+			if !current.transactions.is_empty() {
+				current_size += 1;
+			}
+			current_size += transaction_size;
+			current.transactions.push(transaction);
+		}
+
+		blocks.push(current);
+		blocks
+	}
+
+	/// Computes the transactions that differ between this block and `other`, going from
+	/// `self` to `other`. Ids are reported in the order they appear in their block.
+	pub fn diff(&self, other: &Block) -> BlockDiff {
+		let ids: HashSet<Id> = self.transactions.iter().map(Transaction::id).collect();
+		let other_ids: HashSet<Id> = other.transactions.iter().map(Transaction::id).collect();
+
+		let added = other
+			.transactions
+			.iter()
+			.map(Transaction::id)
+			.filter(|id| !ids.contains(id))
+			.collect();
+		let removed = self
+			.transactions
+			.iter()
+			.map(Transaction::id)
+			.filter(|id| !other_ids.contains(id))
+			.collect();
+
+		BlockDiff { added, removed }
+	}
+}
+
+fn json_size<T: Serialize>(value: &T) -> u64 {
+	serde_json::to_vec(value).expect("unexpected serialization error").len() as u64
+}
+
+fn transactions_root(transactions: &[Transaction]) -> Id {
+	let mut level: Vec<Id> =
+		transactions.iter().map(|transaction| merkle_leaf(&transaction.id())).collect();
+	while level.len() > 1 {
+		level = merkle_parents(&level);
+	}
+	level.pop().unwrap_or_default()
+}
+
+/// Leaves and inner nodes are hashed under different prefixes, so that an inner node cannot
+/// be passed off as a leaf.
+fn merkle_leaf(id: &Id) -> Id {
+	let mut hasher = sha2::Sha256::new();
+	hasher.update([0]);
+	hasher.update(id);
+	Id(hasher.finalize().into())
+}
+
+fn merkle_node(left: &Id, right: &Id) -> Id {
+	let mut hasher = sha2::Sha256::new();
+	hasher.update([1]);
+	hasher.update(left);
+	hasher.update(right);
+	Id(hasher.finalize().into())
+}
+
+/// Hashes the nodes of a level of the tree in pairs. The last node of a level of odd length
+/// is carried up as is, rather than paired with itself, so that no two lists of leaves have
+/// the same root.
+fn merkle_parents(level: &[Id]) -> Vec<Id> {
+	level
+		.chunks(2)
+		.map(|pair| match pair {
+			[left, right] => merkle_node(left, right),
+			[single] => single.clone(),
+			_ => unreachable!("chunks of at most 2"),
+		})
+		.collect()
+}
+
+/// Proof that a transaction is included in a block, built by [`Block::inclusion_proof`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MerkleProof {
+	/// Position of the transaction in the block.
+	pub index: u64,
+	/// Number of transactions in the block.
+	pub leaf_count: u64,
+	/// Siblings of the path from the transaction to the root, bottom up. Levels where the
+	/// path is carried up unpaired have none.
+	pub siblings: Vec<Id>,
+}
+
+impl MerkleProof {
+	/// Whether the transaction with id `leaf` is at `index` in a block with merkle root `root`,
+	/// see [`Block::merkle_root`].
+	pub fn verify(&self, leaf: Id, root: Id) -> bool {
+		if self.index >= self.leaf_count {
+			return false;
+		}
+		let mut hash = merkle_leaf(&leaf);
+		let mut position = self.index;
+		let mut width = self.leaf_count;
+		let mut siblings = self.siblings.iter();
+		while width > 1 {
+			if position % 2 == 1 {
+				let Some(sibling) = siblings.next() else { return false };
+				hash = merkle_node(sibling, &hash);
+			} else if position + 1 < width {
+				let Some(sibling) = siblings.next() else { return false };
+				hash = merkle_node(&hash, sibling);
+			}
+			position /= 2;
+			width = width.div_ceil(2);
+		}
+		siblings.next().is_none() && hash == root
+	}
+}
+
+/// Domain tag of block ids, versioned so that a change of their encoding cannot collide with
+/// ids of the previous one.
+const BLOCK_ID_TAG: &[u8] = b"movement/block-id/v1";
+
+/// The parent is length-prefixed and every field but the trailing `extra` has a fixed width,
+/// so that no two blocks hash the same bytes.
+fn block_id(
+	metadata: &BlockMetadata,
+	parent: &[u8],
+	height: u64,
+	timestamp_ms: u64,
+	transactions_root: &Id,
+	transaction_count: u64,
+	extra: &[u8],
+) -> Id {
+	let mut hasher = sha2::Sha256::new();
+	hasher.update(BLOCK_ID_TAG);
+	hasher.update(metadata.id_bytes());
+	hasher.update((parent.len() as u64).to_le_bytes());
+	hasher.update(parent);
+	hasher.update(height.to_le_bytes());
+	hasher.update(timestamp_ms.to_le_bytes());
+	hasher.update(transactions_root);
+	hasher.update(transaction_count.to_le_bytes());
+	hasher.update(extra);
+	Id(hasher.finalize().into())
+}
+
+/// A [`Block`] without its transactions, which it commits to through `transactions_root`
+/// and `transaction_count`. A header has the id of its block.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq, Hash)]
+pub struct BlockHeader {
+	pub metadata: BlockMetadata,
+	pub parent: Vec<u8>,
+	#[serde(default)]
+	pub height: u64,
+	#[serde(default)]
+	pub timestamp_ms: u64,
+	pub transactions_root: Id,
+	pub transaction_count: u64,
+	#[serde(default)]
+	pub extra: Vec<u8>,
+}
+
+impl BlockHeader {
+	pub fn id(&self) -> Id {
+		block_id(
+			&self.metadata,
+			&self.parent,
+			self.height,
+			self.timestamp_ms,
+			&self.transactions_root,
+			self.transaction_count,
+			&self.extra,
+		)
+	}
+
+	/// Checks that `transactions`, e.g. fetched from a peer, are the body of this header.
+	pub fn matches_body(&self, transactions: &[Transaction]) -> bool {
+		transactions.len() as u64 == self.transaction_count
+			&& transactions_root(transactions) == self.transactions_root
+	}
+}
+
+/// The transaction-level difference between two blocks, as computed by [`Block::diff`].
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq)]
+pub struct BlockDiff {
+	/// Ids of the transactions only present in the other block.
+	pub added: Vec<Id>,
+	/// Ids of the transactions only present in this block.
+	pub removed: Vec<Id>,
+}
+
+impl BlockDiff {
+	pub fn is_empty(&self) -> bool {
+		self.added.is_empty() && self.removed.is_empty()
+	}
 }
 
 #[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -144,13 +816,56 @@ impl Commitment {
 		bcs::serialize_into(&mut hasher, &state_proof).expect("unexpected serialization error");
 		Self(hasher.finalize().into())
 	}
+
+	/// Parses a commitment from the 64 hex digits written by its [`Display`](fmt::Display)
+	/// impl, in either case.
+	pub fn from_hex(hex: &str) -> Result<Self, hex::FromHexError> {
+		let mut bytes = [0; 32];
+		hex::decode_to_slice(hex, &mut bytes)?;
+		Ok(Self(bytes))
+	}
+
+	/// Lowercase hex, the same as its [`Display`](fmt::Display) impl.
+	pub fn to_hex(&self) -> String {
+		hex::encode(self.0)
+	}
+
+	/// Compares in constant time, see [`Id::ct_eq`].
+	pub fn ct_eq(&self, other: &Commitment) -> bool {
+		self.0[..].ct_eq(&other.0[..]).into()
+	}
+}
+
+/// Computes the state commitment settled for a block.
+///
+/// The default implementation hashes the block id, which is enough for chains without
+/// execution. Execution-backed providers override [`CommitmentProvider::commitment_for`] to
+/// commit to the state resulting from executing the block, e.g. with
+/// [`Commitment::digest_state_proof`].
+///
+/// The trait is object safe, so that providers can be held as `dyn CommitmentProvider`.
+#[async_trait::async_trait]
+pub trait CommitmentProvider: Send + Sync {
+	async fn commitment_for(&self, block: &Block) -> Result<Commitment, anyhow::Error> {
+		let mut hasher = sha2::Sha256::new();
+		hasher.update(&block.id());
+		Ok(Commitment(hasher.finalize().into()))
+	}
 }
 
+/// Commits to the block id alone, see [`CommitmentProvider`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BlockIdCommitmentProvider;
+
+#[async_trait::async_trait]
+impl CommitmentProvider for BlockIdCommitmentProvider {}
+
+/// Fails unless the data is exactly 32 bytes long.
 impl TryFrom<Vec<u8>> for Commitment {
 	type Error = std::array::TryFromSliceError;
 
 	fn try_from(data: Vec<u8>) -> Result<Self, Self::Error> {
-		Ok(Self(data[..32].try_into()?))
+		Ok(Self(data.as_slice().try_into()?))
 	}
 }
 
@@ -181,6 +896,17 @@ impl fmt::Display for Commitment {
 	}
 }
 
+/// Parses the hex form of [`Commitment::from_hex`]. Along with the hex
+/// [`Display`](fmt::Display) impl, this allows serializing a commitment as a hex string, e.g.
+/// with `serde_with::DisplayFromStr`, where the default serialization is a byte array.
+impl FromStr for Commitment {
+	type Err = hex::FromHexError;
+
+	fn from_str(hex: &str) -> Result<Self, Self::Err> {
+		Self::from_hex(hex)
+	}
+}
+
 #[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct BlockCommitment {
 	pub height: u64,
@@ -188,6 +914,28 @@ pub struct BlockCommitment {
 	pub commitment: Commitment,
 }
 
+impl BlockCommitment {
+	/// Whether both commitments describe the same block and state, ignoring the height they
+	/// were assigned. Useful to detect a commitment re-posted at a shifted height.
+	pub fn same_content(&self, other: &BlockCommitment) -> bool {
+		self.block_id == other.block_id && self.commitment == other.commitment
+	}
+}
+
+/// Rolls the commitments of a batch of blocks up into a single commitment, which commits to
+/// the height, block id and commitment of every block, in order.
+pub fn aggregate_commitment(block_commitments: &[BlockCommitment]) -> Commitment {
+	let mut hasher = sha2::Sha256::new();
+	hasher.update(b"rollup");
+	hasher.update((block_commitments.len() as u64).to_le_bytes());
+	for block_commitment in block_commitments {
+		hasher.update(block_commitment.height.to_le_bytes());
+		hasher.update(&block_commitment.block_id);
+		hasher.update(block_commitment.commitment.0);
+	}
+	Commitment(hasher.finalize().into())
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum BlockCommitmentRejectionReason {
 	InvalidBlockId,
@@ -201,3 +949,683 @@ pub enum BlockCommitmentEvent {
 	Accepted(BlockCommitment),
 	Rejected { height: u64, reason: BlockCommitmentRejectionReason },
 }
+
+impl BlockCommitmentEvent {
+	/// Height of the commitment, whether accepted or rejected.
+	pub fn height(&self) -> u64 {
+		match self {
+			BlockCommitmentEvent::Accepted(commitment) => commitment.height,
+			BlockCommitmentEvent::Rejected { height, .. } => *height,
+		}
+	}
+
+	pub fn is_accepted(&self) -> bool {
+		matches!(self, BlockCommitmentEvent::Accepted(_))
+	}
+
+	/// The accepted commitment, `None` if the commitment was rejected.
+	pub fn accepted(&self) -> Option<&BlockCommitment> {
+		match self {
+			BlockCommitmentEvent::Accepted(commitment) => Some(commitment),
+			BlockCommitmentEvent::Rejected { .. } => None,
+		}
+	}
+}
+
+#[cfg(test)]
+pub mod test {
+	use super::*;
+
+	#[test]
+	fn test_id_hex_round_trip() -> Result<(), anyhow::Error> {
+		let id = Id::from_name("sequencer");
+		assert_eq!(id.to_hex(), id.to_string());
+		assert_eq!(id.to_hex().len(), 64);
+		assert_eq!(Id::from_hex(&id.to_hex())?, id);
+		assert_eq!(Id::from_hex(&id.to_hex().to_uppercase())?, id);
+
+		let id = Id([0xab; 32]);
+		assert_eq!(id.to_string(), "ab".repeat(32));
+		assert_eq!(Id::from_hex(&id.to_hex())?, id);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_commitment_hex_round_trip() -> Result<(), anyhow::Error> {
+		let commitment = Commitment([0x5e; 32]);
+		assert_eq!(commitment.to_hex(), commitment.to_string());
+		assert_eq!(Commitment::from_hex(&commitment.to_hex())?, commitment);
+		assert_eq!(commitment.to_string().parse::<Commitment>()?, commitment);
+
+		// uppercase is accepted, printing is lowercase
+		let uppercase = "AB".repeat(32);
+		let commitment: Commitment = uppercase.parse()?;
+		assert_eq!(commitment, Commitment([0xab; 32]));
+		assert_eq!(commitment.to_string(), uppercase.to_lowercase());
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_transaction_signature() -> Result<(), anyhow::Error> {
+		let signer = SigningKey::from_bytes(&[7; 32]);
+		let transaction = Transaction::new_signed(vec![1, 2, 3], 5, &signer);
+		assert!(transaction.verify_signature());
+		assert_eq!(
+			transaction.signature.as_ref().map(|signature| signature.public_key),
+			Some(signer.verifying_key().to_bytes())
+		);
+		let serialized = serde_json::to_string(&transaction)?;
+		assert!(serde_json::from_str::<Transaction>(&serialized)?.verify_signature());
+
+		let mut tampered = transaction.clone();
+		tampered.set_data(vec![1, 2, 4]);
+		assert!(!tampered.verify_signature());
+
+		let other_signer = SigningKey::from_bytes(&[8; 32]);
+		let mut resigned = transaction.clone();
+		resigned.signature.as_mut().unwrap().public_key = other_signer.verifying_key().to_bytes();
+		assert!(!resigned.verify_signature());
+
+		let unsigned = Transaction::new(vec![1, 2, 3], 5);
+		assert!(!unsigned.verify_signature());
+		// the signature is not part of the id
+		assert_eq!(unsigned.id(), transaction.id());
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_transaction_size() -> Result<(), anyhow::Error> {
+		let transaction = Transaction::new(vec![7; 100], 1).with_sender(Id::from_name("sender"));
+		assert_eq!(transaction.size_bytes(), 100 + 8);
+		assert_eq!(Transaction::new(Vec::new(), 0).size_bytes(), 8);
+		// the data and its length, the sequence number, the sender, the category and the
+		// absent signature
+		assert_eq!(transaction.serialized_len()?, 1 + 100 + 8 + 32 + 1 + 1);
+		assert_eq!(transaction.serialized_len()?, bcs::to_bytes(&transaction)?.len());
+
+		let signed = Transaction::new_signed(vec![7; 100], 1, &SigningKey::from_bytes(&[7; 32]))
+			.with_category(2);
+		assert_eq!(signed.size_bytes(), transaction.size_bytes());
+		assert_eq!(signed.serialized_len()?, bcs::to_bytes(&signed)?.len());
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_ct_eq_agrees_with_eq() {
+		for _ in 0..1_000 {
+			let (a, b) = (rand::random::<[u8; 32]>(), rand::random::<[u8; 32]>());
+			// equal, unrelated, and differing in a single byte
+			let mut c = a;
+			c[rand::random::<usize>() % 32] ^= 1 << (rand::random::<u32>() % 8);
+			for (x, y) in [(a, a), (a, b), (a, c)] {
+				assert_eq!(Id(x).ct_eq(&Id(y)), Id(x) == Id(y));
+				assert_eq!(Commitment(x).ct_eq(&Commitment(y)), Commitment(x) == Commitment(y));
+			}
+		}
+	}
+
+	#[test]
+	fn test_commitment_from_str_rejects_invalid_input() {
+		let too_short = "ab".repeat(31);
+		assert_eq!(too_short.parse::<Commitment>(), Err(hex::FromHexError::InvalidStringLength));
+		assert_eq!("a".repeat(65).parse::<Commitment>(), Err(hex::FromHexError::OddLength));
+		let err = format!("{}g", "0".repeat(63)).parse::<Commitment>().unwrap_err();
+		assert_eq!(err, hex::FromHexError::InvalidHexCharacter { c: 'g', index: 63 });
+		assert_eq!(err.to_string(), "Invalid character 'g' at position 63");
+	}
+
+	#[test]
+	fn test_commitment_try_from_vec_checks_length() -> Result<(), anyhow::Error> {
+		assert!(Commitment::try_from(vec![]).is_err());
+		// undersized input is an error rather than a panic
+		assert!(Commitment::try_from(vec![1; 31]).is_err());
+		assert_eq!(Commitment::try_from(vec![1; 32])?, Commitment([1; 32]));
+		// oversized input is not truncated
+		assert!(Commitment::try_from(vec![1; 33]).is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_id_from_hex_rejects_invalid_input() {
+		assert_eq!(Id::from_hex(&"a".repeat(63)), Err(hex::FromHexError::OddLength));
+		assert!(matches!(
+			Id::from_hex(&format!("zz{}", "0".repeat(62))),
+			Err(hex::FromHexError::InvalidHexCharacter { c: 'z', index: 0 })
+		));
+		// a valid hex string of the wrong length
+		assert_eq!(Id::from_hex("abcd"), Err(hex::FromHexError::InvalidStringLength));
+	}
+
+	#[test]
+	fn test_block_commitment_same_content_ignores_height() {
+		let commitment =
+			BlockCommitment { height: 1, block_id: Id([1; 32]), commitment: Commitment([2; 32]) };
+		let shifted = BlockCommitment { height: 5, ..commitment.clone() };
+
+		assert!(commitment.same_content(&shifted));
+		assert_ne!(commitment, shifted);
+	}
+
+	#[test]
+	fn test_block_commitment_same_content_different_content() {
+		let commitment =
+			BlockCommitment { height: 1, block_id: Id([1; 32]), commitment: Commitment([2; 32]) };
+		let other_block = BlockCommitment { block_id: Id([3; 32]), ..commitment.clone() };
+		let other_state = BlockCommitment { commitment: Commitment([3; 32]), ..commitment.clone() };
+
+		assert!(!commitment.same_content(&other_block));
+		assert!(!commitment.same_content(&other_state));
+	}
+
+	#[test]
+	fn test_atomic_transaction_bundle_parent_height() -> Result<(), anyhow::Error> {
+		let bundle = AtomicTransactionBundle::from(Transaction::test());
+		assert_eq!(bundle.parent_height, None);
+
+		let bundle = AtomicTransactionBundle { parent_height: Some(42), ..bundle };
+		let serialized = serde_json::to_string(&bundle)?;
+		assert_eq!(serde_json::from_str::<AtomicTransactionBundle>(&serialized)?, bundle);
+
+		// bundles serialized before the field existed
+		let legacy = serde_json::json!({
+			"sequencer_id": Id::default(),
+			"transactions": bundle.transactions,
+		});
+		let legacy: AtomicTransactionBundle = serde_json::from_value(legacy)?;
+		assert_eq!(legacy.parent_height, None);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_block_commitment_event_accessors() {
+		let commitment =
+			BlockCommitment { height: 3, block_id: Id([1; 32]), commitment: Commitment([2; 32]) };
+		let accepted = BlockCommitmentEvent::Accepted(commitment.clone());
+		assert_eq!(accepted.height(), 3);
+		assert!(accepted.is_accepted());
+		assert_eq!(accepted.accepted(), Some(&commitment));
+
+		let rejected = BlockCommitmentEvent::Rejected {
+			height: 4,
+			reason: BlockCommitmentRejectionReason::InvalidCommitment,
+		};
+		assert_eq!(rejected.height(), 4);
+		assert!(!rejected.is_accepted());
+		assert_eq!(rejected.accepted(), None);
+	}
+
+	#[test]
+	fn test_validate_parent() {
+		let parent = Block::new_at(4, 1_000, vec![], vec![Transaction::test()]);
+		let block = Block::new_at(5, 2_000, parent.id().to_vec(), vec![]);
+		assert_eq!(block.validate_parent(&parent), Ok(()));
+		// blocks of unknown height are only checked for the link
+		let unknown_height = Block::new(BlockMetadata::default(), parent.id().to_vec(), vec![]);
+		assert_eq!(unknown_height.validate_parent(&parent), Ok(()));
+
+		let other = Block::new_at(4, 1_001, vec![], vec![Transaction::test()]);
+		assert_eq!(
+			block.validate_parent(&other),
+			Err(BlockValidationError::ParentMismatch { parent: other.id() })
+		);
+
+		let skipping = Block::new_at(6, 2_000, parent.id().to_vec(), vec![]);
+		assert_eq!(
+			skipping.validate_parent(&parent),
+			Err(BlockValidationError::HeightGap { parent_height: 4, height: 6 })
+		);
+
+		let highest = Block::new_at(u64::MAX, 1_000, vec![], vec![Transaction::test()]);
+		let past_highest = Block::new_at(1, 2_000, highest.id().to_vec(), vec![]);
+		assert_eq!(
+			past_highest.validate_parent(&highest),
+			Err(BlockValidationError::HeightGap { parent_height: u64::MAX, height: 1 })
+		);
+	}
+
+	#[test]
+	fn test_block_from_bundle() {
+		let transactions: Vec<_> = (0..3).map(|i| Transaction::new(vec![i], i as u64)).collect();
+		let bundle = AtomicTransactionBundle {
+			sequencer_id: Id::from_name("sequencer"),
+			transactions: transactions
+				.iter()
+				.map(|transaction| TransactionEntry {
+					consumer_id: Id::from_name("consumer"),
+					data: transaction.clone(),
+				})
+				.collect(),
+			parent_height: None,
+		};
+		assert_eq!(bundle.clone().into_transactions(), transactions);
+
+		let block = Block::from_bundle(vec![7], bundle.clone());
+		assert_eq!(block.parent, vec![7]);
+		assert_eq!(block.transactions, transactions);
+		assert_eq!(block.metadata.proposer, bundle.sequencer_id);
+		// a bundle of several transactions cannot be sequenced as a single transaction
+		assert!(Transaction::try_from(bundle).is_err());
+	}
+
+	#[test]
+	fn test_bundle_validation() {
+		let sender = Id::from_name("sender");
+		let entry = |sequence_number| TransactionEntry {
+			consumer_id: Id::from_name("consumer"),
+			data: Transaction::new(vec![1], sequence_number).with_sender(sender.clone()),
+		};
+		let bundle = AtomicTransactionBundle {
+			sequencer_id: Id::from_name("sequencer"),
+			transactions: vec![entry(0), entry(1), entry(5)],
+			parent_height: None,
+		};
+		assert_eq!(bundle.validate(), Ok(()));
+
+		// entries leaving the consumer id to the default throughout are consistent
+		let mut anonymous = bundle.clone();
+		for entry in &mut anonymous.transactions {
+			entry.consumer_id = Id::default();
+		}
+		assert_eq!(anonymous.validate(), Ok(()));
+
+		let empty = AtomicTransactionBundle { transactions: Vec::new(), ..bundle.clone() };
+		assert_eq!(empty.validate(), Err(BundleValidationError::Empty));
+
+		let unsigned = AtomicTransactionBundle { sequencer_id: Id::default(), ..bundle.clone() };
+		assert_eq!(unsigned.validate(), Err(BundleValidationError::MissingSequencerId));
+
+		let mut mixed = bundle.clone();
+		mixed.transactions[2].consumer_id = Id::default();
+		assert_eq!(
+			mixed.validate(),
+			Err(BundleValidationError::InconsistentConsumerId { index: 2 })
+		);
+
+		let mut reordered = bundle.clone();
+		reordered.transactions.swap(0, 1);
+		assert_eq!(
+			reordered.validate(),
+			Err(BundleValidationError::SequenceOutOfOrder { index: 1 })
+		);
+
+		let mut duplicated = bundle.clone();
+		duplicated.transactions.push(entry(5));
+		assert_eq!(
+			duplicated.validate(),
+			Err(BundleValidationError::SequenceOutOfOrder { index: 3 })
+		);
+
+		// the order is per sender
+		let mut other_sender = bundle;
+		other_sender.transactions.push(TransactionEntry {
+			consumer_id: Id::from_name("consumer"),
+			data: Transaction::new(vec![2], 0).with_sender(Id::from_name("other")),
+		});
+		assert_eq!(other_sender.validate(), Ok(()));
+	}
+
+	#[test]
+	fn test_payload_round_trip() -> Result<(), anyhow::Error> {
+		#[derive(Serialize, Deserialize, Debug, PartialEq)]
+		struct Transfer {
+			to: Id,
+			amount: u64,
+			memo: String,
+		}
+
+		let payload = Transfer { to: Id([7; 32]), amount: 42, memo: "rent".to_string() };
+		let transaction = Transaction::from_payload(&payload, 3)?;
+		assert_eq!(transaction.sequence_number, 3);
+		assert_eq!(transaction.decode_payload::<Transfer>()?, payload);
+
+		// data that is not exactly a payload of that type is rejected
+		assert!(transaction.decode_payload::<(Id, u64)>().is_err());
+		assert!(Transaction::new(vec![1, 2], 0).decode_payload::<Transfer>().is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_set_data_updates_id() -> Result<(), anyhow::Error> {
+		let mut transaction = Transaction::new(vec![1], 0);
+		let id = transaction.id();
+
+		transaction.set_data(vec![2]);
+		assert_eq!(transaction.data(), [2]);
+		assert_ne!(transaction.id(), id);
+		assert_eq!(transaction.id(), Transaction::new(vec![2], 0).id());
+
+		// the cached id does not outlive a change of the public fields either
+		transaction.sequence_number = 1;
+		assert_eq!(transaction.id(), Transaction::new(vec![2], 1).id());
+		assert_eq!(transaction, Transaction::new(vec![2], 1));
+		let transaction = transaction.with_sender(Id([1; 32])).with_category(2);
+		assert_eq!(
+			transaction.id(),
+			Transaction::new(vec![2], 1).with_sender(Id([1; 32])).with_category(2).id()
+		);
+
+		// the cache is neither serialized nor compared
+		let deserialized: Transaction = serde_json::from_slice(&serde_json::to_vec(&transaction)?)?;
+		assert_eq!(deserialized, transaction);
+		assert_eq!(deserialized.id(), transaction.id());
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_sender_is_committed_in_id() {
+		let transaction = Transaction::new(vec![1], 0);
+		let sent = transaction.clone().with_sender(Id::from_name("sender"));
+		assert_ne!(sent.id(), transaction.id());
+	}
+
+	#[test]
+	fn test_transaction_id_is_unambiguous() {
+		// the sequence number and sender of the victim moved into the data of a transaction
+		// without a sender
+		let sender = Id::from_name("victim");
+		let victim = Transaction::new(vec![1, 2, 3], 5).with_sender(sender.clone());
+		let mut data = vec![1, 2, 3];
+		data.extend_from_slice(&5u64.to_le_bytes());
+		data.extend_from_slice(&sender.0[..24]);
+		let sequence_number = u64::from_le_bytes(sender.0[24..].try_into().unwrap());
+		let forged = Transaction::new(data, sequence_number);
+		assert_ne!(forged.id(), victim.id());
+	}
+
+	#[test]
+	fn test_category_is_committed_in_id() {
+		let transaction = Transaction::new(vec![1], 0);
+		let system = transaction.clone().with_category(1);
+		assert_ne!(system.id(), transaction.id());
+		assert_eq!(transaction.clone().with_category(0).id(), transaction.id());
+
+		// transactions serialized before categories existed belong to the default one
+		let json = r#"{"data":[1],"sequence_number":0,"sender":[0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0]}"#;
+		let legacy: Transaction = serde_json::from_str(json).unwrap();
+		assert_eq!(legacy.category, 0);
+		assert_eq!(legacy.id(), transaction.id());
+	}
+
+	#[test]
+	fn test_derived_ids() {
+		assert_eq!(Id::from_name("sequencer-a"), Id::from_name("sequencer-a"));
+		assert_ne!(Id::from_name("sequencer-a"), Id::from_name("sequencer-b"));
+		assert_ne!(Id::from_name("sequencer-a"), Id::default());
+		assert_eq!(
+			Id::from_name("sequencer-a").0,
+			<[u8; 32]>::from(sha2::Sha256::digest(b"sequencer-a"))
+		);
+
+		let public_key = [7; 33];
+		assert_eq!(Id::from_public_key(&public_key), Id::from_public_key(&public_key));
+		assert_ne!(Id::from_public_key(&public_key), Id::from_public_key(&[8; 33]));
+		assert_ne!(Id::from_public_key(b"sequencer-a"), Id::from_name("sequencer-a"));
+	}
+
+	#[test]
+	fn test_sequence_counter_transactions() {
+		let counter = SequenceCounter::new(5);
+		let transactions: Vec<_> =
+			(0..10).map(|_| Transaction::next_from(&counter, vec![1, 2, 3])).collect();
+
+		for pair in transactions.windows(2) {
+			assert!(pair[0].sequence_number < pair[1].sequence_number);
+		}
+		assert_eq!(transactions[0].sequence_number, 5);
+
+		let ids: HashSet<_> = transactions.iter().map(Transaction::id).collect();
+		assert_eq!(ids.len(), transactions.len());
+	}
+
+	#[tokio::test]
+	async fn test_block_id_commitment_provider_is_stable() -> Result<(), anyhow::Error> {
+		let block = Block::test();
+		let provider = BlockIdCommitmentProvider;
+
+		let commitment = provider.commitment_for(&block).await?;
+		assert_eq!(commitment, provider.commitment_for(&block.clone()).await?);
+
+		let mut other = block.clone();
+		other.add_transaction(Transaction::new(vec![1], 1));
+		assert_ne!(commitment, provider.commitment_for(&other).await?);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_block_header_has_block_id() -> Result<(), anyhow::Error> {
+		let transactions: Vec<_> = (0..100u64).map(|i| Transaction::new(vec![7; 256], i)).collect();
+		let block = Block::new_with_extra(
+			BlockMetadata::default(),
+			vec![1; 32],
+			transactions.clone(),
+			vec![2],
+		);
+
+		let header = block.header();
+		assert_eq!(header.id(), block.id());
+		assert!(header.matches_body(&transactions));
+		assert!(!header.matches_body(&transactions[1..]));
+
+		let header_size = serde_json::to_vec(&header)?.len();
+		let block_size = serde_json::to_vec(&block)?.len();
+		assert!(header_size * 100 < block_size, "header {header_size}, block {block_size}");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_block_height_and_timestamp_in_id() {
+		let transactions = vec![Transaction::new(vec![1], 0)];
+		let at = |height, timestamp_ms| {
+			Block::new_at(height, timestamp_ms, vec![1; 32], transactions.clone())
+		};
+
+		assert_ne!(at(1, 1000).id(), at(2, 1000).id());
+		assert_ne!(at(1, 1000).id(), at(1, 2000).id());
+		assert_eq!(at(1, 1000).id(), at(1, 1000).id());
+		assert_eq!(at(1, 1000).header().id(), at(1, 1000).id());
+		// left unset, the height and timestamp are 0
+		let block = Block::new(BlockMetadata::default(), vec![1; 32], transactions.clone());
+		assert_eq!((block.height, block.timestamp_ms), (0, 0));
+		assert_eq!(at(0, 0).id(), block.id());
+	}
+
+	#[test]
+	fn test_inclusion_proof() {
+		let transactions: Vec<_> = (0..5).map(|i| Transaction::new(vec![i], 0)).collect();
+		let block = Block::new(BlockMetadata::default(), vec![0], transactions.clone());
+		let root = block.merkle_root();
+		assert_eq!(block.header().transactions_root, root);
+
+		let proof = block.inclusion_proof(2).expect("proof");
+		assert!(proof.verify(transactions[2].id(), root.clone()));
+		// another transaction, position or root
+		assert!(!proof.verify(transactions[3].id(), root.clone()));
+		assert!(
+			!MerkleProof { index: 3, ..proof.clone() }.verify(transactions[2].id(), root.clone())
+		);
+		assert!(!proof.verify(transactions[2].id(), Id([1; 32])));
+
+		// every transaction can be proven, including the one carried up unpaired
+		for (index, transaction) in transactions.iter().enumerate() {
+			let proof = block.inclusion_proof(index).expect("proof");
+			assert!(proof.verify(transaction.id(), root.clone()));
+		}
+		assert_eq!(block.inclusion_proof(5), None);
+
+		// the root commits to the order of the transactions
+		let mut reordered = transactions.clone();
+		reordered.swap(0, 1);
+		let reordered = Block::new(BlockMetadata::default(), vec![0], reordered);
+		assert_ne!(reordered.merkle_root(), root);
+		assert_ne!(reordered.id(), block.id());
+
+		// duplicating the unpaired transaction does not yield the same root
+		let mut duplicated = transactions.clone();
+		duplicated.push(transactions[4].clone());
+		let duplicated = Block::new(BlockMetadata::default(), vec![0], duplicated);
+		assert_ne!(duplicated.merkle_root(), root);
+
+		let single = Block::new(BlockMetadata::default(), vec![0], transactions[..1].to_vec());
+		let proof = single.inclusion_proof(0).expect("proof");
+		assert!(proof.siblings.is_empty());
+		assert!(proof.verify(transactions[0].id(), single.merkle_root()));
+	}
+
+	#[test]
+	fn test_empty_heartbeat_block_ids() {
+		let heartbeat =
+			|height, timestamp_ms| Block::new_at(height, timestamp_ms, vec![1; 32], Vec::new());
+
+		// same parent, no transactions, distinct heights or timestamps
+		assert_ne!(heartbeat(1, 1000).id(), heartbeat(2, 1000).id());
+		assert_ne!(heartbeat(1, 1000).id(), heartbeat(1, 2000).id());
+		assert_eq!(heartbeat(1, 1000).id(), heartbeat(1, 1000).id());
+		assert_eq!(heartbeat(1, 1000).header().id(), heartbeat(1, 1000).id());
+
+		// empty blocks without height or timestamp only commit to their parent
+		let empty = Block::new(BlockMetadata::default(), vec![1; 32], Vec::new());
+		assert_eq!(empty.id(), Block::new(BlockMetadata::default(), vec![1; 32], Vec::new()).id());
+		assert_ne!(empty.id(), heartbeat(1, 0).id());
+	}
+
+	#[test]
+	fn test_block_metadata_in_id() {
+		let transactions = vec![Transaction::new(vec![1], 0)];
+		let proposed_by = |proposer| {
+			let metadata = BlockMetadata { proposer, epoch: 2, round: 7 };
+			Block::new(metadata, vec![1; 32], transactions.clone())
+		};
+
+		assert_ne!(proposed_by(Id([1; 32])).id(), proposed_by(Id([2; 32])).id());
+		assert_eq!(proposed_by(Id([1; 32])).id(), proposed_by(Id([1; 32])).id());
+		assert_eq!(proposed_by(Id([1; 32])).header().id(), proposed_by(Id([1; 32])).id());
+		let next_round = Block::new(
+			BlockMetadata { round: 8, ..proposed_by(Id([1; 32])).metadata },
+			vec![1; 32],
+			transactions.clone(),
+		);
+		assert_ne!(next_round.id(), proposed_by(Id([1; 32])).id());
+
+		let block = Block::new(BlockMetadata::default(), vec![1; 32], transactions.clone());
+		assert_ne!(block.id(), proposed_by(Id::default()).id());
+		assert_eq!(block.id(), Block::new_at(0, 0, vec![1; 32], transactions).id());
+	}
+
+	#[test]
+	fn test_block_id_is_unambiguous() {
+		let transactions = vec![Transaction::new(vec![1], 0)];
+		// the height and timestamp of the victim moved into the parent of a block without them
+		let victim = Block::new_at(1, 2, vec![1; 32], transactions.clone());
+		let mut parent = vec![1; 32];
+		parent.extend_from_slice(&1u64.to_le_bytes());
+		parent.extend_from_slice(&2u64.to_le_bytes());
+		let forged = Block::new(BlockMetadata::default(), parent, transactions.clone());
+		assert_ne!(forged.id(), victim.id());
+
+		// the metadata of the victim moved into the parent of a block without metadata
+		let metadata = BlockMetadata { proposer: Id([3; 32]), epoch: 4, round: 5 };
+		let victim = Block::new(metadata.clone(), vec![1; 32], transactions.clone());
+		let mut parent = metadata.id_bytes();
+		parent.extend_from_slice(&[1; 32]);
+		let forged = Block::new(BlockMetadata::default(), parent, transactions);
+		assert_ne!(forged.id(), victim.id());
+	}
+
+	#[test]
+	fn test_block_metadata_serde_round_trip() -> Result<(), anyhow::Error> {
+		let metadata = BlockMetadata { proposer: Id([3; 32]), epoch: 4, round: 5 };
+		let json = serde_json::to_vec(&metadata)?;
+		assert_eq!(serde_json::from_slice::<BlockMetadata>(&json)?, metadata);
+		let bcs = bcs::to_bytes(&metadata)?;
+		assert_eq!(bcs::from_bytes::<BlockMetadata>(&bcs)?, metadata);
+
+		let block = Block::new(metadata, vec![1; 32], vec![Transaction::new(vec![1], 0)]);
+		let decoded: Block = serde_json::from_slice(&serde_json::to_vec(&block)?)?;
+		assert_eq!(decoded, block);
+		assert_eq!(decoded.id(), block.id());
+
+		// fields missing from the encoding default to zero
+		let partial: BlockMetadata = serde_json::from_str(r#"{"epoch":4}"#)?;
+		assert_eq!(partial, BlockMetadata { epoch: 4, ..Default::default() });
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_split_by_bytes() -> Result<(), anyhow::Error> {
+		let transactions: Vec<_> = (0..20u64).map(|i| Transaction::new(vec![7; 100], i)).collect();
+		let block = Block::new(BlockMetadata::default(), vec![1; 32], transactions.clone());
+		let max_bytes = 2000;
+		assert!(serde_json::to_vec(&block)?.len() as u64 > max_bytes);
+
+		let blocks = block.split_by_bytes(max_bytes);
+		assert!(blocks.len() > 1);
+		assert_eq!(blocks[0].parent, vec![1; 32]);
+		for block in &blocks {
+			assert!(serde_json::to_vec(block)?.len() as u64 <= max_bytes);
+		}
+		for pair in blocks.windows(2) {
+			assert_eq!(pair[1].parent, pair[0].id().to_vec());
+			assert_eq!(pair[1].height, 0);
+			// sub-blocks are filled greedily
+			let mut grown = pair[0].clone();
+			grown.add_transaction(pair[1].transactions[0].clone());
+			assert!(serde_json::to_vec(&grown)?.len() as u64 > max_bytes);
+		}
+		let rejoined: Vec<_> = blocks.into_iter().flat_map(|block| block.transactions).collect();
+		assert_eq!(rejoined, transactions);
+
+		// an oversized transaction gets a sub-block of its own
+		let oversized = Transaction::new(vec![7; 4000], 0);
+		let block = Block::new(
+			BlockMetadata::default(),
+			vec![1; 32],
+			vec![Transaction::test(), oversized.clone(), Transaction::test()],
+		);
+		let blocks = block.split_by_bytes(max_bytes);
+		assert_eq!(blocks.len(), 3);
+		assert_eq!(blocks[1].transactions, vec![oversized]);
+
+		// sub-blocks of a block at a known height chain at consecutive heights
+		let block = Block::new_at(5, 1000, vec![1; 32], transactions);
+		let blocks = block.split_by_bytes(max_bytes);
+		assert!(blocks.len() > 1);
+		for (offset, block) in blocks.iter().enumerate() {
+			assert_eq!(block.height, 5 + offset as u64);
+			assert_eq!(block.timestamp_ms, 1000);
+		}
+		for pair in blocks.windows(2) {
+			assert_eq!(pair[1].validate_parent(&pair[0]), Ok(()));
+		}
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_block_diff_overlapping_blocks() {
+		let shared = Transaction::new(vec![1], 0);
+		let only_a = Transaction::new(vec![2], 0);
+		let only_b = Transaction::new(vec![3], 0);
+		let block_a =
+			Block::new(BlockMetadata::default(), vec![0], vec![shared.clone(), only_a.clone()]);
+		let block_b = Block::new(BlockMetadata::default(), vec![0], vec![only_b.clone(), shared]);
+
+		let diff = block_a.diff(&block_b);
+		assert_eq!(diff, BlockDiff { added: vec![only_b.id()], removed: vec![only_a.id()] });
+
+		let reverse = block_b.diff(&block_a);
+		assert_eq!(reverse, BlockDiff { added: vec![only_a.id()], removed: vec![only_b.id()] });
+
+		assert!(block_a.diff(&block_a).is_empty());
+	}
+}